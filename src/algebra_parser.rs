@@ -1,56 +1,127 @@
+//! Turns expression text into a [`Node`] tree and evaluates it.
+//!
+//! Text goes through the lexical analyzer, which splits it into tokens, and the syntax
+//! analyzer, which builds the tree. [`evaluator`] then computes values from a tree.
 use itertools::Itertools;
 use std::backtrace::Backtrace;
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::fmt::{self, Debug};
 // Lexical Analysis Errors
 const PROCEDURE_SYNTAX_ERROR: &str = "Attempted to locate procedure: Unsucessful";
 const PARENTHESIS_ASSIGN_ERROR: &str = "Could not find correspondence for every parenthesis";
+const UNKNOWN_CHARACTER_ERROR: &str = "Encountered a character that is not part of any token";
+const NUMBER_FORMAT_ERROR: &str = "Could not read number";
+const BLACKLISTED_TERM_ERROR: &str = "Expression uses a term that is not allowed here";
 //
-#[derive(Debug)]
+// Syntax Analysis Errors
+const EMPTY_EXPRESSION_ERROR: &str = "Expression is empty";
+const UNEXPECTED_TOKEN_ERROR: &str = "Unexpected token";
+const UNEXPECTED_END_ERROR: &str = "Expression ended unexpectedly";
+//
+// Evaluation Errors
+const UNDEFINED_VARIABLE_ERROR: &str = "Variable has no value";
+const UNDEFINED_FUNCTION_ERROR: &str = "Function does not exist";
+const ARGUMENT_COUNT_ERROR: &str = "Function was called with the wrong number of arguments";
+const TUPLE_VALUE_ERROR: &str = "A tuple cannot be used as a single number";
+const LIST_VALUE_ERROR: &str = "A list cannot be used as a single number";
+//
+const LIST_ARGUMENT_ERROR: &str = "Function needs a list";
+//
+const BUILTIN_FUNCTIONS: [&str; 24] = [
+    "arcsin", "arccos", "arctan", "asin", "acos", "atan", "sinh", "cosh", "tanh", "sin", "cos",
+    "tan", "sec", "csc", "cot", "sqrt", "abs", "ln", "log", "exp", "floor", "ceil", "round",
+    "sign",
+];
+// Functions over lists, see the statistics module
+pub(crate) const LIST_FUNCTIONS: [&str; 13] = [
+    "mean",
+    "median",
+    "mode",
+    "variance",
+    "stdev",
+    "quantile",
+    "min",
+    "max",
+    "sum",
+    "sort",
+    "corr",
+    "histogram",
+    "boxplot",
+];
+/// Names that are read as a number instead of a variable.
+pub const CONSTANTS: [&str; 3] = ["pi", "π", "e"];
+const KEYWORDS: [&str; 2] = ["and", "or"];
+#[derive(Debug, Clone, PartialEq)]
 #[repr(i32)]
-enum Tokens<'a> {
+pub(crate) enum Tokens<'a> {
     ParenthesisLeft,
     ParenthesisRight,
-    Function,
+    Function(&'a str),
     Exponent,
     Mul,
     Div,
     Add,
     Sub,
-    Procedure(i32, Box<(HashMap<&'a str, String>, Tokens<'a>)>),
-    Variable(i32),
-    Number(i32),
+    Comma,
+    BraceLeft,
+    BraceRight,
+    BracketLeft,
+    BracketRight,
+    Colon,
+    Variable(&'a str),
+    Number(f64),
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
 }
-enum FilterType {
+pub(crate) enum FilterType {
     WhiteList,
+    // Nothing outside the tests forbids names yet
+    #[allow(dead_code)]
     BlackList,
 }
-struct FilterList<'a> {
-    list_type: FilterType,
-    list: Vec<&'a str>,
+// WhiteList: multi-letter names that should be read as one term instead of a product of letters
+// BlackList: names that may not appear at all
+pub(crate) struct FilterList<'a> {
+    pub list_type: FilterType,
+    pub list: Vec<&'a str>,
 }
 #[derive(Debug)]
-struct TracebackWrapper<'a, T> {
-    err: Result<T, &'a str>,
-    traceback: String,
+pub(crate) struct TracebackWrapper<'a, T> {
+    pub err: Result<T, &'a str>,
+    // Only shown when the wrapper is printed with `{:?}`
+    #[allow(dead_code)]
+    pub traceback: String,
 }
-enum TraceExists<'a, T> {
+pub(crate) enum TraceExists<'a, T> {
     Wrapper(TracebackWrapper<'a, T>),
     Success(T),
 }
+impl<'a, T> TraceExists<'a, T> {
+    #[allow(dead_code)]
+    pub fn into_result(self) -> Result<T, &'a str> {
+        match self {
+            TraceExists::Success(x) => Ok(x),
+            TraceExists::Wrapper(x) => x.err,
+        }
+    }
+}
 macro_rules! backtrace_wrapper {
-    ($backtrace_wrapper:ident, $name:ty) => {
-        let mut error_store: Vec<TraceExists<'a, $name>> = Vec::new();
+    ($backtrace_wrapper:ident, $error_store:ident, $name:ty) => {
+        let mut $error_store: Vec<TraceExists<'a, $name>> = Vec::new();
         let mut $backtrace_wrapper = |err: Result<$name, &'a str>| {
-            error_store.push(TraceExists::Wrapper(TracebackWrapper {
-                err: err,
+            $error_store.push(TraceExists::Wrapper(TracebackWrapper {
+                err,
                 traceback: Backtrace::force_capture().to_string(),
             }));
         };
     };
 }
 type LexicalTracerType<'a> = HashMap<&'a str, Vec<Tokens<'a>>>;
-pub mod lexical_analyzer {
+mod lexical_analyzer {
     use super::*;
     pub fn clean<'a>(function_map: &HashMap<&'a str, String>) -> HashMap<&'a str, String> {
         function_map
@@ -58,152 +129,147 @@ pub mod lexical_analyzer {
             .map(|(&name, function)| (name, function.replace(" ", "")))
             .collect::<HashMap<&str, String>>()
     }
+    fn is_builtin(term: &str) -> bool {
+        BUILTIN_FUNCTIONS.contains(&term) || LIST_FUNCTIONS.contains(&term)
+    }
     pub fn analyze<'a>(
-        function_map: &HashMap<&'a str, String>,
-        filter_list: Option<FilterList>,
+        function_map: &'a HashMap<&'a str, String>,
+        filter_list: Option<FilterList<'a>>,
     ) -> TraceExists<'a, LexicalTracerType<'a>> {
-        backtrace_wrapper!(lexical_tracer, LexicalTracerType<'a>);
+        backtrace_wrapper!(lexical_tracer, error_store, LexicalTracerType<'a>);
+        let (white_list, black_list) = match filter_list {
+            Some(FilterList {
+                list_type: FilterType::WhiteList,
+                list,
+            }) => (list, Vec::new()),
+            Some(FilterList {
+                list_type: FilterType::BlackList,
+                list,
+            }) => (Vec::new(), list),
+            None => (Vec::new(), Vec::new()),
+        };
         let mut tokenized_map = HashMap::new();
         for (function, expression) in function_map.iter() {
             let mut vector = Vec::new();
-            let mut expression_iterator = expression.chars().enumerate();
             let mut parenthesis_check = 0;
-            let analyze = |position: usize, char: char| {};
+            let mut expression_iterator = expression.char_indices().peekable();
             while let Some((position, char)) = expression_iterator.next() {
-                if (char == '(') {
+                if char == '(' || char == '{' || char == '[' {
                     parenthesis_check += 1;
-                } else if (char == ')') {
+                } else if char == ')' || char == '}' || char == ']' {
                     parenthesis_check -= 1;
                 }
-                let mut token = None;
-                if (char.is_numeric()) {
-                    let mut consecutive_digit = 0;
-                    let mut encountered_alphabet = false;
-                    expression
-                        .chars()
-                        .skip(position)
-                        .take_while(|x| {
-                            consecutive_digit += 1;
-                            expression_iterator.next();
-                            let is_alphabetic = x.is_alphabetic();
-                            if (is_alphabetic) {
-                                encountered_alphabet = true;
+                if parenthesis_check < 0 {
+                    break;
+                }
+                if char.is_numeric() || (char == '.') {
+                    let mut end = position + char.len_utf8();
+                    while let Some(&(next_position, next)) = expression_iterator.peek() {
+                        if !(next.is_numeric() || (next == '.')) {
+                            break;
+                        }
+                        end = next_position + next.len_utf8();
+                        expression_iterator.next();
+                    }
+                    // An exponent is only part of the number when digits follow it, so 2e is still 2*e
+                    let rest = &expression[end..];
+                    if let Some(exponent) = rest.strip_prefix(['e', 'E']) {
+                        let digits = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+                        let length = digits.len()
+                            - digits
+                                .trim_start_matches(|x: char| x.is_ascii_digit())
+                                .len();
+                        if length > 0 {
+                            let taken = rest.len() - digits.len() + length;
+                            end += taken;
+                            for _ in 0..taken {
+                                expression_iterator.next();
                             }
-                            (x.is_numeric() || (*x == '.'))
-                                || (encountered_alphabet && !(is_alphabetic))
-                        })
-                        .collect::<String>();
-                    token = Some(Tokens::Number(consecutive_digit));
-                } else if (char.is_alphabetic()) {
-                    let mut name_length = 0;
-                    let mut invalid_flag = false;
-                    let mut variables_iteration = 0;
-                    let mut variables_iter = expression.chars().multipeek();
-                    while let Some(x) = variables_iter.next() {
-                        variables_iteration += 1;
-                        if (variables_iteration < position) {
-                            continue;
                         }
-                        name_length += 1;
-                        let is_alphabetic = x.is_alphabetic();
-                        if (!is_alphabetic) {
-                            if let Some(i) = self::match_operation(x) {
-                                token = Some(Tokens::Variable(name_length - 1));
-                            } else if (x == ')') {
-                                break;
-                            } else {
-                                let f1 = (x == '(');
-                                let f2 = {
-                                    let mut temp_parenthesis_check = 0;
-                                    let mut is_first_iteration = true;
-                                    let mut break_flag = false;
-                                    let mut match_success = false;
-                                    let mut n_peek = variables_iter.peek();
-                                    let nested_functions: Vec<&str> = Vec::new();
-                                    while (!is_first_iteration && !break_flag) {
-                                        if (is_first_iteration) {
-                                            is_first_iteration = false;
-                                        } else if (temp_parenthesis_check <= 0) {
-                                            match_success = true;
-                                            break;
-                                        }
-                                        match n_peek {
-                                            Some(v) => {
-                                                if (*v == '(') {
-                                                    temp_parenthesis_check += 1;
-                                                    let mut rev_find_variable = expression
-                                                        .chars()
-                                                        .rev()
-                                                        .skip(expression.len() - position);
-                                                    let mut rev_iteration = 0;
-                                                    let mut flag = false;
-                                                    let mut name =
-                                                        String::with_capacity(expression.len());
-                                                    while let Some(w) = rev_find_variable.next() {
-                                                        rev_iteration += 1;
-                                                        if (!w.is_alphabetic()) {
-                                                            if (self::match_operation(w).is_some())
-                                                            {
-                                                                if (rev_iteration > 1) {
-                                                                    flag = true;
-                                                                }
-                                                            }
-                                                            break;
-                                                        } else {
-                                                            name.push(w);
-                                                        }
-                                                    }
-                                                    if (flag) {}
-                                                } else if (*v == ')') {
-                                                    temp_parenthesis_check -= 1;
-                                                }
-                                            }
-                                            None => {
-                                                break_flag = true;
-                                            }
-                                        }
-                                        n_peek = variables_iter.peek();
-                                    }
-                                    match_success || break_flag
-                                };
-                                if !(f1 && f2) {
-                                    invalid_flag = true;
-                                    break;
-                                }
-                                // token = Some(Tokens::Procedure(name_length));
-                                break;
-                            };
+                    }
+                    match expression[position..end].parse::<f64>() {
+                        Ok(x) => vector.push(Tokens::Number(x)),
+                        Err(_) => {
+                            lexical_tracer(Err(NUMBER_FORMAT_ERROR));
+                            break;
                         }
+                    }
+                } else if char.is_alphabetic() {
+                    let mut end = position + char.len_utf8();
+                    while let Some(&(next_position, next)) = expression_iterator.peek() {
+                        if !next.is_alphabetic() {
+                            break;
+                        }
+                        end = next_position + next.len_utf8();
                         expression_iterator.next();
-                        if (x.is_alphabetic()) {
-                            continue;
+                    }
+                    let is_call = expression_iterator.peek().map(|x| x.1) == Some('(');
+                    let terms = split_terms(&expression[position..end], &white_list);
+                    let term_count = terms.len();
+                    for (index, term) in terms.into_iter().enumerate() {
+                        if black_list.contains(&term) {
+                            lexical_tracer(Err(BLACKLISTED_TERM_ERROR));
+                        }
+                        let is_last = index + 1 == term_count;
+                        let is_function =
+                            is_builtin(term) || (is_last && is_call && white_list.contains(&term));
+                        match (term, is_function) {
+                            ("and", _) => vector.push(Tokens::And),
+                            ("or", _) => vector.push(Tokens::Or),
+                            (_, true) => vector.push(Tokens::Function(term)),
+                            (_, false) => vector.push(Tokens::Variable(term)),
                         }
-                        break;
                     }
-                    if (invalid_flag) {
-                        lexical_tracer(Err(PROCEDURE_SYNTAX_ERROR));
+                } else if let Some(token) = match_comparison(char) {
+                    // `<=` and `>=` are two characters wide
+                    let or_equal = expression_iterator.peek().map(|x| x.1) == Some('=');
+                    if or_equal {
+                        expression_iterator.next();
                     }
-                } else {
-                    token = match_operation(char);
-                }
-                match token {
-                    Some(x) => {
-                        vector.push(x);
+                    match (token, or_equal) {
+                        (Tokens::Less, true) => vector.push(Tokens::LessEqual),
+                        (Tokens::Greater, true) => vector.push(Tokens::GreaterEqual),
+                        (token, _) => vector.push(token),
                     }
-                    None => println!("Well that was a dud!"),
+                } else if let Some(token) = match_operation(char) {
+                    vector.push(token);
+                } else {
+                    lexical_tracer(Err(UNKNOWN_CHARACTER_ERROR));
+                    break;
                 }
             }
-            if (parenthesis_check != 0) {
+            if parenthesis_check != 0 {
                 lexical_tracer(Err(PARENTHESIS_ASSIGN_ERROR));
             }
             tokenized_map.insert(*function, vector);
         }
-        return TraceExists::Success(tokenized_map);
+        match error_store.pop() {
+            Some(x) => x,
+            None => TraceExists::Success(tokenized_map),
+        }
     }
-    pub fn get_terms<'a>(tokenized_map: HashMap<&'a str, Vec<Tokens>>) -> Vec<&'a str> {
-        // After lexical analysis has been successful, retrieve terms
-        let vector = Vec::new();
-        vector
+    // Splits a run of letters into terms, preferring the longest known name at every step.
+    // Anything unknown is read one letter at a time so that `ab` means `a*b`
+    fn split_terms<'a>(name: &'a str, white_list: &[&'a str]) -> Vec<&'a str> {
+        let known = BUILTIN_FUNCTIONS
+            .iter()
+            .chain(LIST_FUNCTIONS.iter())
+            .chain(CONSTANTS.iter())
+            .chain(KEYWORDS.iter())
+            .chain(white_list.iter())
+            .sorted_by_key(|x| std::cmp::Reverse(x.len()))
+            .collect::<Vec<&&str>>();
+        let mut terms = Vec::new();
+        let mut rest = name;
+        while let Some(first) = rest.chars().next() {
+            let length = match known.iter().find(|x| rest.starts_with(**x)) {
+                Some(x) => x.len(),
+                None => first.len_utf8(),
+            };
+            terms.push(&rest[..length]);
+            rest = &rest[length..];
+        }
+        terms
     }
     fn match_operation<'a>(operation: char) -> Option<Tokens<'a>> {
         match operation {
@@ -214,45 +280,1029 @@ pub mod lexical_analyzer {
             '/' => Some(Tokens::Div),
             '+' => Some(Tokens::Add),
             '-' => Some(Tokens::Sub),
+            ',' => Some(Tokens::Comma),
+            '{' => Some(Tokens::BraceLeft),
+            '}' => Some(Tokens::BraceRight),
+            '[' => Some(Tokens::BracketLeft),
+            ']' => Some(Tokens::BracketRight),
+            ':' => Some(Tokens::Colon),
+            _ => None,
+        }
+    }
+    fn match_comparison<'a>(comparison: char) -> Option<Tokens<'a>> {
+        match comparison {
+            '<' => Some(Tokens::Less),
+            '>' => Some(Tokens::Greater),
+            '≤' => Some(Tokens::LessEqual),
+            '≥' => Some(Tokens::GreaterEqual),
             _ => None,
         }
     }
-    fn match_token_to_priority(operation: Tokens) -> f32 {
+    pub fn match_token_to_priority(operation: &Tokens) -> f32 {
         match operation {
-            Tokens::ParenthesisLeft => 61 as f32,
-            Tokens::ParenthesisRight => 60 as f32,
-            Tokens::Function => 50 as f32,
-            Tokens::Exponent => 40 as f32,
-            Tokens::Mul => 31 as f32,
-            Tokens::Div => 30 as f32,
-            Tokens::Add => 20 as f32,
-            Tokens::Sub => 10 as f32,
-            Tokens::Procedure(x, y) => 3 as f32,
-            Tokens::Variable(x) => 2 as f32,
-            Tokens::Number(x) => 1 as f32,
+            Tokens::ParenthesisLeft | Tokens::BraceLeft | Tokens::BracketLeft => 61.0,
+            Tokens::ParenthesisRight | Tokens::BraceRight | Tokens::BracketRight => 60.0,
+            Tokens::Function(_) => 50.0,
+            Tokens::Exponent => 40.0,
+            Tokens::Mul => 31.0,
+            Tokens::Div => 30.0,
+            Tokens::Add => 21.0,
+            Tokens::Sub => 20.0,
+            Tokens::Less | Tokens::LessEqual | Tokens::Greater | Tokens::GreaterEqual => 15.0,
+            Tokens::And => 11.0,
+            Tokens::Or => 10.0,
+            Tokens::Comma => 4.0,
+            Tokens::Colon => 3.0,
+            Tokens::Variable(_) => 2.0,
+            Tokens::Number(_) => 1.0,
         }
     }
-    fn token_is_less_than(a: Tokens, b: Tokens) -> bool {
-        (match_token_to_priority(a) / 10.0).floor() < (match_token_to_priority(b) / 10.0).floor()
+}
+/// A binary arithmetic operator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    /// `a + b`
+    Add,
+    /// `a - b`
+    Sub,
+    /// `a * b`, also written by putting terms next to each other as in `2x`
+    Mul,
+    /// `a / b`
+    Div,
+    /// `a ^ b`
+    Exponent,
+}
+/// A comparison inside a condition, `≤` and `≥` can also be typed as `<=` and `>=`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    /// `a < b`
+    Less,
+    /// `a ≤ b`
+    LessEqual,
+    /// `a > b`
+    Greater,
+    /// `a ≥ b`
+    GreaterEqual,
+}
+impl Comparison {
+    /// Strict comparisons leave their boundary out of the region
+    pub fn is_strict(&self) -> bool {
+        matches!(self, Comparison::Less | Comparison::Greater)
     }
-    fn token_is_greater_than(a: Tokens, b: Tokens) -> bool {
-        (match_token_to_priority(a) / 10.0).floor() > (match_token_to_priority(b) / 10.0).floor()
+    /// Whether `a` compared to `b` is true.
+    pub fn holds(&self, a: f64, b: f64) -> bool {
+        match self {
+            Comparison::Less => a < b,
+            Comparison::LessEqual => a <= b,
+            Comparison::Greater => a > b,
+            Comparison::GreaterEqual => a >= b,
+        }
     }
 }
-#[cfg(test)]
-mod test {
+/// A parsed expression.
+///
+/// `Display` prints it back as text that parses to the same tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// A number literal or a constant such as `pi`
+    Number(f64),
+    /// A variable, list or slider name
+    Variable(String),
+    /// `-x`
+    Negate(Box<Node>),
+    /// `a <operator> b`
+    Operation(Operator, Box<Node>, Box<Node>),
+    /// A builtin, list or user defined function with its arguments
+    Call(String, Vec<Node>),
+    /// `(x(t), y(t))`, only meaningful as a whole expression
+    Tuple(Vec<Node>),
+    /// Conditions evaluate to 1 when they hold and 0 otherwise
+    Compare(Comparison, Box<Node>, Box<Node>),
+    /// `a and b`
+    And(Box<Node>, Box<Node>),
+    /// `a or b`
+    Or(Box<Node>, Box<Node>),
+    /// `{condition: value, ...}`, the first piece whose condition holds gives the value
+    Piecewise(Vec<(Node, Node)>),
+    /// `[a, b, ...]`, e.g. a list of points to plot
+    List(Vec<Node>),
+}
+impl Node {
+    /// Every variable the expression reads, in order of first appearance
+    pub fn get_variables(&self) -> Vec<String> {
+        let mut variables = Vec::new();
+        self.collect_variables(&mut variables);
+        variables
+    }
+    /// Every comparison inside a condition, these are the boundaries of its region
+    pub fn get_comparisons(&self) -> Vec<(Comparison, &Node, &Node)> {
+        match self {
+            Node::Compare(comparison, a, b) => vec![(*comparison, a.as_ref(), b.as_ref())],
+            Node::And(a, b) | Node::Or(a, b) => {
+                let mut comparisons = a.get_comparisons();
+                comparisons.extend(b.get_comparisons());
+                comparisons
+            }
+            _ => Vec::new(),
+        }
+    }
+    /// Replaces the variables that have a value with that value
+    pub fn substitute(&self, values: &HashMap<String, f64>) -> Node {
+        let all = |nodes: &Vec<Node>| nodes.iter().map(|x| x.substitute(values)).collect();
+        let both = |a: &Node, b: &Node| {
+            (
+                Box::new(a.substitute(values)),
+                Box::new(b.substitute(values)),
+            )
+        };
+        match self {
+            Node::Number(x) => Node::Number(*x),
+            Node::Variable(name) => match values.get(name) {
+                Some(x) => Node::Number(*x),
+                None => Node::Variable(name.clone()),
+            },
+            Node::Negate(x) => Node::Negate(Box::new(x.substitute(values))),
+            Node::Operation(operator, a, b) => {
+                let (a, b) = both(a, b);
+                Node::Operation(*operator, a, b)
+            }
+            Node::Call(name, arguments) => Node::Call(name.clone(), all(arguments)),
+            Node::Tuple(components) => Node::Tuple(all(components)),
+            Node::List(items) => Node::List(all(items)),
+            Node::Compare(comparison, a, b) => {
+                let (a, b) = both(a, b);
+                Node::Compare(*comparison, a, b)
+            }
+            Node::And(a, b) => {
+                let (a, b) = both(a, b);
+                Node::And(a, b)
+            }
+            Node::Or(a, b) => {
+                let (a, b) = both(a, b);
+                Node::Or(a, b)
+            }
+            Node::Piecewise(pieces) => Node::Piecewise(
+                pieces
+                    .iter()
+                    .map(|(condition, value)| {
+                        (condition.substitute(values), value.substitute(values))
+                    })
+                    .collect(),
+            ),
+        }
+    }
+    // How tightly the node binds when printed, children that bind looser get parentheses
+    fn get_precedence(&self) -> i32 {
+        match self {
+            Node::Or(..) => 1,
+            Node::And(..) => 2,
+            Node::Compare(..) => 3,
+            Node::Operation(Operator::Add | Operator::Sub, ..) => 4,
+            Node::Operation(Operator::Mul | Operator::Div, ..) | Node::Negate(_) => 5,
+            Node::Number(x) if (*x < 0.0) => 5,
+            Node::Operation(Operator::Exponent, ..) => 7,
+            _ => 9,
+        }
+    }
+    fn collect_variables(&self, variables: &mut Vec<String>) {
+        match self {
+            Node::Number(_) => {}
+            Node::Variable(x) => {
+                if !variables.contains(x) {
+                    variables.push(x.clone());
+                }
+            }
+            Node::Negate(x) => x.collect_variables(variables),
+            Node::Operation(_, a, b)
+            | Node::Compare(_, a, b)
+            | Node::And(a, b)
+            | Node::Or(a, b) => {
+                a.collect_variables(variables);
+                b.collect_variables(variables);
+            }
+            Node::Call(_, arguments) | Node::Tuple(arguments) | Node::List(arguments) => {
+                for argument in arguments.iter() {
+                    argument.collect_variables(variables);
+                }
+            }
+            Node::Piecewise(pieces) => {
+                for (condition, value) in pieces.iter() {
+                    condition.collect_variables(variables);
+                    value.collect_variables(variables);
+                }
+            }
+        }
+    }
+}
+// Prints an expression the parser reads back as the same tree
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let wrap = |node: &Node, minimum: i32| match node.get_precedence() < minimum {
+            true => format!("({})", node),
+            false => node.to_string(),
+        };
+        let join = |nodes: &Vec<Node>| nodes.iter().map(|x| x.to_string()).join(", ");
+        match self {
+            Node::Number(x) => write!(f, "{}", x),
+            Node::Variable(name) => write!(f, "{}", name),
+            Node::Negate(x) => write!(f, "-{}", wrap(x, 5)),
+            Node::Operation(operator, a, b) => {
+                let (symbol, left, right) = match operator {
+                    Operator::Add => ("+", 4, 5),
+                    Operator::Sub => ("-", 4, 5),
+                    Operator::Mul => ("*", 5, 6),
+                    Operator::Div => ("/", 5, 6),
+                    Operator::Exponent => ("^", 8, 7),
+                };
+                match (operator, b.as_ref()) {
+                    // `a + -2` reads better as `a - 2`
+                    (Operator::Add, Node::Number(x)) if (*x < 0.0) => {
+                        write!(f, "{} - {}", wrap(a, left), -x)
+                    }
+                    (Operator::Add | Operator::Sub, _) => {
+                        write!(f, "{} {} {}", wrap(a, left), symbol, wrap(b, right))
+                    }
+                    _ => write!(f, "{}{}{}", wrap(a, left), symbol, wrap(b, right)),
+                }
+            }
+            Node::Call(name, arguments) => write!(f, "{}({})", name, join(arguments)),
+            Node::Tuple(components) => write!(f, "({})", join(components)),
+            Node::List(items) => write!(f, "[{}]", join(items)),
+            Node::Compare(comparison, a, b) => {
+                let symbol = match comparison {
+                    Comparison::Less => "<",
+                    Comparison::LessEqual => "<=",
+                    Comparison::Greater => ">",
+                    Comparison::GreaterEqual => ">=",
+                };
+                write!(f, "{} {} {}", wrap(a, 4), symbol, wrap(b, 4))
+            }
+            Node::And(a, b) => write!(f, "{} and {}", wrap(a, 2), wrap(b, 3)),
+            Node::Or(a, b) => write!(f, "{} or {}", wrap(a, 1), wrap(b, 2)),
+            Node::Piecewise(pieces) => {
+                let pieces = pieces
+                    .iter()
+                    .map(|(condition, value)| format!("{}: {}", condition, value))
+                    .join(", ");
+                write!(f, "{{{}}}", pieces)
+            }
+        }
+    }
+}
+mod syntax_analyzer {
+    use super::lexical_analyzer::match_token_to_priority;
     use super::*;
-    #[test]
-    fn lexical_analysis() {}
+    fn priority_class(token: &Tokens) -> i32 {
+        (match_token_to_priority(token) / 10.0).floor() as i32
+    }
+    fn match_token_to_comparison(token: &Tokens) -> Option<Comparison> {
+        match token {
+            Tokens::Less => Some(Comparison::Less),
+            Tokens::LessEqual => Some(Comparison::LessEqual),
+            Tokens::Greater => Some(Comparison::Greater),
+            Tokens::GreaterEqual => Some(Comparison::GreaterEqual),
+            _ => None,
+        }
+    }
+    fn match_token_to_operator(token: &Tokens) -> Option<Operator> {
+        match token {
+            Tokens::Add => Some(Operator::Add),
+            Tokens::Sub => Some(Operator::Sub),
+            Tokens::Mul => Some(Operator::Mul),
+            Tokens::Div => Some(Operator::Div),
+            Tokens::Exponent => Some(Operator::Exponent),
+            _ => None,
+        }
+    }
+    // Tokens that can begin a factor, used to detect implicit multiplication such as `2x`
+    fn starts_factor(token: &Tokens) -> bool {
+        matches!(
+            token,
+            Tokens::Number(_)
+                | Tokens::Variable(_)
+                | Tokens::Function(_)
+                | Tokens::ParenthesisLeft
+                | Tokens::BraceLeft
+        )
+    }
+    struct Parser<'b, 'a> {
+        tokens: &'b [Tokens<'a>],
+        position: usize,
+    }
+    impl<'b, 'a> Parser<'b, 'a> {
+        fn peek(&self) -> Option<&'b Tokens<'a>> {
+            self.tokens.get(self.position)
+        }
+        fn next(&mut self) -> Option<&'b Tokens<'a>> {
+            self.position += 1;
+            self.tokens.get(self.position - 1)
+        }
+        fn expect(&mut self, token: Tokens) -> Result<(), &'static str> {
+            match self.next() {
+                Some(x) if (*x == token) => Ok(()),
+                Some(_) => Err(UNEXPECTED_TOKEN_ERROR),
+                None => Err(UNEXPECTED_END_ERROR),
+            }
+        }
+        // Conditions sit below arithmetic, `or` binds loosest, then `and`, then comparisons
+        fn parse_condition(&mut self) -> Result<Node, &'static str> {
+            let mut left = self.parse_conjunction()?;
+            while let Some(Tokens::Or) = self.peek() {
+                self.next();
+                let right = self.parse_conjunction()?;
+                left = Node::Or(Box::new(left), Box::new(right));
+            }
+            Ok(left)
+        }
+        fn parse_conjunction(&mut self) -> Result<Node, &'static str> {
+            let mut left = self.parse_comparison()?;
+            while let Some(Tokens::And) = self.peek() {
+                self.next();
+                let right = self.parse_comparison()?;
+                left = Node::And(Box::new(left), Box::new(right));
+            }
+            Ok(left)
+        }
+        // `0 < x < 1` reads as `0 < x and x < 1`
+        fn parse_comparison(&mut self) -> Result<Node, &'static str> {
+            let first = self.parse_expression(0)?;
+            let mut left = first.clone();
+            let mut comparisons = Vec::new();
+            while let Some(comparison) = self.peek().and_then(match_token_to_comparison) {
+                self.next();
+                let right = self.parse_expression(0)?;
+                comparisons.push(Node::Compare(
+                    comparison,
+                    Box::new(left),
+                    Box::new(right.clone()),
+                ));
+                left = right;
+            }
+            Ok(comparisons
+                .into_iter()
+                .reduce(|a, b| Node::And(Box::new(a), Box::new(b)))
+                .unwrap_or(first))
+        }
+        // `{x < 0: -x, x^2}`, a last piece without a condition covers everything else
+        fn parse_piecewise(&mut self) -> Result<Node, &'static str> {
+            let mut pieces = Vec::new();
+            loop {
+                let first = self.parse_condition()?;
+                match self.peek() {
+                    Some(Tokens::Colon) => {
+                        self.next();
+                        pieces.push((first, self.parse_condition()?));
+                    }
+                    _ => {
+                        pieces.push((Node::Number(1.0), first));
+                        self.expect(Tokens::BraceRight)?;
+                        break;
+                    }
+                }
+                match self.next() {
+                    Some(Tokens::Comma) => continue,
+                    Some(Tokens::BraceRight) => break,
+                    Some(_) => return Err(UNEXPECTED_TOKEN_ERROR),
+                    None => return Err(UNEXPECTED_END_ERROR),
+                }
+            }
+            Ok(Node::Piecewise(pieces))
+        }
+        // Precedence climbing over the priority classes of `match_token_to_priority`
+        fn parse_expression(&mut self, min_class: i32) -> Result<Node, &'static str> {
+            let mut left = self.parse_unary()?;
+            while let Some(token) = self.peek() {
+                let (operator, class) = match match_token_to_operator(token) {
+                    Some(x) => (x, priority_class(token)),
+                    None if starts_factor(token) => (Operator::Mul, priority_class(&Tokens::Mul)),
+                    None => break,
+                };
+                if class < min_class {
+                    break;
+                }
+                if match_token_to_operator(token).is_some() {
+                    self.next();
+                }
+                // Exponents are right associative, everything else is left associative
+                let right = match operator {
+                    Operator::Exponent => self.parse_expression(class)?,
+                    _ => self.parse_expression(class + 1)?,
+                };
+                left = Node::Operation(operator, Box::new(left), Box::new(right));
+            }
+            Ok(left)
+        }
+        fn parse_unary(&mut self) -> Result<Node, &'static str> {
+            match self.peek() {
+                Some(Tokens::Sub) => {
+                    self.next();
+                    // Only powers bind tighter, `-x^2` is -(x^2) and `2^-1*3` is (2^-1)*3
+                    let operand = self.parse_expression(priority_class(&Tokens::Exponent))?;
+                    Ok(Node::Negate(Box::new(operand)))
+                }
+                Some(Tokens::Add) => {
+                    self.next();
+                    self.parse_unary()
+                }
+                _ => self.parse_primary(),
+            }
+        }
+        fn parse_primary(&mut self) -> Result<Node, &'static str> {
+            match self.next() {
+                Some(Tokens::Number(x)) => Ok(Node::Number(*x)),
+                Some(Tokens::Variable(x)) => Ok(Node::Variable(x.to_string())),
+                Some(Tokens::Function(name)) => {
+                    let arguments = match self.peek() {
+                        Some(Tokens::ParenthesisLeft) => {
+                            self.next();
+                            let mut arguments = vec![self.parse_condition()?];
+                            while let Some(Tokens::Comma) = self.peek() {
+                                self.next();
+                                arguments.push(self.parse_condition()?);
+                            }
+                            self.expect(Tokens::ParenthesisRight)?;
+                            arguments
+                        }
+                        // `sin x^2` reads as sin(x^2)
+                        Some(_) => vec![self.parse_expression(priority_class(&Tokens::Exponent))?],
+                        None => return Err(UNEXPECTED_END_ERROR),
+                    };
+                    Ok(Node::Call(name.to_string(), arguments))
+                }
+                Some(Tokens::ParenthesisLeft) => {
+                    let mut inner = vec![self.parse_condition()?];
+                    while let Some(Tokens::Comma) = self.peek() {
+                        self.next();
+                        inner.push(self.parse_condition()?);
+                    }
+                    self.expect(Tokens::ParenthesisRight)?;
+                    match inner.len() == 1 {
+                        true => Ok(inner.remove(0)),
+                        false => Ok(Node::Tuple(inner)),
+                    }
+                }
+                Some(Tokens::BraceLeft) => self.parse_piecewise(),
+                Some(Tokens::BracketLeft) => {
+                    let mut items = Vec::new();
+                    if let Some(Tokens::BracketRight) = self.peek() {
+                        self.next();
+                        return Ok(Node::List(items));
+                    }
+                    items.push(self.parse_condition()?);
+                    while let Some(Tokens::Comma) = self.peek() {
+                        self.next();
+                        items.push(self.parse_condition()?);
+                    }
+                    self.expect(Tokens::BracketRight)?;
+                    Ok(Node::List(items))
+                }
+                Some(_) => Err(UNEXPECTED_TOKEN_ERROR),
+                None => Err(UNEXPECTED_END_ERROR),
+            }
+        }
+    }
+    pub fn parse<'a>(tokens: &[Tokens<'a>]) -> Result<Node, &'static str> {
+        if tokens.is_empty() {
+            return Err(EMPTY_EXPRESSION_ERROR);
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+        };
+        let node = parser.parse_condition()?;
+        match parser.peek() {
+            Some(_) => Err(UNEXPECTED_TOKEN_ERROR),
+            None => Ok(node),
+        }
+    }
 }
-// AST = Abstract Syntax Tree
-pub fn generate_all_ast(data: &HashMap<&str, String>) {
+/// Evaluating syntax trees with the values in a [`evaluator::Context`].
+pub mod evaluator {
+    use super::*;
+    use crate::statistics;
+    /// What an expression evaluates to once lists are allowed
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Value {
+        /// A single number
+        Number(f64),
+        /// A list of numbers, e.g. `[1, 2, 3]` or `sort(data)`
+        List(Vec<f64>),
+    }
+    /// Values for variables and user defined functions (parameter names and body)
+    #[derive(Debug, Clone, Default)]
+    pub struct Context {
+        /// Variables and slider values by name
+        pub variables: HashMap<String, f64>,
+        /// Named lists such as imported dataset columns
+        pub lists: HashMap<String, Vec<f64>>,
+        /// User defined functions by name, as their parameter names and body
+        pub functions: HashMap<String, (Vec<String>, Node)>,
+    }
+    impl Context {
+        /// Sets `name` to `value` and returns the context, for building one inline.
+        pub fn with_variable(mut self, name: &str, value: f64) -> Context {
+            self.variables.insert(name.to_string(), value);
+            self
+        }
+    }
+    /// A borrowed context with a few variables bound on top, so sampling x or t does not
+    /// copy every list and function for each point
+    #[derive(Debug, Clone, Copy)]
+    pub struct Scope<'a> {
+        /// The variables, lists and functions below the local bindings
+        pub context: &'a Context,
+        locals: &'a [(&'a str, f64)],
+        // The caller's scope while evaluating a user defined function
+        parent: Option<&'a Scope<'a>>,
+    }
+    impl<'a> Scope<'a> {
+        /// Binds `locals` on top of `context`, the first match for a name wins.
+        pub fn new(context: &'a Context, locals: &'a [(&'a str, f64)]) -> Scope<'a> {
+            Scope {
+                context,
+                locals,
+                parent: None,
+            }
+        }
+        fn get_variable(&self, name: &str) -> Option<f64> {
+            match (self.locals.iter().find(|(x, _)| *x == name), self.parent) {
+                (Some((_, value)), _) => Some(*value),
+                (None, Some(parent)) => parent.get_variable(name),
+                (None, None) => self.context.variables.get(name).copied(),
+            }
+        }
+    }
+    fn call_builtin(name: &str, arguments: &[f64]) -> Result<f64, &'static str> {
+        let x = match arguments {
+            [x] => *x,
+            _ => return Err(ARGUMENT_COUNT_ERROR),
+        };
+        let result = match name {
+            "sin" => x.sin(),
+            "cos" => x.cos(),
+            "tan" => x.tan(),
+            "sec" => 1.0 / x.cos(),
+            "csc" => 1.0 / x.sin(),
+            "cot" => 1.0 / x.tan(),
+            "asin" | "arcsin" => x.asin(),
+            "acos" | "arccos" => x.acos(),
+            "atan" | "arctan" => x.atan(),
+            "sinh" => x.sinh(),
+            "cosh" => x.cosh(),
+            "tanh" => x.tanh(),
+            "sqrt" => x.sqrt(),
+            "abs" => x.abs(),
+            "ln" => x.ln(),
+            "log" => x.log10(),
+            "exp" => x.exp(),
+            "floor" => x.floor(),
+            "ceil" => x.ceil(),
+            "round" => x.round(),
+            "sign" => match x == 0.0 {
+                true => 0.0,
+                false => x.signum(),
+            },
+            _ => return Err(UNDEFINED_FUNCTION_ERROR),
+        };
+        Ok(result)
+    }
+    // `mean(1, 2, 3)` and `mean([1, 2, 3])` are the same list
+    fn gather_list(arguments: &[Value]) -> Result<Vec<f64>, &'static str> {
+        match arguments {
+            [Value::List(x)] => Ok(x.clone()),
+            _ => arguments
+                .iter()
+                .map(|x| match x {
+                    Value::Number(x) => Ok(*x),
+                    Value::List(_) => Err(LIST_ARGUMENT_ERROR),
+                })
+                .collect(),
+        }
+    }
+    fn call_list_function(name: &str, arguments: &[Value]) -> Result<Value, &'static str> {
+        let number = |x: f64| Ok(Value::Number(x));
+        match (name, arguments) {
+            ("quantile", [Value::List(values), Value::Number(p)]) => {
+                number(statistics::quantile(values, *p))
+            }
+            ("quantile", _) => Err(ARGUMENT_COUNT_ERROR),
+            ("corr", [Value::List(a), Value::List(b)]) => number(statistics::correlation(a, b)),
+            ("corr", _) => Err(LIST_ARGUMENT_ERROR),
+            // Plots, entries draw them but they have no value of their own
+            ("histogram" | "boxplot", _) => Err(LIST_VALUE_ERROR),
+            _ => {
+                let values = gather_list(arguments)?;
+                match name {
+                    "mean" => number(statistics::mean(&values)),
+                    "median" => number(statistics::median(&values)),
+                    "mode" => number(statistics::mode(&values)),
+                    "variance" => number(statistics::variance(&values)),
+                    "stdev" => number(statistics::standard_deviation(&values)),
+                    "min" => number(statistics::min(&values)),
+                    "max" => number(statistics::max(&values)),
+                    "sum" => number(statistics::sum(&values)),
+                    "sort" => Ok(Value::List(statistics::sorted(&values))),
+                    _ => Err(UNDEFINED_FUNCTION_ERROR),
+                }
+            }
+        }
+    }
+    /// Like `evaluate`, but lists and the functions returning them are allowed
+    pub fn evaluate_value(node: &Node, context: &Context) -> Result<Value, &'static str> {
+        evaluate_value_in(node, &Scope::new(context, &[]))
+    }
+    /// Like [`evaluate_value`], in a scope with local bindings.
+    pub fn evaluate_value_in(node: &Node, scope: &Scope) -> Result<Value, &'static str> {
+        let context = scope.context;
+        match node {
+            Node::Variable(name)
+                if (scope.get_variable(name).is_none() && context.lists.contains_key(name)) =>
+            {
+                Ok(Value::List(context.lists[name].clone()))
+            }
+            Node::List(items) => Ok(Value::List(
+                items
+                    .iter()
+                    .map(|x| evaluate_in(x, scope))
+                    .collect::<Result<Vec<f64>, &'static str>>()?,
+            )),
+            Node::Call(name, arguments)
+                if (LIST_FUNCTIONS.contains(&name.as_str())
+                    && !context.functions.contains_key(name)) =>
+            {
+                let values = arguments
+                    .iter()
+                    .map(|x| evaluate_value_in(x, scope))
+                    .collect::<Result<Vec<Value>, &'static str>>()?;
+                call_list_function(name, &values)
+            }
+            x => Ok(Value::Number(evaluate_in(x, scope)?)),
+        }
+    }
+    /// Evaluates an expression that has to give a list, e.g. the argument of `mean`.
+    pub fn evaluate_list(node: &Node, context: &Context) -> Result<Vec<f64>, &'static str> {
+        match evaluate_value(node, context)? {
+            Value::List(x) => Ok(x),
+            Value::Number(_) => Err(LIST_ARGUMENT_ERROR),
+        }
+    }
+    /// Evaluates an expression to one number, lists and tuples are errors.
+    pub fn evaluate(node: &Node, context: &Context) -> Result<f64, &'static str> {
+        evaluate_in(node, &Scope::new(context, &[]))
+    }
+    /// Like [`evaluate`], in a scope with local bindings.
+    pub fn evaluate_in(node: &Node, scope: &Scope) -> Result<f64, &'static str> {
+        let context = scope.context;
+        match node {
+            Node::Number(x) => Ok(*x),
+            Node::Variable(name) => match scope.get_variable(name) {
+                Some(x) => Ok(x),
+                None => match name.as_str() {
+                    "pi" | "π" => Ok(std::f64::consts::PI),
+                    "e" => Ok(std::f64::consts::E),
+                    _ if (context.lists.contains_key(name)) => Err(LIST_VALUE_ERROR),
+                    _ => Err(UNDEFINED_VARIABLE_ERROR),
+                },
+            },
+            Node::Negate(x) => Ok(-evaluate_in(x, scope)?),
+            Node::Operation(operator, a, b) => {
+                let (a, b) = (evaluate_in(a, scope)?, evaluate_in(b, scope)?);
+                Ok(match operator {
+                    Operator::Add => a + b,
+                    Operator::Sub => a - b,
+                    Operator::Mul => a * b,
+                    Operator::Div => a / b,
+                    Operator::Exponent => a.powf(b),
+                })
+            }
+            Node::Call(name, _)
+                if (LIST_FUNCTIONS.contains(&name.as_str())
+                    && !context.functions.contains_key(name)) =>
+            {
+                match evaluate_value_in(node, scope)? {
+                    Value::Number(x) => Ok(x),
+                    Value::List(_) => Err(LIST_VALUE_ERROR),
+                }
+            }
+            Node::Call(name, arguments) => {
+                let values = arguments
+                    .iter()
+                    .map(|x| evaluate_in(x, scope))
+                    .collect::<Result<Vec<f64>, &'static str>>()?;
+                match context.functions.get(name) {
+                    Some((parameters, body)) => {
+                        if parameters.len() != values.len() {
+                            return Err(ARGUMENT_COUNT_ERROR);
+                        }
+                        let bindings = parameters
+                            .iter()
+                            .map(|x| x.as_str())
+                            .zip(values.iter().copied())
+                            .collect::<Vec<(&str, f64)>>();
+                        let inner = Scope {
+                            context,
+                            locals: &bindings,
+                            parent: Some(scope),
+                        };
+                        evaluate_in(body, &inner)
+                    }
+                    None => call_builtin(name, &values),
+                }
+            }
+            Node::Tuple(_) => Err(TUPLE_VALUE_ERROR),
+            Node::List(_) => Err(LIST_VALUE_ERROR),
+            Node::Compare(comparison, a, b) => {
+                let holds = comparison.holds(evaluate_in(a, scope)?, evaluate_in(b, scope)?);
+                Ok(holds as i32 as f64)
+            }
+            Node::And(a, b) => {
+                let holds = evaluate_condition_in(a, scope)? && evaluate_condition_in(b, scope)?;
+                Ok(holds as i32 as f64)
+            }
+            Node::Or(a, b) => {
+                let holds = evaluate_condition_in(a, scope)? || evaluate_condition_in(b, scope)?;
+                Ok(holds as i32 as f64)
+            }
+            // Undefined where no piece applies
+            Node::Piecewise(pieces) => {
+                for (condition, value) in pieces.iter() {
+                    if evaluate_condition_in(condition, scope)? {
+                        return evaluate_in(value, scope);
+                    }
+                }
+                Ok(f64::NAN)
+            }
+        }
+    }
+    /// Any value other than 0 (and NaN) counts as true
+    pub fn evaluate_condition(node: &Node, context: &Context) -> Result<bool, &'static str> {
+        evaluate_condition_in(node, &Scope::new(context, &[]))
+    }
+    /// Like [`evaluate_condition`], in a scope with local bindings.
+    pub fn evaluate_condition_in(node: &Node, scope: &Scope) -> Result<bool, &'static str> {
+        let value = evaluate_in(node, scope)?;
+        Ok(value != 0.0 && !value.is_nan())
+    }
+    /// Every component of a tuple, or the single value of any other expression
+    pub fn evaluate_tuple(node: &Node, context: &Context) -> Result<Vec<f64>, &'static str> {
+        evaluate_tuple_in(node, &Scope::new(context, &[]))
+    }
+    /// Like [`evaluate_tuple`], in a scope with local bindings.
+    pub fn evaluate_tuple_in(node: &Node, scope: &Scope) -> Result<Vec<f64>, &'static str> {
+        match node {
+            Node::Tuple(components) => components.iter().map(|x| evaluate_in(x, scope)).collect(),
+            x => Ok(vec![evaluate_in(x, scope)?]),
+        }
+    }
+}
+/// Names the lexer already gives a meaning to, anything else can be declared as a term
+pub fn is_reserved_name(name: &str) -> bool {
+    BUILTIN_FUNCTIONS
+        .iter()
+        .chain(LIST_FUNCTIONS.iter())
+        .chain(CONSTANTS.iter())
+        .chain(KEYWORDS.iter())
+        .any(|x| *x == name)
+}
+/// Parses every named expression in `data` into an abstract syntax tree.
+pub fn generate_all_ast<'a>(
+    data: &HashMap<&'a str, String>,
+) -> HashMap<&'a str, Result<Node, String>> {
+    generate_all_ast_with_names(data, &[])
+}
+/// `names` are multi-letter terms such as list names that are read as one variable
+pub fn generate_all_ast_with_names<'a>(
+    data: &HashMap<&'a str, String>,
+    names: &[&str],
+) -> HashMap<&'a str, Result<Node, String>> {
     let mut function_map = (*data).clone();
     function_map = lexical_analyzer::clean(&function_map);
-    let tokenized_map = lexical_analyzer::analyze(&function_map, None);
-    match tokenized_map {
-        TraceExists::Success(x) => {}
-        TraceExists::Wrapper(x) => {}
-    }
+    function_map
+        .iter()
+        .map(|(&name, expression)| {
+            let single = HashMap::from([(name, expression.clone())]);
+            let filter_list = FilterList {
+                list_type: FilterType::WhiteList,
+                list: names.to_vec(),
+            };
+            let tokenized_map = lexical_analyzer::analyze(&single, Some(filter_list));
+            let ast = match tokenized_map {
+                TraceExists::Success(x) => syntax_analyzer::parse(&x[name]).map_err(String::from),
+                TraceExists::Wrapper(x) => {
+                    Err(x.err.err().unwrap_or(PROCEDURE_SYNTAX_ERROR).to_string())
+                }
+            };
+            (name, ast)
+        })
+        .collect()
 }
 //
+#[cfg(test)]
+mod test {
+    use super::evaluator::{evaluate, evaluate_in, Context, Scope};
+    use super::*;
+    fn parse_one(expression: &str) -> Result<Node, String> {
+        let data = HashMap::from([("f", expression.to_string())]);
+        generate_all_ast(&data).remove("f").unwrap()
+    }
+    fn evaluate_at(expression: &str, x: f64) -> f64 {
+        evaluate(
+            &parse_one(expression).unwrap(),
+            &Context::default().with_variable("x", x),
+        )
+        .unwrap()
+    }
+    #[test]
+    fn lexical_analysis() {
+        let data = HashMap::from([("f", "2.5sin(ab)+x".to_string())]);
+        let tokens = match lexical_analyzer::analyze(&data, None) {
+            TraceExists::Success(x) => x,
+            TraceExists::Wrapper(x) => panic!("{:?}", x.err.err()),
+        };
+        assert_eq!(
+            tokens["f"],
+            vec![
+                Tokens::Number(2.5),
+                Tokens::Function("sin"),
+                Tokens::ParenthesisLeft,
+                Tokens::Variable("a"),
+                Tokens::Variable("b"),
+                Tokens::ParenthesisRight,
+                Tokens::Add,
+                Tokens::Variable("x"),
+            ]
+        );
+        let data = HashMap::from([("f", "(x+1".to_string())]);
+        assert!(lexical_analyzer::analyze(&data, None)
+            .into_result()
+            .is_err());
+    }
+    #[test]
+    fn filter_lists() {
+        let data = HashMap::from([("f", "height*2".to_string())]);
+        let white_list = FilterList {
+            list_type: FilterType::WhiteList,
+            list: vec!["height"],
+        };
+        let tokens = lexical_analyzer::analyze(&data, Some(white_list))
+            .into_result()
+            .unwrap();
+        assert_eq!(tokens["f"][0], Tokens::Variable("height"));
+        let black_list = FilterList {
+            list_type: FilterType::BlackList,
+            list: vec!["y"],
+        };
+        let data = HashMap::from([("f", "x+y".to_string())]);
+        assert!(lexical_analyzer::analyze(&data, Some(black_list))
+            .into_result()
+            .is_err());
+    }
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate_at("1-2+3", 0.0), 2.0);
+        assert_eq!(evaluate_at("2^3^2", 0.0), 512.0);
+        assert_eq!(evaluate_at("-x^2", 3.0), -9.0);
+        assert_eq!(evaluate_at("2^-1*3", 0.0), 1.5);
+        assert_eq!(evaluate_at("2^-x*3", 1.0), 1.5);
+        assert_eq!(evaluate_at("-2x/4", 2.0), -1.0);
+        assert_eq!(evaluate_at("2^-x^2", 1.0), 0.5);
+        assert_eq!(evaluate_at("2x(x+1)", 3.0), 24.0);
+        assert_eq!(evaluate_at("8/2/2", 0.0), 2.0);
+        assert!((evaluate_at("sin x^2", 2.0) - 4f64.sin()).abs() < 1e-12);
+        assert!((evaluate_at("2pi", 0.0) - std::f64::consts::TAU).abs() < 1e-12);
+    }
+    #[test]
+    fn scientific_notation() {
+        assert_eq!(parse_one("1e5"), Ok(Node::Number(1e5)));
+        assert_eq!(evaluate_at("2.5e-3", 0.0), 2.5e-3);
+        assert_eq!(evaluate_at("1E+2x", 2.0), 200.0);
+        let e = std::f64::consts::E;
+        assert!((evaluate_at("2e", 0.0) - 2.0 * e).abs() < 1e-12);
+        assert!((evaluate_at("2ex", 3.0) - 6.0 * e).abs() < 1e-12);
+        assert_eq!(evaluate_at("2e-1", 0.0), 0.2);
+        assert!((evaluate_at("2*e-1", 0.0) - (2.0 * e - 1.0)).abs() < 1e-12);
+    }
+    #[test]
+    fn tuples() {
+        let node = parse_one("(cos(t), 2sin(t))").unwrap();
+        let context = Context::default().with_variable("t", 0.0);
+        assert_eq!(
+            evaluator::evaluate_tuple(&node, &context),
+            Ok(vec![1.0, 0.0])
+        );
+        assert!(evaluate(&node, &context).is_err());
+        assert_eq!(evaluate_at("(x + 1)", 1.0), 2.0);
+    }
+    #[test]
+    fn conditions() {
+        assert_eq!(evaluate_at("x < 2", 1.0), 1.0);
+        assert_eq!(evaluate_at("x >= 2", 1.0), 0.0);
+        assert_eq!(evaluate_at("0 < x < 1", 0.5), 1.0);
+        assert_eq!(evaluate_at("0 < x < 1", 1.5), 0.0);
+        assert_eq!(evaluate_at("x < 0 or x > 1 and x < 2", 1.5), 1.0);
+        assert_eq!(evaluate_at("x ≤ 1 and x ≥ 1", 1.0), 1.0);
+        let node = parse_one("x^2 + y^2 <= 9 and y > x").unwrap();
+        let comparisons = node.get_comparisons();
+        assert_eq!(comparisons.len(), 2);
+        assert_eq!(comparisons[1].0, Comparison::Greater);
+        assert!(parse_one("x <").is_err());
+    }
+    #[test]
+    fn piecewise() {
+        let expression = "{x < 0: -x, x >= 0: x^2}";
+        assert_eq!(evaluate_at(expression, -2.0), 2.0);
+        assert_eq!(evaluate_at(expression, 3.0), 9.0);
+        assert_eq!(evaluate_at("{x < 0: 1, 0 <= x < 1: 2, 3}", 0.5), 2.0);
+        assert_eq!(evaluate_at("{x < 0: 1, 0 <= x < 1: 2, 3}", 5.0), 3.0);
+        assert_eq!(evaluate_at("2{x > 0: x}", 4.0), 8.0);
+        assert!(evaluate_at("{x > 0: x}", -1.0).is_nan());
+        assert_eq!(
+            parse_one("{x < 0: x}").unwrap().get_variables(),
+            vec!["x".to_string()]
+        );
+        assert!(parse_one("{x < 0: 1, 2, x > 0: 3}").is_err());
+        assert!(parse_one("{x < 0: 1").is_err());
+        assert!(parse_one("{x < 0: 1)").is_err());
+    }
+    #[test]
+    fn lists() {
+        let node = parse_one("[(1, 2), (3, a)]").unwrap();
+        match node {
+            Node::List(ref items) => assert_eq!(items.len(), 2),
+            _ => panic!("{:?}", node),
+        }
+        assert_eq!(node.get_variables(), vec!["a".to_string()]);
+        assert_eq!(parse_one("[]"), Ok(Node::List(Vec::new())));
+        assert!(evaluate(&node, &Context::default()).is_err());
+        assert!(parse_one("[1, 2").is_err());
+    }
+    #[test]
+    fn list_functions() {
+        assert_eq!(evaluate_at("mean([1, 2, 6])", 0.0), 3.0);
+        assert_eq!(evaluate_at("median(5, 1, 3) + x", 1.0), 4.0);
+        assert_eq!(evaluate_at("max([x, 2x, -x])", 2.0), 4.0);
+        assert_eq!(evaluate_at("quantile([1, 2, 3, 4, 5], 0.25)", 0.0), 2.0);
+        assert_eq!(evaluate_at("sum(sort([3, 1, 2]))", 0.0), 6.0);
+        assert!(
+            (evaluate_at("corr([1, 2, 3], [2, 4, 7])", 0.0) - 0.9933992677987828).abs() < 1e-12
+        );
+        let node = parse_one("sort([3, 1, 2])").unwrap();
+        assert_eq!(
+            evaluator::evaluate_value(&node, &Context::default()),
+            Ok(evaluator::Value::List(vec![1.0, 2.0, 3.0]))
+        );
+        assert!(evaluate(&node, &Context::default()).is_err());
+        assert!(evaluate(&parse_one("quantile([1, 2])").unwrap(), &Context::default()).is_err());
+        // `e` inside `mean` is not read as the constant
+        assert_eq!(
+            parse_one("mean(x)").unwrap().get_variables(),
+            vec!["x".to_string()]
+        );
+    }
+    #[test]
+    fn scopes_bind_over_the_context() {
+        let mut context = Context::default()
+            .with_variable("x", 1.0)
+            .with_variable("a", 2.0);
+        let body = parse_one("a*x + b").unwrap();
+        context
+            .functions
+            .insert("f".to_string(), (vec!["b".to_string()], body));
+        let data = HashMap::from([("g", "f(10) + x".to_string())]);
+        let node = generate_all_ast_with_names(&data, &["f"])
+            .remove("g")
+            .unwrap()
+            .unwrap();
+        assert_eq!(evaluate(&node, &context), Ok(13.0));
+        // Locals shadow the context and reach into function bodies like variables do
+        let locals = [("x", 3.0), ("a", 4.0)];
+        assert_eq!(evaluate_in(&node, &Scope::new(&context, &locals)), Ok(25.0));
+        assert_eq!(context.variables["x"], 1.0);
+    }
+    #[test]
+    fn printing_round_trips() {
+        let expressions = [
+            "1 - (2 - x)",
+            "-x^2 + 3*x",
+            "(x + 1)^(2^x)",
+            "sin(x)/(x*2)",
+            "2^(-x)",
+            "{x < 0: -x, 0 <= x and x < 1: 1, x^2}",
+            "[(1, 2), (3, 4)]",
+        ];
+        for expression in expressions {
+            let node = parse_one(expression).unwrap();
+            assert_eq!(parse_one(&node.to_string()), Ok(node.clone()), "{}", node);
+        }
+        let node = parse_one("a*x + b").unwrap();
+        let values = HashMap::from([("a".to_string(), 2.0), ("b".to_string(), -1.5)]);
+        assert_eq!(node.substitute(&values).to_string(), "2*x - 1.5");
+    }
+    #[test]
+    fn syntax_errors() {
+        assert!(parse_one("").is_err());
+        assert!(parse_one("x+").is_err());
+        assert!(parse_one("*x").is_err());
+        assert!(parse_one("x$").is_err());
+    }
+}
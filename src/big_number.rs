@@ -1,17 +1,10 @@
-use itertools::Dedup;
-use macroquad::experimental::scene::HandleUntyped;
-use num::{
-    complex::ComplexFloat,
-    traits::{
-        float::{FloatCore, TotalOrder},
-        real::Real,
-        Pow,
-    },
-    Float,
-};
+//! Numbers stored as an `f64` base and a separate decimal exponent, so camera positions
+//! and zoom levels can go far beyond the range of an `f64`.
+use num::traits::real::Real;
 use std::{
-    cmp::{self, Ordering},
-    ops::{self, BitAndAssign},
+    cmp::Ordering,
+    fmt,
+    ops::{self},
 };
 const HAVEN_ABBREVIATIONS: [Option<&str>; 9] = [
     None,
@@ -24,205 +17,211 @@ const HAVEN_ABBREVIATIONS: [Option<&str>; 9] = [
     Some("SX"),
     Some("SP"),
 ];
-fn bool_from_number(number: i32) -> bool {
-    match number {
-        -1 => false,
-        0 => false,
-        _ => true,
-    }
-}
+const DISPLAY_PRECISION: i32 = 12;
 fn sign_from_bool(boolean: bool) -> i32 {
     match boolean {
         false => -1,
         true => 1,
     }
 }
-fn reverse_number(a: i32, b: i32, c: i32) -> i32 {
-    *(a..=b)
-        .rev()
-        .collect::<Vec<i32>>()
-        .get((c - a) as usize)
-        .unwrap_or(&0)
-}
-fn cyclic_wrap(a: i32, b: i32, max: i32) -> f32 {
-    let modulo_of_sum = (a + b) % max;
-    Real::powf(
-        10.0,
-        match (modulo_of_sum != 0) {
-            true => modulo_of_sum,
-            false => max,
-        } as f32,
-    )
+// Digits with an optional decimal point as `(mantissa, exponent)` with the mantissa in
+// [1, 10). Only the leading 17 significant digits are kept so no length of input overflows,
+// which is still enough for every f64 to come back bit for bit
+fn parse_decimal(text: &str) -> Option<(f64, i32)> {
+    let (integer, fraction) = text.split_once('.').unwrap_or((text, ""));
+    let digits = format!("{}{}", integer, fraction);
+    if digits.is_empty() || !digits.chars().all(|x| x.is_ascii_digit()) {
+        return None;
+    }
+    let leading = digits.chars().take_while(|x| *x == '0').count();
+    if leading == digits.len() {
+        return Some((0.0, 0));
+    }
+    let significant = &digits[leading..(leading + 17).min(digits.len())];
+    let mantissa = format!("{}.{}", &significant[..1], &significant[1..])
+        .parse::<f64>()
+        .ok()?;
+    let exponent = i32::try_from(integer.len() as i64 - leading as i64 - 1).ok()?;
+    Some((mantissa, exponent))
 }
-fn exponential_modulo_ten(a: f32, b: f32) -> i32 {
-    let modulus = (a.log10().floor() - b.log10().floor()) % 3.0;
-    let map = match modulus as i32 {
-        0 => 0,
-        1 => 1,
-        _ => -1,
+// Splits off a sign and an `e` exponent, e.g. `-1.5e-3` -> (-1, "1.5", -3)
+fn split_sign_and_exponent(text: &str) -> Result<(f64, &str, i32), AnalysisErrors> {
+    let text = text.trim();
+    let (sign, rest) = match text.strip_prefix('-') {
+        Some(x) => (-1.0, x),
+        None => (1.0, text.strip_prefix('+').unwrap_or(text)),
     };
-    map
+    match rest.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => match exponent.parse::<i32>() {
+            Ok(x) => Ok((sign, mantissa, x)),
+            Err(_) => Err(AnalysisErrors::Exponent),
+        },
+        None => Ok((sign, rest, 0)),
+    }
 }
-fn get_first_significant_figure(number: f32) -> f32 {
+fn get_first_significant_figure(number: f64) -> f64 {
     number / Real::powf(10.0, number.log10().floor())
 }
-fn is_between_integers(a: f32, b: i32, c: i32) -> bool {
-    (a > b as f32) && (a < c as f32)
+// Trims the floating point noise left over from normalizing the base, e.g. 8.999999 -> 9
+fn round_to_precision(number: f64) -> f64 {
+    if number == 0.0 {
+        return number;
+    }
+    let scale = Real::powi(
+        10.0,
+        DISPLAY_PRECISION - 1 - number.abs().log10().floor() as i32,
+    );
+    (number * scale).round() / scale
 }
+/// How a [`BigNumber`] is written: Haven uses suffixes such as `1.5K`, Scientific writes
+/// `1.5x10^3`.
 #[derive(Clone)]
 pub enum Format {
+    /// Suffixed text such as `1.5K`
     Haven(String),
+    /// Text such as `1.5x10^3`
     Scientific(String),
 }
+/// A number stored as `base * 10^exponent` with the base in [1, 10), so magnitudes far
+/// beyond the range of an `f64` keep their precision.
+///
+/// ```
+/// use graphing_calculator::BigNumber;
+///
+/// let huge = BigNumber::parse("1.5e400").unwrap();
+/// let product = huge * BigNumber::new_d(2.0);
+/// assert_eq!(product.to_string(), "3x10^400");
+/// assert_eq!(BigNumber::new_d(1234.0).to_string(), "1.234K");
+/// assert!(BigNumber::new_d(-5.0) < BigNumber::new_d(0.001));
+/// ```
 #[derive(Clone)]
 pub struct BigNumber {
+    /// The text `Display` writes, kept in step with the value
     pub serialized: Format,
-    pub base: f32,
+    /// The magnitude of the mantissa, in [1, 10) or 0
+    pub base: f64,
+    /// The power of ten the base is scaled by
     pub exponent: i32,
+    /// The sign, zero counts as positive
     pub is_positive: bool,
 }
-macro_rules! handle_analysis_errors {
-    ($condition:expr, $error:expr) => {
-        if ($condition) {
-            match $error.err().unwrap() {
-                AnalysisErrors::InvalidPrefix => {
-                    println!("BRO");
-                }
-                AnalysisErrors::InvalidSuffix => {
-                    println!("BRUH");
-                }
-                AnalysisErrors::InvalidExponent => {
-                    println!("DUDE");
-                }
-            }
-            return None;
-        }
-    };
-}
 impl BigNumber {
+    /// Reads text in the given format, None when it is not a number.
     pub fn new(serialized: Format) -> Option<BigNumber> {
-        let mut big_number = BigNumber::new_d(1.0);
-        let inner = big_number.get_value();
-        let exponent = match serialized {
-            Format::Haven(_) => Haven::get_exponent(inner.clone()),
-            Format::Scientific(_) => Scientific::get_exponent(inner.clone()),
-        }
-        .ok()
-        .unwrap();
-        let multiplier = match serialized {
-            Format::Haven(_) => Haven::get_multiplier(inner, exponent),
-            Format::Scientific(_) => Scientific::get_multiplier(inner, exponent),
+        let parsed = match serialized {
+            Format::Haven(ref x) => Haven::parse(x),
+            Format::Scientific(ref x) => Scientific::parse(x),
         };
-        handle_analysis_errors!(multiplier.is_err(), multiplier);
-        big_number.increase_power(exponent, false);
-        match serialized {
-            Format::Haven(_) => {
-                big_number.serialized =
-                    Format::Haven(Haven::create(multiplier.ok().unwrap(), exponent, false));
-            }
-            Format::Scientific(_) => {
-                big_number.serialized = Format::Scientific(Scientific::create(
-                    multiplier.ok().unwrap(),
-                    exponent,
-                    false,
-                ));
-            }
+        let (mantissa, exponent) = parsed.ok()?;
+        Some(BigNumber::from_parts(mantissa, exponent))
+    }
+    /// Reads anything `Display` writes as well as plain decimals such as `1.5e400`, which
+    /// would overflow an f64 if parsed directly.
+    pub fn parse(text: &str) -> Option<BigNumber> {
+        match text.contains("x10^") {
+            true => BigNumber::new(Format::Scientific(text.to_string())),
+            false => BigNumber::new(Format::Haven(text.to_string())),
         }
-        Some(big_number)
     }
-    pub fn new_d(deserialized: f32) -> BigNumber {
-        let positive_deserialized = deserialized.abs();
-        let mut temp = BigNumber {
-            serialized: Format::Haven(("1.0").to_string()),
-            base: 1.0,
-            exponent: 0,
-            is_positive: (deserialized >= 0.0),
-        };
-        if (deserialized == 0.0) {
+    /// Reads plain decimals such as `-1.5e400` only, without the suffixes `parse` accepts.
+    pub fn parse_plain(text: &str) -> Option<BigNumber> {
+        let (sign, number, exponent) = split_sign_and_exponent(text).ok()?;
+        let (mantissa, shift) = parse_decimal(number)?;
+        Some(BigNumber::from_parts(
+            sign * mantissa,
+            exponent.checked_add(shift)?,
+        ))
+    }
+    /// The value of an f64.
+    pub fn new_d(deserialized: f64) -> BigNumber {
+        BigNumber::from_parts(deserialized, 0)
+    }
+    /// Builds `mantissa * 10^exponent`, moving every power of ten out of the mantissa
+    /// so that `base` always lands in [1, 10).
+    pub fn from_parts(mantissa: f64, exponent: i32) -> BigNumber {
+        if mantissa == 0.0 {
             return BigNumber {
-                serialized: Format::Haven(("0.0").to_string()),
+                serialized: Format::Haven(("0").to_string()),
                 base: 0.0,
                 exponent: 0,
                 ..Default::default()
             };
         }
-        if (is_between_integers(positive_deserialized, 0, 1)) {
-            let base = get_first_significant_figure(positive_deserialized);
-            let exponent = -((1.0 / positive_deserialized).log10().ceil()) as i32;
-            return BigNumber {
-                serialized: Format::Scientific(Scientific::create(base, exponent, false)),
-                base,
-                exponent,
-                is_positive: (deserialized >= 0.0),
-            };
-        }
-        if (positive_deserialized < 10.0) {
-            return BigNumber {
-                serialized: Format::Haven((positive_deserialized as f32).to_string()),
-                base: positive_deserialized,
-                exponent: 0,
-                is_positive: (deserialized >= 0.0),
-            };
+        // NaN has no sign and infinities have no exponent
+        let mut big_number = BigNumber {
+            base: mantissa.abs(),
+            exponent: match mantissa.is_finite() {
+                true => exponent,
+                false => 0,
+            },
+            is_positive: (mantissa > 0.0) || mantissa.is_nan(),
+            ..Default::default()
+        };
+        if big_number.base.is_finite() {
+            // Dividing by powi(10, n) drifts for large n, the {:e} formatting is exact
+            let scientific = format!("{:e}", big_number.base);
+            let (base, shift) = scientific.split_once('e').unwrap();
+            big_number.base = base.parse::<f64>().unwrap();
+            big_number.exponent += shift.parse::<i32>().unwrap();
         }
-        temp.increase_power(positive_deserialized.log10().floor() as i32, false);
-        match temp.serialized {
-            Format::Haven(x) => {
-                temp.base = get_first_significant_figure(positive_deserialized);
-                temp.serialized =
-                    Format::Haven(Haven::create(temp.base, temp.exponent as i32, false));
-            }
-            Format::Scientific(x) => {
-                let current_multiplier =
-                    Scientific::get_multiplier(x, positive_deserialized.log10().floor() as i32);
-                temp.base = current_multiplier.ok().unwrap();
-                temp.serialized = Format::Scientific(Scientific::create(
-                    temp.base,
-                    positive_deserialized.log10().floor() as i32,
-                    false,
-                ));
-            }
+        big_number.serialize();
+        big_number
+    }
+    /// Unlike `Display` this keeps every digit of the base, `parse` reads it back exactly.
+    pub fn to_exact_string(&self) -> String {
+        format!("{}e{}", self.get_signed_base(), self.exponent)
+    }
+    /// Always in scientific notation, `Display` only switches to it outside the Haven
+    /// range.
+    pub fn to_scientific_string(&self) -> String {
+        let sign = match self.is_positive {
+            true => "",
+            false => "-",
+        };
+        // Zero, infinity and NaN have no exponent to write
+        match (self.base == 0.0) || !self.base.is_finite() {
+            true => self.to_string(),
+            false => format!("{}{}", sign, Scientific::create(self.base, self.exponent)),
         }
-        temp
     }
+    /// The serialized text without the sign.
     pub fn get_value(&self) -> String {
         match self.serialized.clone() {
             Format::Haven(x) => x,
             Format::Scientific(x) => x,
         }
     }
-    pub fn increase_power(&mut self, increment: i32, is_bounce: bool) -> Option<bool> {
-        if (increment == 0) {
-            return Some(true);
-        }
-        let exponent = match self.serialized.clone() {
-            Format::Haven(x) => Haven::get_exponent(x),
-            Format::Scientific(x) => Scientific::get_exponent(x),
-        };
-        handle_analysis_errors!(exponent.is_err(), exponent);
-        let mut flag = false;
-        let new_power = match (is_bounce && (self.exponent + increment) < 0) {
-            false => exponent.ok().unwrap() + increment,
-            true => {
-                flag = true;
-                println!("flag updated");
-                (self.exponent + increment).abs()
-            }
-        };
-        if ((new_power > (HAVEN_ABBREVIATIONS.len() * 3) as i32) || (flag)) {
-            let multiplier = Scientific::get_multiplier(self.get_value(), increment);
-            handle_analysis_errors!(multiplier.is_err(), multiplier);
-            self.exponent = new_power;
-            self.serialized = Format::Scientific(Scientific::create(self.base, new_power, false));
+    /// The value as an f64. Only use this once the magnitude is known to fit, e.g. after
+    /// scaling to screen space.
+    pub fn get_deserialized(&self) -> f64 {
+        sign_from_bool(self.is_positive) as f64 * self.base * Real::powi(10.0, self.exponent)
+    }
+    fn get_signed_base(&self) -> f64 {
+        sign_from_bool(self.is_positive) as f64 * self.base
+    }
+    fn serialize(&mut self) {
+        if self.base == 0.0 {
+            self.serialized = Format::Haven(("0").to_string());
+        } else if !self.base.is_finite() {
+            self.serialized = Format::Haven(self.base.to_string());
+        } else if (self.exponent >= 0) && (self.exponent < (HAVEN_ABBREVIATIONS.len() * 3) as i32) {
+            self.serialized = Format::Haven(Haven::create(self.base, self.exponent));
         } else {
-            let multiplier = Haven::get_multiplier(self.get_value(), new_power);
-            handle_analysis_errors!(multiplier.is_err(), multiplier);
-            self.exponent = new_power;
-            self.serialized = Format::Haven(Haven::create(self.base, new_power, false));
+            self.serialized = Format::Scientific(Scientific::create(self.base, self.exponent));
         }
+    }
+    /// Multiplies by `10^increment`, zero stays zero.
+    pub fn increase_power(&mut self, increment: i32) -> Option<bool> {
+        if (increment == 0) || (self.base == 0.0) {
+            return Some(true);
+        }
+        self.exponent += increment;
+        self.serialize();
         Some(true)
     }
+    /// Divides by `10^increment`, zero stays zero.
     pub fn decrease_power(&mut self, increment: i32) -> Option<bool> {
-        self.increase_power(-increment, false)
+        self.increase_power(-increment)
     }
 }
 impl Default for BigNumber {
@@ -235,46 +234,69 @@ impl Default for BigNumber {
         }
     }
 }
+impl fmt::Display for BigNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.is_positive || self.base == 0.0 {
+            true => write!(f, "{}", self.get_value()),
+            false => write!(f, "-{}", self.get_value()),
+        }
+    }
+}
 impl PartialEq for BigNumber {
     fn eq(&self, other: &Self) -> bool {
-        ((self.is_positive == other.is_positive)
+        (self.is_positive == other.is_positive)
             && (self.base == other.base)
-            && (self.exponent == other.exponent))
+            && (self.exponent == other.exponent)
     }
 }
 impl Eq for BigNumber {}
 impl PartialOrd for BigNumber {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let mut result = None;
-        result = match self.exponent.partial_cmp(&other.exponent) {
-            Some(Ordering::Equal) => self.base.partial_cmp(&other.base),
-            Some(x) => Some(x),
-            None => result,
-        };
-        result
+        Some(self.cmp(other))
     }
     fn lt(&self, other: &Self) -> bool {
-        (self.partial_cmp(other).unwrap() == Ordering::Less)
+        self.partial_cmp(other).unwrap() == Ordering::Less
     }
     fn le(&self, other: &Self) -> bool {
-        ((self.lt(other)) || (self.partial_cmp(other).unwrap() == Ordering::Equal))
+        (self.lt(other)) || (self.partial_cmp(other).unwrap() == Ordering::Equal)
     }
     fn gt(&self, other: &Self) -> bool {
-        (self.partial_cmp(other).unwrap() == Ordering::Greater)
+        self.partial_cmp(other).unwrap() == Ordering::Greater
     }
     fn ge(&self, other: &Self) -> bool {
-        ((self.gt(other)) || (self.partial_cmp(other).unwrap() == Ordering::Equal))
+        (self.gt(other)) || (self.partial_cmp(other).unwrap() == Ordering::Equal)
     }
 }
 impl Ord for BigNumber {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+        let sign = |x: &BigNumber| match (x.base == 0.0, x.is_positive) {
+            (true, _) => 0,
+            (false, true) => 1,
+            (false, false) => -1,
+        };
+        let (self_sign, other_sign) = (sign(self), sign(other));
+        if (self_sign != other_sign) || (self_sign == 0) {
+            return self_sign.cmp(&other_sign);
+        }
+        // Infinity and NaN are past every finite exponent
+        let exponent = |x: &BigNumber| match x.base.is_finite() {
+            true => x.exponent,
+            false => i32::MAX,
+        };
+        let magnitude = match exponent(self).cmp(&exponent(other)) {
+            Ordering::Equal => self.base.total_cmp(&other.base),
+            x => x,
+        };
+        match self_sign > 0 {
+            true => magnitude,
+            false => magnitude.reverse(),
+        }
     }
     fn max(self, other: Self) -> Self
     where
         Self: Sized,
     {
-        match (self > other) {
+        match self > other {
             false => other,
             true => self,
         }
@@ -283,7 +305,7 @@ impl Ord for BigNumber {
     where
         Self: Sized,
     {
-        match (self < other) {
+        match self < other {
             false => other,
             true => self,
         }
@@ -296,9 +318,9 @@ impl Ord for BigNumber {
         (self.min(max)).max(min)
     }
 }
-impl ops::Add<f32> for BigNumber {
+impl ops::Add<f64> for BigNumber {
     type Output = BigNumber;
-    fn add(self, rhs: f32) -> BigNumber {
+    fn add(self, rhs: f64) -> BigNumber {
         let big_version = BigNumber::new_d(rhs);
         self + big_version
     }
@@ -306,55 +328,46 @@ impl ops::Add<f32> for BigNumber {
 impl ops::Add for BigNumber {
     type Output = BigNumber;
     fn add(self, other: BigNumber) -> BigNumber {
-        let mut sum = BigNumber::max(self.clone(), other.clone());
-        let mut other_min = BigNumber::min(self.clone(), other.clone());
-        let base_delta =
-            other_min.base / Real::powi(10.0, sum.exponent - other_min.exponent) as f32;
-        let new_base = (sum.base * sign_from_bool(sum.is_positive) as f32)
-            + (sign_from_bool(other_min.is_positive) as f32 * base_delta);
-        let is_bounce = ((new_base < 0.0) && sum.is_positive);
-        let exponent_difference = new_base.log10().floor()
-            - match is_bounce {
-                false => new_base.log10().floor(),
-                true => new_base.log10().floor() - other_min.base.log10(),
-            };
-        if (is_bounce) {
-            sum.is_positive = false;
+        if self.base == 0.0 {
+            return other;
+        } else if other.base == 0.0 {
+            return self;
         }
-        sum.increase_power(
-            match exponent_difference as i32 * sign_from_bool(other_min.is_positive) {
-                x => {
-                    let mut new_x = x;
-                    if (is_bounce) {
-                        if (x > 0) {
-                            new_x = x + 1;
-                        } else {
-                            new_x = x - 1;
-                        }
-                    } else {
-                        new_x += other_min.base.log10().floor() as i32;
-                    }
-                    new_x
-                }
-            },
-            is_bounce,
-        );
-        sum.base = get_first_significant_figure(new_base.abs());
-        match sum.serialized {
-            Format::Haven(_) => {
-                sum.serialized = Format::Haven(Haven::create(sum.base, sum.exponent, false));
-            }
-            Format::Scientific(_) => {
-                sum.serialized =
-                    Format::Scientific(Scientific::create(sum.base, sum.exponent, false));
-            }
+        // Align the smaller exponent to the larger one, anything below f64 precision vanishes
+        let (larger, smaller) = match self.exponent >= other.exponent {
+            true => (self, other),
+            false => (other, self),
+        };
+        let base_delta = smaller.get_signed_base()
+            / Real::powi(10.0, (larger.exponent - smaller.exponent).min(400));
+        BigNumber::from_parts(larger.get_signed_base() + base_delta, larger.exponent)
+    }
+}
+impl ops::Sub<f64> for BigNumber {
+    type Output = BigNumber;
+    fn sub(self, rhs: f64) -> BigNumber {
+        self + BigNumber::new_d(-rhs)
+    }
+}
+impl ops::Sub for BigNumber {
+    type Output = BigNumber;
+    fn sub(self, other: BigNumber) -> BigNumber {
+        self + (-other)
+    }
+}
+impl ops::Neg for BigNumber {
+    type Output = BigNumber;
+    fn neg(self) -> BigNumber {
+        let mut negated = self;
+        if negated.base != 0.0 {
+            negated.is_positive = !negated.is_positive;
         }
-        sum
+        negated
     }
 }
-impl ops::Mul<f32> for BigNumber {
+impl ops::Mul<f64> for BigNumber {
     type Output = BigNumber;
-    fn mul(self, rhs: f32) -> Self::Output {
+    fn mul(self, rhs: f64) -> Self::Output {
         let big_version = BigNumber::new_d(rhs);
         self * big_version
     }
@@ -362,202 +375,235 @@ impl ops::Mul<f32> for BigNumber {
 impl ops::Mul for BigNumber {
     type Output = BigNumber;
     fn mul(self, other: BigNumber) -> Self::Output {
-        let mut product = self.clone();
-        let multiplier = match product.serialized {
-            Format::Haven(ref x) => Haven::get_multiplier(x.to_string(), product.exponent),
-            Format::Scientific(ref x) => Ok(product.base),
-        }
-        .ok()
-        .unwrap()
-            * match other.serialized {
-                Format::Haven(ref x) => Haven::get_multiplier(x.to_string(), other.exponent),
-                Format::Scientific(ref x) => Ok(other.base),
-            }
-            .ok()
-            .unwrap();
-        let mut new_multiplier: f32 = 0.0;
-        match product.serialized {
-            Format::Haven(ref x) => {
-                let original_multiplier = Haven::get_multiplier(x.to_string(), product.exponent);
-                let unwrapped_multiplier = original_multiplier.ok().unwrap();
-                let difference = multiplier.log10().floor() - unwrapped_multiplier.log10().floor();
-                let factor = (10 as i32).pow(match exponential_modulo_ten(
-                    multiplier,
-                    unwrapped_multiplier,
-                ) {
-                    x => {
-                        let mut result = (unwrapped_multiplier).log10().floor() as i32 + x;
-                        let is_less_than = result < 0;
-                        let is_greater_than = result > 2;
-                        if (is_greater_than) {
-                            result = 0;
-                        } else if (is_less_than) {
-                            result = 2;
-                        };
-                        result
-                    }
-                } as u32);
-                new_multiplier =
-                    get_first_significant_figure(unwrapped_multiplier * other.base) * factor as f32;
-                ()
-            }
-            Format::Scientific(ref x) => {
-                new_multiplier = get_first_significant_figure(multiplier);
-                let change = (get_first_significant_figure(self.base)
-                    * get_first_significant_figure(other.base))
-                .log10()
-                .floor();
-                if (change == 1.0) {
-                    product.increase_power(change as i32, false);
-                }
-                ()
-            }
-        }
-        product.increase_power(other.exponent, false);
-        match product.serialized {
-            Format::Haven(_) => {
-                product.serialized =
-                    Format::Haven(Haven::create(new_multiplier, product.exponent, true));
-            }
-            Format::Scientific(_) => {
-                product.serialized =
-                    Format::Scientific(Scientific::create(new_multiplier, product.exponent, true));
-            }
-        }
-        product.is_positive = bool_from_number(
-            sign_from_bool(product.is_positive) * sign_from_bool(other.is_positive),
-        );
-        product
+        BigNumber::from_parts(
+            self.get_signed_base() * other.get_signed_base(),
+            self.exponent + other.exponent,
+        )
+    }
+}
+impl ops::Div<f64> for BigNumber {
+    type Output = BigNumber;
+    fn div(self, rhs: f64) -> Self::Output {
+        self / BigNumber::new_d(rhs)
+    }
+}
+impl ops::Div for BigNumber {
+    type Output = BigNumber;
+    fn div(self, other: BigNumber) -> Self::Output {
+        BigNumber::from_parts(
+            self.get_signed_base() / other.get_signed_base(),
+            self.exponent - other.exponent,
+        )
     }
 }
 enum AnalysisErrors {
     // Haven
-    InvalidPrefix,
-    InvalidSuffix,
+    Prefix,
+    Suffix,
     //
-    InvalidExponent,
+    Exponent,
 }
 trait NumberMethods {
-    fn get_exponent(x: String) -> Result<i32, AnalysisErrors>;
-    fn get_multiplier(x: String, exponent: i32) -> Result<f32, AnalysisErrors>;
-    fn create(a: f32, b: i32, is_product: bool) -> String;
+    // The serialized text as `(mantissa, exponent)`
+    fn parse(x: &str) -> Result<(f64, i32), AnalysisErrors>;
+    fn create(a: f64, b: i32) -> String;
 }
 struct Haven;
 struct Scientific;
 impl NumberMethods for Haven {
-    fn get_exponent(x: String) -> Result<i32, AnalysisErrors> {
-        let mut abbreviation = String::new();
-        let mut rest = x
-            .chars()
-            .rev()
-            .skip_while(|char| {
-                let is_alphabetic = char.is_ascii_alphabetic();
-                if (is_alphabetic) {
-                    abbreviation.push(*char);
-                }
-                is_alphabetic
-            })
-            .collect::<String>();
-        abbreviation = abbreviation.chars().rev().collect::<String>();
-        rest = rest.chars().rev().collect::<String>();
-        if (abbreviation.is_empty()) {
-            let num = x.parse::<f32>();
-            let unwrapped = match num {
-                Ok(n) => n,
-                Err(error) => -1.0,
-            };
-            if (!bool_from_number(unwrapped as i32)) {
-                return Err(AnalysisErrors::InvalidPrefix);
-            }
-            return Ok(((unwrapped as f32).log10().floor()) as i32);
-        } else {
-            let mut position = None;
-            for (index, suffix) in HAVEN_ABBREVIATIONS.iter().enumerate() {
-                if (index == 0) {
-                    continue;
-                }
-                if (*suffix.unwrap() == abbreviation) {
-                    position = Some(index);
-                    break;
-                }
-            }
-            if (position.is_some()) {
-                let parsed_rest = rest.parse::<f32>();
-                if (parsed_rest.is_err()) {
-                    return Err(AnalysisErrors::InvalidPrefix);
-                }
-                let result = parsed_rest.ok().unwrap().log10().floor();
-                return Ok(((position.unwrap() as i32 * 3 as i32)
-                    - reverse_number(1, 3, result as i32)
-                    + 1));
-            } else {
-                return Err(AnalysisErrors::InvalidSuffix);
-            }
-        }
-    }
-    fn get_multiplier(x: String, exponent: i32) -> Result<f32, AnalysisErrors> {
-        match x
-            .chars()
-            .take_while(|char| char.is_ascii_digit() || *char == '.')
-            .collect::<String>()
-            .parse::<f32>()
-        {
-            Ok(number) => match (number < 1000.0) {
-                true => Ok(number),
-                false => Ok(get_first_significant_figure(number)
-                    * (cyclic_wrap((((number).log10()).floor() as i32), exponent, 3)) as f32),
-            },
-            Err(error) => Err(AnalysisErrors::InvalidPrefix),
-        }
+    fn parse(x: &str) -> Result<(f64, i32), AnalysisErrors> {
+        let x = x.trim();
+        let split = x.len()
+            - x.chars()
+                .rev()
+                .take_while(|x| x.is_ascii_alphabetic())
+                .count();
+        let (number, abbreviation) = x.split_at(split);
+        let position = match abbreviation.is_empty() {
+            true => 0,
+            false => HAVEN_ABBREVIATIONS
+                .iter()
+                .position(|x| x.is_some_and(|x| x.eq_ignore_ascii_case(abbreviation)))
+                .ok_or(AnalysisErrors::Suffix)?,
+        };
+        let (sign, number, exponent) = split_sign_and_exponent(number)?;
+        let (mantissa, shift) = parse_decimal(number).ok_or(AnalysisErrors::Prefix)?;
+        let exponent = exponent
+            .checked_add(shift)
+            .and_then(|x| x.checked_add(position as i32 * 3))
+            .ok_or(AnalysisErrors::Exponent)?;
+        Ok((sign * mantissa, exponent))
     }
-    fn create(a: f32, b: i32, is_product: bool) -> String {
+    fn create(a: f64, b: i32) -> String {
         let mut serialized = String::new();
-        let abbreviation = HAVEN_ABBREVIATIONS[(b as f32 / 3.0).floor() as usize];
-        if (is_product) {
-            serialized.push_str(a.to_string().as_str());
-        } else {
-            serialized.push_str(
-                (get_first_significant_figure(a) * Real::powi(10.0, b % 3))
-                    .to_string()
-                    .as_str(),
-            );
-        }
-        match abbreviation {
-            Some(x) => {
-                serialized.push_str(abbreviation.unwrap());
-            }
-            _ => {}
+        let abbreviation = HAVEN_ABBREVIATIONS[(b as f64 / 3.0).floor() as usize];
+        serialized.push_str(
+            round_to_precision(get_first_significant_figure(a) * Real::powi(10.0, b % 3))
+                .to_string()
+                .as_str(),
+        );
+        if let Some(x) = abbreviation {
+            serialized.push_str(x);
         }
         serialized
     }
 }
 impl NumberMethods for Scientific {
-    fn get_exponent(x: String) -> Result<i32, AnalysisErrors> {
-        let mut exponent = x
-            .chars()
-            .rev()
-            .take_while(|char| char.is_ascii_digit())
-            .collect::<String>();
-        exponent = exponent.chars().rev().collect::<String>();
-        let parsed_exponent = exponent.parse::<i32>();
-        match parsed_exponent {
-            Ok(number) => Ok(number),
-            Err(error) => Err(AnalysisErrors::InvalidExponent),
+    fn parse(x: &str) -> Result<(f64, i32), AnalysisErrors> {
+        let (number, exponent) = x.split_once("x10^").ok_or(AnalysisErrors::Exponent)?;
+        let exponent = exponent
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| AnalysisErrors::Exponent)?;
+        let (mantissa, shift) = Haven::parse(number)?;
+        match exponent.checked_add(shift) {
+            Some(x) => Ok((mantissa, x)),
+            None => Err(AnalysisErrors::Exponent),
         }
     }
-    fn get_multiplier(x: String, exponent: i32) -> Result<f32, AnalysisErrors> {
-        Haven::get_multiplier(x, exponent)
-    }
-    fn create(a: f32, b: i32, is_product: bool) -> String {
+    fn create(a: f64, b: i32) -> String {
         let mut serialized = String::new();
-        serialized.push_str(get_first_significant_figure(a).to_string().as_str());
+        serialized.push_str(
+            round_to_precision(get_first_significant_figure(a))
+                .to_string()
+                .as_str(),
+        );
         serialized.push_str("x10^");
         serialized.push_str(b.to_string().as_str());
         serialized
     }
 }
+/// A point of the plane with [`BigNumber`] coordinates.
 #[derive(Clone)]
 pub struct BigVec2 {
+    /// The horizontal coordinate
     pub x: BigNumber,
+    /// The vertical coordinate
     pub y: BigNumber,
 }
+impl BigVec2 {
+    /// The point `(x, y)`.
+    pub fn new(x: BigNumber, y: BigNumber) -> BigVec2 {
+        BigVec2 { x, y }
+    }
+    /// The point `(x, y)` from f64 coordinates.
+    pub fn new_d(x: f64, y: f64) -> BigVec2 {
+        BigVec2 {
+            x: BigNumber::new_d(x),
+            y: BigNumber::new_d(y),
+        }
+    }
+}
+impl ops::Add for BigVec2 {
+    type Output = BigVec2;
+    fn add(self, other: BigVec2) -> BigVec2 {
+        BigVec2::new(self.x + other.x, self.y + other.y)
+    }
+}
+impl ops::Sub for BigVec2 {
+    type Output = BigVec2;
+    fn sub(self, other: BigVec2) -> BigVec2 {
+        BigVec2::new(self.x - other.x, self.y - other.y)
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn assert_close(a: BigNumber, b: f64) {
+        let value = a.get_deserialized();
+        assert!(
+            (value - b).abs() <= b.abs() * 1e-12,
+            "expected {} but got {}",
+            b,
+            value
+        );
+    }
+    #[test]
+    fn arithmetic() {
+        assert_close(BigNumber::new_d(99.0) + BigNumber::new_d(1.0), 100.0);
+        assert_close(BigNumber::new_d(0.0) + BigNumber::new_d(5.0), 5.0);
+        assert_close(BigNumber::new_d(-3.0) + BigNumber::new_d(5.0), 2.0);
+        assert_close(BigNumber::new_d(1e10) - BigNumber::new_d(1.0), 1e10 - 1.0);
+        assert_close(BigNumber::new_d(5.0) * -3.0, -15.0);
+        assert_close(BigNumber::new_d(123.0) * 0.5, 61.5);
+        assert_close(BigNumber::new_d(1.0) / BigNumber::new_d(8.0), 0.125);
+        assert_eq!(
+            (BigNumber::new_d(1e200) * BigNumber::new_d(1e200)).exponent,
+            400
+        );
+    }
+    #[test]
+    fn ordering_respects_sign() {
+        assert!(BigNumber::new_d(-5.0) < BigNumber::new_d(0.001));
+        assert!(BigNumber::new_d(0.0) < BigNumber::new_d(0.001));
+        assert!(BigNumber::new_d(-500.0) < BigNumber::new_d(-2.0));
+        assert!(BigNumber::new_d(7e30) > BigNumber::new_d(6e30));
+    }
+    #[test]
+    fn serialization() {
+        assert_eq!(BigNumber::new_d(1234.0).to_string(), "1.234K");
+        assert_eq!(BigNumber::new_d(-0.003).to_string(), "-3x10^-3");
+        assert_eq!(
+            BigNumber::new_d(-1234.0).to_scientific_string(),
+            "-1.234x10^3"
+        );
+        assert_eq!(BigNumber::new_d(7e30).to_string(), "7x10^30");
+        assert_eq!((BigNumber::new_d(-5000.0) + -200.0).to_string(), "-5.2K");
+    }
+    #[test]
+    fn non_finite_values() {
+        let zero = BigNumber::new_d(0.0);
+        assert_eq!((BigNumber::new_d(1.0) / zero.clone()).to_string(), "inf");
+        assert_eq!((BigNumber::new_d(-2.0) / 0.0).to_string(), "-inf");
+        assert_eq!((zero.clone() / zero).to_string(), "NaN");
+        assert_eq!(BigNumber::new_d(f64::NAN).to_string(), "NaN");
+        assert_eq!(BigNumber::new_d(-f64::NAN).to_string(), "NaN");
+        assert_eq!(
+            BigNumber::new_d(f64::INFINITY).to_scientific_string(),
+            "inf"
+        );
+        assert_eq!(
+            BigNumber::new_d(f64::NEG_INFINITY).to_scientific_string(),
+            "-inf"
+        );
+        assert_eq!(BigNumber::new_d(f64::NAN).to_scientific_string(), "NaN");
+        assert_eq!(BigNumber::new_d(0.0).to_scientific_string(), "0");
+        assert_eq!(BigNumber::from_parts(f64::INFINITY, 12).exponent, 0);
+        assert!(BigNumber::new_d(f64::NEG_INFINITY) < BigNumber::new_d(-1e300));
+    }
+    #[test]
+    fn parsing() {
+        assert_close(BigNumber::parse("1.5K").unwrap(), 1500.0);
+        assert_close(BigNumber::parse(" -0.05 ").unwrap(), -0.05);
+        assert_close(BigNumber::parse("2.5e-3").unwrap(), 0.0025);
+        assert_close(BigNumber::parse("7x10^30").unwrap(), 7e30);
+        assert_close(BigNumber::parse_plain("-2.5E-3").unwrap(), -0.0025);
+        assert!(BigNumber::parse_plain("1.5K").is_none());
+        assert_eq!(BigNumber::parse_plain("1e400").unwrap().exponent, 400);
+        let huge = BigNumber::parse("-1.5e400").unwrap();
+        assert_eq!(
+            (huge.base, huge.exponent, huge.is_positive),
+            (1.5, 400, false)
+        );
+        let long = format!("1{}", "0".repeat(400));
+        assert_eq!(BigNumber::parse(&long).unwrap().exponent, 400);
+        for number in [1234.0, -0.003, 7e30, 0.0, 42.0] {
+            let text = BigNumber::new_d(number).to_string();
+            assert!(BigNumber::parse(&text).unwrap() == BigNumber::new_d(number));
+        }
+        for number in [
+            BigNumber::new_d(0.1) / BigNumber::new_d(3.0),
+            -BigNumber::from_parts(std::f64::consts::PI, -400),
+        ] {
+            let parsed = BigNumber::parse(&number.to_exact_string()).unwrap();
+            assert_eq!(
+                (parsed.base, parsed.exponent),
+                (number.base, number.exponent)
+            );
+            assert_eq!(parsed.is_positive, number.is_positive);
+        }
+        for text in ["", "abc", "1.2.3", "5Z", "1e", "--1", "inf"] {
+            assert!(BigNumber::parse(text).is_none(), "{}", text);
+        }
+    }
+}
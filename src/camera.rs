@@ -0,0 +1,141 @@
+//! The view onto the plane: which world point is in the middle of the screen and how
+//! many pixels one unit takes.
+use crate::big_number::{BigNumber, BigVec2};
+use crate::prelude::*;
+#[cfg(feature = "ui")]
+use macroquad::window::screen_width;
+
+// Smallest pixel relative to the distance from the origin, a few hundred f64 steps so
+// neighbouring pixels still evaluate to distinct, smoothly changing values
+const MIN_RELATIVE_PIXEL: f64 = 1e-13;
+
+// Helper Functions
+// The most pixels per unit that still resolve around `point`, None at the origin where
+// f64 keeps full precision at any zoom
+fn get_zoom_limit(point: &BigVec2) -> Option<BigNumber> {
+    let magnitude = |x: &BigNumber| BigNumber {
+        is_positive: true,
+        ..x.clone()
+    };
+    let largest = magnitude(&point.x).max(magnitude(&point.y));
+    match largest.base == 0.0 {
+        true => None,
+        false => Some(BigNumber::new_d(1.0) / (largest * MIN_RELATIVE_PIXEL)),
+    }
+}
+//
+
+/// The part of the plane on screen.
+///
+/// World coordinates stay in BigNumber space until they have been scaled to screen pixels,
+/// which keeps deep zooms from collapsing into f32 jitter. Expressions are still evaluated
+/// in f64 though, so how far in the view can go depends on where it is. Around the origin
+/// any zoom works, around a point `p` zooming in stops once a pixel is about
+/// `1e-13 * |p|` units wide. Zooming out is not limited.
+pub struct Camera {
+    /// World coordinate shown in the middle of the screen
+    pub position: BigVec2,
+    /// Pixels between two consecutive whole numbers
+    pub number_distance: BigNumber,
+}
+impl Camera {
+    /// Centered on the origin with ten units across the screen. Reads the screen size,
+    /// which needs a window.
+    #[cfg(feature = "ui")]
+    pub fn new() -> Self {
+        Camera::default()
+    }
+    /// The zoom as an f64, only for zooms that fit one.
+    pub fn get_pixels_per_unit(&self) -> f64 {
+        self.number_distance.get_deserialized()
+    }
+    /// The pixel a world point lands on, for a screen of size `screen`.
+    pub fn world_to_screen(&self, point: &BigVec2, screen: Vec2) -> Vec2 {
+        let offset = point.clone() - self.position.clone();
+        let x = offset.x * self.number_distance.clone();
+        let y = offset.y * self.number_distance.clone();
+        vec2(
+            screen.x / 2.0 + x.get_deserialized() as f32,
+            screen.y / 2.0 - y.get_deserialized() as f32,
+        )
+    }
+    /// The world point under a pixel, the inverse of [`Camera::world_to_screen`].
+    pub fn screen_to_world(&self, pixel: Vec2, screen: Vec2) -> BigVec2 {
+        let x = BigNumber::new_d((pixel.x - screen.x / 2.0) as f64) / self.number_distance.clone();
+        let y = BigNumber::new_d((screen.y / 2.0 - pixel.y) as f64) / self.number_distance.clone();
+        self.position.clone() + BigVec2::new(x, y)
+    }
+    /// Moves the view by `delta` pixels, as when dragging the plane.
+    pub fn pan(&mut self, delta: Vec2) {
+        let x = BigNumber::new_d(-delta.x as f64) / self.number_distance.clone();
+        let y = BigNumber::new_d(delta.y as f64) / self.number_distance.clone();
+        self.position = self.position.clone() + BigVec2::new(x, y);
+    }
+    /// Scales around `pixel` so the world point under the cursor does not move. Zooming in
+    /// stops at the limit described on [`Camera`].
+    pub fn zoom_at(&mut self, pixel: Vec2, factor: f64, screen: Vec2) {
+        let anchor = self.screen_to_world(pixel, screen);
+        let factor = match get_zoom_limit(&anchor) {
+            Some(most) if (factor > 1.0) => {
+                let room = (most / self.number_distance.clone()).get_deserialized();
+                // Below 1 when the camera is already past the limit, e.g. after a session load
+                factor.min(room)
+            }
+            _ => factor,
+        };
+        let offset = self.position.clone() - anchor.clone();
+        self.position = anchor + BigVec2::new(offset.x / factor, offset.y / factor);
+        self.number_distance = self.number_distance.clone() * factor;
+    }
+}
+#[cfg(feature = "ui")]
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            position: BigVec2 {
+                x: BigNumber::new_d(0.0),
+                y: BigNumber::new_d(0.0),
+            },
+            number_distance: BigNumber::new_d((screen_width() / 10.0) as f64),
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn round_trip_at_extreme_zoom() {
+        let screen = vec2(1000.0, 500.0);
+        for zoom in [1e-30, 1.0, 1e30] {
+            let camera = Camera {
+                position: BigVec2::new_d(3.0 / zoom, -7.0 / zoom),
+                number_distance: BigNumber::new_d(zoom),
+            };
+            let pixel = vec2(123.0, 456.0);
+            let back = camera.world_to_screen(&camera.screen_to_world(pixel, screen), screen);
+            assert!((back - pixel).length() < 1e-3);
+        }
+    }
+    #[test]
+    fn zoom_keeps_anchor_fixed() {
+        let screen = vec2(1000.0, 500.0);
+        let mut camera = Camera {
+            position: BigVec2::new_d(2.0, 2.0),
+            number_distance: BigNumber::new_d(100.0),
+        };
+        let pixel = vec2(300.0, 450.0);
+        for _ in 0..100 {
+            let anchor = camera.screen_to_world(pixel, screen);
+            camera.zoom_at(pixel, 2.0, screen);
+            let moved = camera.world_to_screen(&anchor, screen);
+            assert!((moved - pixel).length() < 1e-2);
+        }
+        // The anchor is the origin, where zooming has no limit
+        assert_eq!(camera.number_distance.exponent, 32);
+        // Elsewhere it stops where a pixel is 1e-13 of the distance to the origin
+        camera.position = BigVec2::new_d(1.5, 0.25);
+        camera.zoom_at(pixel, 2.0, screen);
+        let limit = 1.0 / (camera.position.x.get_deserialized() * 1e-13);
+        assert!((camera.get_pixels_per_unit() / limit - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,580 @@
+use crate::repl;
+use graphing_calculator::algebra_parser::evaluator::{self, Context, Scope, Value};
+use graphing_calculator::algebra_parser::Node;
+use graphing_calculator::big_number::{BigNumber, BigVec2};
+use graphing_calculator::camera::Camera;
+use graphing_calculator::derivative_solver::derivative;
+use graphing_calculator::entries::{EntryKind, EntryList, ExpressionEntry};
+use graphing_calculator::grid;
+use graphing_calculator::intersections;
+use graphing_calculator::raster;
+use graphing_calculator::scene::Scene;
+use graphing_calculator::shapes::ShapeCache;
+use graphing_calculator::svg;
+use macroquad::prelude::*;
+use std::io::{IsTerminal, Read, Write};
+
+const UNKNOWN_COMMAND_ERROR: &str = "Unknown command";
+const UNKNOWN_OPTION_ERROR: &str = "Unknown option";
+const MISSING_VALUE_ERROR: &str = "Option needs a value";
+const NUMBER_ERROR: &str = "Could not read number";
+const RANGE_ERROR: &str = "Range must be written min:max with min below max";
+const ASSIGNMENT_ERROR: &str = "Variables are set with name=value";
+const SIZE_ERROR: &str = "Size must be written widthxheight";
+const POINT_ERROR: &str = "Center must be written x,y";
+const NO_EXPRESSIONS_ERROR: &str = "No expressions given";
+const FORMAT_ERROR: &str = "Output file must end in .svg or .png";
+const KIND_ERROR: &str = "Only functions of x can be evaluated, plot it instead";
+const X_NEEDED_ERROR: &str = "Expression depends on x, give values with --x";
+//
+const USAGE: &str = "\
+Usage: graphing-calculator <command> [options] [expression]...
+
+Commands:
+  eval    Print the value of each expression
+  diff    Print the derivative of each expression
+  plot    Draw the expressions into an SVG or PNG file
+  repl    Start an interactive calculator, :help lists its commands
+  help    Print this message
+
+Expressions are read one per line from stdin when none are given.
+Without a command the interactive window opens.
+
+Options:
+  --x <value>[,<value>...]  Evaluate at these x, may be repeated
+  --roots <min>:<max>       Find where the result is 0 between min and max
+  --order <n>               Derivative order for diff (default 1)
+  --set <name>=<value>      Give a variable a value
+  --json                    Print JSON instead of text
+  -o, --output <path>       File for plot, .svg or .png (default graph.svg)
+  --size <width>x<height>   Image size in pixels (default 800x600)
+  --center <x>,<y>          Point in the middle of the image (default 0,0)
+  --scale <pixels>          Pixels between whole numbers (default 50)
+";
+const ERROR_EXIT: i32 = 1;
+const USAGE_EXIT: i32 = 2;
+// Brackets searched for sign changes by --roots
+const ROOT_SAMPLES: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Eval,
+    Diff,
+    Plot,
+    Repl,
+    Help,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub expressions: Vec<String>,
+    pub at: Vec<f64>,
+    pub roots: Option<(f64, f64)>,
+    pub order: u8,
+    pub variables: Vec<(String, f64)>,
+    pub json: bool,
+    pub output: String,
+    pub size: Vec2,
+    pub center: (f64, f64),
+    pub scale: f64,
+}
+// Everything printed about one expression
+#[derive(Debug, Clone, PartialEq)]
+struct Report {
+    expression: String,
+    derivative: Option<String>,
+    // `None` for expressions that don't depend on x
+    values: Vec<(Option<f64>, Value)>,
+    roots: Option<Vec<f64>>,
+    error: Option<String>,
+}
+
+// Helper Functions
+// Option values are expressions too, so `--x pi/2` works
+fn parse_number(text: &str) -> Result<f64, String> {
+    let error = || format!("{}: {}", NUMBER_ERROR, text.trim());
+    let node = graphing_calculator::parse(text).map_err(|_| error())?;
+    match graphing_calculator::evaluate(&node, &Context::default()) {
+        Ok(x) if (x.is_finite()) => Ok(x),
+        _ => Err(error()),
+    }
+}
+fn parse_pair(text: &str, separator: char, error: &str) -> Result<(f64, f64), String> {
+    let (a, b) = text
+        .split_once(separator)
+        .ok_or_else(|| format!("{}: {}", error, text))?;
+    Ok((parse_number(a)?, parse_number(b)?))
+}
+// Plain decimals where they are short, scientific notation for tiny and huge values
+fn format_number(x: f64) -> String {
+    let magnitude = x.abs();
+    match (x != 0.0 && !(1e-6..1e16).contains(&magnitude)) {
+        true => format!("{:e}", x),
+        false => format!("{}", x),
+    }
+}
+fn format_text(x: f64) -> String {
+    match (x.is_finite()) {
+        true => format_number(x),
+        false => "undefined".to_string(),
+    }
+}
+fn format_json(x: f64) -> String {
+    match (x.is_finite()) {
+        true => format_number(x),
+        false => "null".to_string(),
+    }
+}
+fn format_value(value: &Value, format: fn(f64) -> String) -> String {
+    match value {
+        Value::Number(x) => format(*x),
+        Value::List(x) => format!(
+            "[{}]",
+            x.iter().map(|x| format(*x)).collect::<Vec<_>>().join(", ")
+        ),
+    }
+}
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for char in text.chars() {
+        match char {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            x if (x.is_control()) => quoted.push_str(&format!("\\u{:04x}", x as u32)),
+            x => quoted.push(x),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+fn depends_on_x(node: &Node) -> bool {
+    node.get_variables().iter().any(|x| x == "x")
+}
+fn fill_report(
+    report: &mut Report,
+    entry: &ExpressionEntry,
+    options: &Options,
+    context: &Context,
+) -> Result<(), String> {
+    if let Some(x) = entry.get_error(context) {
+        return Err(x);
+    }
+    if (entry.get_kind() != EntryKind::Function) {
+        return Err(KIND_ERROR.to_string());
+    }
+    let mut node = entry.ast.clone()?;
+    if (options.command == Command::Diff) {
+        for _ in 0..options.order {
+            node = derivative::differentiate(&node, "x")?;
+        }
+        report.derivative = Some(node.to_string());
+    }
+    // Values that don't change with x are printed once
+    match (depends_on_x(&node), options.command) {
+        (true, Command::Eval) if (options.at.is_empty() && options.roots.is_none()) => {
+            return Err(X_NEEDED_ERROR.to_string())
+        }
+        (true, _) => {
+            for x in options.at.iter() {
+                let value =
+                    evaluator::evaluate_value_in(&node, &Scope::new(context, &[("x", *x)]))?;
+                report.values.push((Some(*x), value));
+            }
+        }
+        (false, Command::Eval) => {
+            let value = evaluator::evaluate_value(&node, context)?;
+            report.values.push((None, value));
+        }
+        (false, _) => {}
+    }
+    if let Some((min, max)) = options.roots {
+        let f = |x: f64| {
+            evaluator::evaluate_in(&node, &Scope::new(context, &[("x", x)])).unwrap_or(f64::NAN)
+        };
+        let roots = intersections::find_intersections(f, |_| 0.0, min, max, ROOT_SAMPLES);
+        report.roots = Some(roots);
+    }
+    Ok(())
+}
+fn write_text(report: &Report, options: &Options, out: &mut dyn Write, err: &mut dyn Write) {
+    if let Some(ref error) = report.error {
+        let _ = writeln!(err, "{}: {}", report.expression, error);
+        return;
+    }
+    let subject = report.derivative.as_ref().unwrap_or(&report.expression);
+    if let Some(ref derivative) = report.derivative {
+        let operator = match options.order {
+            1 => "d/dx".to_string(),
+            n => format!("d^{0}/dx^{0}", n),
+        };
+        let _ = writeln!(out, "{} {} = {}", operator, report.expression, derivative);
+    }
+    for (x, value) in report.values.iter() {
+        let value = format_value(value, format_text);
+        let _ = match x {
+            Some(x) => writeln!(out, "{} at x = {}: {}", subject, format_text(*x), value),
+            None => writeln!(out, "{} = {}", subject, value),
+        };
+    }
+    if let (Some(roots), Some((min, max))) = (&report.roots, options.roots) {
+        let _ = match roots.is_empty() {
+            true => writeln!(out, "{} has no roots in [{}, {}]", subject, min, max),
+            false => writeln!(
+                out,
+                "{} = 0 at x = {}",
+                subject,
+                roots
+                    .iter()
+                    .map(|x| format_text(*x))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+    }
+}
+fn to_json(report: &Report) -> String {
+    let mut fields = vec![format!(
+        "\"expression\": {}",
+        json_string(&report.expression)
+    )];
+    if let Some(ref error) = report.error {
+        fields.push(format!("\"error\": {}", json_string(error)));
+    }
+    if let Some(ref derivative) = report.derivative {
+        fields.push(format!("\"derivative\": {}", json_string(derivative)));
+    }
+    if (!report.values.is_empty()) {
+        let values = report
+            .values
+            .iter()
+            .map(|(x, value)| match x {
+                Some(x) => format!(
+                    "{{\"x\": {}, \"value\": {}}}",
+                    format_json(*x),
+                    format_value(value, format_json)
+                ),
+                None => format!("{{\"value\": {}}}", format_value(value, format_json)),
+            })
+            .collect::<Vec<String>>();
+        fields.push(format!("\"values\": [{}]", values.join(", ")));
+    }
+    if let Some(ref roots) = report.roots {
+        let roots = roots.iter().map(|x| format_json(*x)).collect::<Vec<_>>();
+        fields.push(format!("\"roots\": [{}]", roots.join(", ")));
+    }
+    format!("{{{}}}", fields.join(", "))
+}
+fn read_stdin() -> Result<Vec<String>, String> {
+    let mut stdin = std::io::stdin();
+    // Nothing is piped in, waiting for a terminal would just look like a hang
+    if (stdin.is_terminal()) {
+        return Ok(Vec::new());
+    }
+    let mut text = String::new();
+    stdin.read_to_string(&mut text).map_err(|x| x.to_string())?;
+    Ok(text
+        .lines()
+        .map(|x| x.trim().to_string())
+        .filter(|x| !x.is_empty())
+        .collect())
+}
+//
+
+impl Options {
+    pub fn new(command: Command) -> Options {
+        Options {
+            command,
+            expressions: Vec::new(),
+            at: Vec::new(),
+            roots: None,
+            order: 1,
+            variables: Vec::new(),
+            json: false,
+            output: svg::EXPORT_PATH.to_string(),
+            size: vec2(800.0, 600.0),
+            center: (0.0, 0.0),
+            scale: 50.0,
+        }
+    }
+    // Anything not starting with `--` is an expression, so `-x^2` needs no quoting beyond
+    // the shell's. After `--` everything is an expression
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let (command, rest) = args.split_first().ok_or(NO_EXPRESSIONS_ERROR.to_string())?;
+        let command = match command.as_str() {
+            "eval" => Command::Eval,
+            "diff" => Command::Diff,
+            "plot" => Command::Plot,
+            "repl" => Command::Repl,
+            "help" | "--help" | "-h" => Command::Help,
+            x => return Err(format!("{}: {}", UNKNOWN_COMMAND_ERROR, x)),
+        };
+        let mut options = Options::new(command);
+        let mut rest = rest.iter();
+        let mut only_expressions = false;
+        while let Some(arg) = rest.next() {
+            if (only_expressions) {
+                options.expressions.push(arg.clone());
+                continue;
+            }
+            let mut value = || {
+                rest.next()
+                    .ok_or_else(|| format!("{}: {}", MISSING_VALUE_ERROR, arg))
+            };
+            match arg.as_str() {
+                "--" => only_expressions = true,
+                "--json" => options.json = true,
+                "--x" => {
+                    for x in value()?.split(',') {
+                        options.at.push(parse_number(x)?);
+                    }
+                }
+                "--roots" => {
+                    let (min, max) = parse_pair(value()?, ':', RANGE_ERROR)?;
+                    if (min >= max) {
+                        return Err(format!("{}: {}:{}", RANGE_ERROR, min, max));
+                    }
+                    options.roots = Some((min, max));
+                }
+                "--order" => {
+                    let text = value()?;
+                    options.order = text
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("{}: {}", NUMBER_ERROR, text))?;
+                }
+                "--set" => {
+                    let text = value()?;
+                    let (name, number) = text
+                        .split_once('=')
+                        .ok_or_else(|| format!("{}: {}", ASSIGNMENT_ERROR, text))?;
+                    options
+                        .variables
+                        .push((name.trim().to_string(), parse_number(number)?));
+                }
+                "-o" | "--output" => options.output = value()?.clone(),
+                "--size" => {
+                    let (width, height) = parse_pair(value()?, 'x', SIZE_ERROR)?;
+                    if (width < 1.0 || height < 1.0) {
+                        return Err(format!("{}: {}x{}", SIZE_ERROR, width, height));
+                    }
+                    options.size = vec2(width as f32, height as f32);
+                }
+                "--center" => options.center = parse_pair(value()?, ',', POINT_ERROR)?,
+                "--scale" => {
+                    let text = value()?;
+                    options.scale = match parse_number(text)? {
+                        x if (x > 0.0) => x,
+                        _ => return Err(format!("{}: {}", NUMBER_ERROR, text)),
+                    };
+                }
+                x if (x.starts_with("--")) => {
+                    return Err(format!("{}: {}", UNKNOWN_OPTION_ERROR, x))
+                }
+                x => options.expressions.push(x.to_string()),
+            }
+        }
+        Ok(options)
+    }
+}
+
+// The same layers the window draws, minus everything interactive
+pub fn plot_scene(entries: &EntryList, camera: &Camera, size: Vec2) -> Scene {
+    let mut scene = Scene::new(size);
+    grid::push_to_scene(&grid::compute(camera, size), &mut scene);
+    entries.push_to_scene(&mut scene, camera, 0.0);
+    let mut shapes = ShapeCache::new();
+    entries.push_markers(&mut shapes);
+    shapes.push_to_scene(&mut scene, camera);
+    entries.push_legend(&mut scene);
+    scene
+}
+// Runs a parsed command, returning the exit code
+pub fn execute(options: &Options, out: &mut dyn Write, err: &mut dyn Write) -> i32 {
+    let mut entries = EntryList::new();
+    for (name, value) in options.variables.iter() {
+        entries.set_variable(name, *value);
+    }
+    for expression in options.expressions.iter() {
+        entries.add(expression);
+    }
+    let mut failed = false;
+    if (options.command == Command::Plot) {
+        for entry in entries.entries.iter() {
+            if let Some(error) = entry.get_error(&entries.context) {
+                let _ = writeln!(err, "{}: {}", entry.expression, error);
+                failed = true;
+            }
+        }
+        let camera = Camera {
+            position: BigVec2::new_d(options.center.0, options.center.1),
+            number_distance: BigNumber::new_d(options.scale),
+        };
+        let scene = plot_scene(&entries, &camera, options.size);
+        let path = options.output.as_str();
+        let result = match path.rsplit_once('.').map(|x| x.1.to_lowercase()) {
+            Some(x) if (x == "svg") => svg::export(&scene, path),
+            Some(x) if (x == "png") => raster::export(&scene, path),
+            _ => Err(format!("{}: {}", FORMAT_ERROR, path)),
+        };
+        if let Err(error) = result {
+            let _ = writeln!(err, "{}", error);
+            return ERROR_EXIT;
+        }
+    } else {
+        let mut reports = Vec::new();
+        for entry in entries.entries.iter() {
+            let mut report = Report {
+                expression: entry.expression.clone(),
+                derivative: None,
+                values: Vec::new(),
+                roots: None,
+                error: None,
+            };
+            if let Err(error) = fill_report(&mut report, entry, options, &entries.context) {
+                report.error = Some(error);
+                failed = true;
+            }
+            reports.push(report);
+        }
+        match options.json {
+            true => {
+                let lines = reports.iter().map(to_json).collect::<Vec<String>>();
+                let _ = writeln!(out, "[\n  {}\n]", lines.join(",\n  "));
+            }
+            false => {
+                for report in reports.iter() {
+                    write_text(report, options, out, err);
+                }
+            }
+        }
+    }
+    match failed {
+        true => ERROR_EXIT,
+        false => 0,
+    }
+}
+pub fn run(args: &[String]) -> i32 {
+    let (mut out, mut err) = (std::io::stdout(), std::io::stderr());
+    let mut options = match Options::parse(args) {
+        Ok(x) => x,
+        Err(x) => {
+            let _ = writeln!(err, "{}\n\n{}", x, USAGE);
+            return USAGE_EXIT;
+        }
+    };
+    match options.command {
+        Command::Help => {
+            let _ = write!(out, "{}", USAGE);
+            return 0;
+        }
+        Command::Repl => return repl::run(),
+        _ => {}
+    }
+    if (options.expressions.is_empty()) {
+        options.expressions = match read_stdin() {
+            Ok(x) => x,
+            Err(x) => {
+                let _ = writeln!(err, "{}", x);
+                return ERROR_EXIT;
+            }
+        };
+    }
+    if (options.expressions.is_empty()) {
+        let _ = writeln!(err, "{}\n\n{}", NO_EXPRESSIONS_ERROR, USAGE);
+        return USAGE_EXIT;
+    }
+    execute(&options, &mut out, &mut err)
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn run_with(args: &[&str]) -> (i32, String, String) {
+        let args = args.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+        let options = Options::parse(&args).unwrap();
+        let (mut out, mut err) = (Vec::new(), Vec::new());
+        let code = execute(&options, &mut out, &mut err);
+        let text = |x: Vec<u8>| String::from_utf8(x).unwrap();
+        (code, text(out), text(err))
+    }
+    #[test]
+    fn options_are_parsed() {
+        let args = [
+            "plot", "-x^2", "--x", "1,pi", "--set", "a=2", "--size", "40x30", "--",
+        ];
+        let args = args
+            .iter()
+            .map(|x| x.to_string())
+            .chain(["--json".to_string()]);
+        let options = Options::parse(&args.collect::<Vec<String>>()).unwrap();
+        assert_eq!(options.command, Command::Plot);
+        assert_eq!(options.expressions, vec!["-x^2", "--json"]);
+        assert_eq!(options.at, vec![1.0, std::f64::consts::PI]);
+        assert_eq!(options.variables, vec![("a".to_string(), 2.0)]);
+        assert_eq!(options.size, vec2(40.0, 30.0));
+        assert!(!options.json);
+        let parse =
+            |args: &[&str]| Options::parse(&args.iter().map(|x| x.to_string()).collect::<Vec<_>>());
+        assert_eq!(
+            parse(&["graph"]),
+            Err(format!("{}: graph", UNKNOWN_COMMAND_ERROR))
+        );
+        assert_eq!(
+            parse(&["eval", "--x"]),
+            Err(format!("{}: --x", MISSING_VALUE_ERROR))
+        );
+        assert!(parse(&["eval", "--roots", "2:1"]).is_err());
+    }
+    #[test]
+    fn values_derivatives_and_roots() {
+        let (code, out, err) = run_with(&["eval", "2+3", "a*sin(x)", "--x", "0", "--set", "a=2"]);
+        assert_eq!((code, err.as_str()), (0, ""));
+        assert_eq!(out, "2+3 = 5\na*sin(x) at x = 0: 0\n");
+        let (code, out, _) = run_with(&["diff", "x^3", "--x", "2"]);
+        assert_eq!(code, 0);
+        assert!(out.starts_with("d/dx x^3 = 3*x^2\n"));
+        assert!(out.ends_with("3*x^2 at x = 2: 12\n"));
+        let (_, out, _) = run_with(&["eval", "x^2 - 2", "--roots", "0:2"]);
+        assert!(out.starts_with("x^2 - 2 = 0 at x = 1.41421356237"));
+        assert_eq!(out.lines().count(), 1);
+        // Bad expressions are reported without stopping the rest
+        let (code, out, err) = run_with(&["eval", "sin(x)", "(1, 2)", "mean([1, 2, 6])"]);
+        assert_eq!(code, ERROR_EXIT);
+        assert_eq!(out, "mean([1, 2, 6]) = 3\n");
+        assert_eq!(
+            err,
+            format!("sin(x): {}\n(1, 2): {}\n", X_NEEDED_ERROR, KIND_ERROR)
+        );
+    }
+    #[test]
+    fn json_output() {
+        let (_, out, _) = run_with(&["diff", "--json", "ln(x)", "--x", "0", "q"]);
+        assert_eq!(
+            out,
+            "[\n  {\"expression\": \"ln(x)\", \"derivative\": \"1/x\", \"values\": [{\"x\": 0, \"value\": null}]},\n  \
+            {\"expression\": \"q\", \"error\": \"Undefined: q\"}\n]\n"
+        );
+    }
+    #[test]
+    fn plots_are_written() {
+        let directory = std::env::temp_dir();
+        for extension in ["svg", "png"] {
+            let path = directory.join(format!("cli_plot_test.{}", extension));
+            let path = path.to_str().unwrap();
+            let (code, _, err) =
+                run_with(&["plot", "sin(x)", "[(1, 1)]", "-o", path, "--size", "80x60"]);
+            assert_eq!((code, err.as_str()), (0, ""));
+            let bytes = std::fs::read(path).unwrap();
+            let _ = std::fs::remove_file(path);
+            match extension {
+                "svg" => assert!(bytes.starts_with(b"<svg")),
+                _ => assert!(bytes.starts_with(b"\x89PNG")),
+            }
+        }
+        let (code, _, err) = run_with(&["plot", "x", "-o", "graph.bmp"]);
+        assert_eq!(
+            (code, err),
+            (ERROR_EXIT, format!("{}: graph.bmp\n", FORMAT_ERROR))
+        );
+    }
+}
@@ -1,49 +1,71 @@
+//! Named jobs that switch on once their interval has passed, checked once per frame.
 use macroquad::time::get_time;
 use std::collections::HashMap;
 
 trait ObjDefault<T> {
     fn default() -> T;
 }
+/// One job, stored by tag.
 pub struct Object {
-    duration: isize,
-    old_time: isize,
+    duration: f64,
+    old_time: f64,
+    /// Set for the frame in which the interval has passed
     pub on: bool,
 }
 impl ObjDefault<Object> for Object {
     fn default() -> Object {
         Object {
-            duration: 5,
-            old_time: get_time().floor() as isize,
+            duration: 5.0,
+            old_time: get_time().floor(),
             on: false,
         }
     }
 }
+/// Adding, checking and updating the jobs in a storage map.
 pub mod job {
     use super::*;
+    /// Adds a job that switches on every `duration` whole seconds.
     pub fn add<'a>(storage: &mut HashMap<&'a str, Object>, tag: &'a str, duration: isize) {
         let object = Object {
-            duration,
+            duration: duration as f64,
             ..Object::default()
         };
         storage.insert(tag, object);
     }
+    /// Same as `add` for jobs that need to run more than once a second
+    pub fn add_seconds<'a>(storage: &mut HashMap<&'a str, Object>, tag: &'a str, duration: f64) {
+        let object = Object {
+            duration,
+            old_time: get_time(),
+            on: false,
+        };
+        storage.insert(tag, object);
+    }
+    /// Whether the job switched on this frame, panics for an unknown tag.
     pub fn is_on(storage: &HashMap<&str, Object>, tag: &str) -> bool {
         storage.get(tag).unwrap().on
     }
+    /// Drops the job.
     pub fn remove(storage: &mut HashMap<&str, Object>, tag: &str) {
         storage.remove(tag);
     }
+    /// Switches on every job whose interval has passed, call once at the start of a frame.
     pub fn update(storage: &mut HashMap<&str, Object>) {
-        for (tag, object) in storage.iter_mut() {
-            let record_time = get_time().floor() as isize;
-            if ((record_time - object.duration) > object.old_time) {
+        for object in storage.values_mut() {
+            // Whole second jobs keep ticking on the second
+            let record_time = match object.duration.fract() == 0.0 {
+                true => get_time().floor(),
+                false => get_time(),
+            };
+            if (record_time - object.duration) > object.old_time {
                 object.old_time = record_time;
                 object.on = true;
             }
         }
     }
+    /// Switches every job off again, call once at the end of a frame.
     pub fn update_next(storage: &mut HashMap<&str, Object>) {
-        for (tag, object) in storage.iter_mut() {
+        for object in storage.values_mut() {
             object.on = false;
         }
     }
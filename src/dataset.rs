@@ -0,0 +1,331 @@
+//! Importing CSV and TSV files as named lists of numbers, one list per column.
+use crate::algebra_parser::is_reserved_name;
+use crate::big_number::BigNumber;
+use crate::entries::EntryList;
+#[cfg(feature = "ui")]
+use crate::prelude::*;
+#[cfg(feature = "ui")]
+use macroquad::ui::{hash, root_ui, widgets};
+
+const EMPTY_FILE_ERROR: &str = "File has no rows";
+const NO_DATA_ERROR: &str = "File has no rows with numbers";
+const FIELD_COUNT_ERROR: &str = "Row has a different number of fields than the first row";
+const QUOTE_ERROR: &str = "Quoted field is never closed";
+const NUMBER_ERROR: &str = "Field is not a number";
+const MISSING_ERROR: &str = "Field is empty";
+const RANGE_ERROR: &str = "Number is too large to plot";
+//
+// Bad rows listed in the panel, the rest are summarised
+#[cfg(feature = "ui")]
+const SHOWN_ERRORS: usize = 8;
+// Single letters stay free for parameters and sliders
+const MIN_NAME_LENGTH: usize = 2;
+
+/// One column of numbers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    /// The list name expressions use, derived from the header
+    pub name: String,
+    /// The header as written in the file, the name is what expressions use
+    pub header: String,
+    /// The numbers from every row that could be read
+    pub values: Vec<f64>,
+}
+/// A row that could not be read. These rows are left out, `line` counts from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    /// The line in the file
+    pub line: usize,
+    /// What was wrong with the row
+    pub message: &'static str,
+}
+/// The columns of an imported file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dataset {
+    /// The columns in file order
+    pub columns: Vec<Column>,
+    /// The rows that were left out
+    pub errors: Vec<RowError>,
+}
+/// The window for importing a file.
+#[cfg(feature = "ui")]
+pub struct DatasetPanel {
+    path: String,
+    status: Vec<String>,
+}
+
+// Helper Functions
+// Splits one line at `delimiter`, fields may be quoted and `""` inside quotes is a quote
+fn split_fields(line: &str, delimiter: char) -> Result<Vec<String>, &'static str> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(char) = chars.next() {
+        match (quoted, char) {
+            (true, '"') if (chars.peek() == Some(&'"')) => {
+                field.push('"');
+                chars.next();
+            }
+            (true, '"') => quoted = false,
+            (false, '"') if (field.trim().is_empty()) => {
+                field.clear();
+                quoted = true;
+            }
+            (false, x) if (x == delimiter) => fields.push(std::mem::take(&mut field)),
+            (_, x) => field.push(x),
+        }
+    }
+    if quoted {
+        return Err(QUOTE_ERROR);
+    }
+    fields.push(field);
+    Ok(fields)
+}
+// Plain decimals with an optional `e` exponent. `BigNumber` checks the text and catches
+// magnitudes such as `1e400` that would silently become infinity, the value itself comes
+// from the f64 parser so it is correctly rounded. Haven suffixes are not read, `5m` in
+// data is more likely a unit than millions
+fn parse_number(field: &str) -> Result<f64, &'static str> {
+    let field = field.trim();
+    if field.is_empty() {
+        return Err(MISSING_ERROR);
+    }
+    let number = BigNumber::parse_plain(field).ok_or(NUMBER_ERROR)?;
+    if number.exponent > f64::MAX_10_EXP {
+        return Err(RANGE_ERROR);
+    }
+    match field.parse::<f64>() {
+        Ok(x) if (x.is_finite()) => Ok(x),
+        _ => Err(RANGE_ERROR),
+    }
+}
+// A, B, ..., Z, AA, AB, ... like spreadsheet columns
+fn column_letters(index: usize) -> String {
+    let mut letters = String::new();
+    let mut rest = index + 1;
+    while rest > 0 {
+        letters.insert(0, (b'A' + ((rest - 1) % 26) as u8) as char);
+        rest = (rest - 1) / 26;
+    }
+    letters
+}
+// Letters of the header, the lexer splits names at anything else. Headers that would
+// clash with a function or another column become `colA`, `colB`, ...
+fn column_names(headers: &[String]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for (index, header) in headers.iter().enumerate() {
+        let name = header
+            .chars()
+            .filter(|x| x.is_ascii_alphabetic())
+            .collect::<String>();
+        let usable =
+            name.len() >= MIN_NAME_LENGTH && !is_reserved_name(&name) && !names.contains(&name);
+        names.push(match usable {
+            true => name,
+            false => format!("col{}", column_letters(index)),
+        });
+    }
+    names
+}
+//
+
+impl Dataset {
+    /// Comma separated unless the first line has a tab. The first row is a header when
+    /// any of its fields is not a number.
+    pub fn parse(text: &str) -> Result<Dataset, &'static str> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+            .filter(|(_, line)| !line.trim().is_empty());
+        let (first_line, first) = lines.next().ok_or(EMPTY_FILE_ERROR)?;
+        let delimiter = match first.contains('\t') {
+            true => '\t',
+            false => ',',
+        };
+        let mut errors = Vec::new();
+        let header = split_fields(first, delimiter)?;
+        let has_header = header.iter().any(|x| parse_number(x) == Err(NUMBER_ERROR));
+        let width = header.len();
+        let mut rows = Vec::new();
+        let mut read_row = |line: usize, text: &str| {
+            let parsed = split_fields(text, delimiter).and_then(|fields| {
+                if fields.len() != width {
+                    return Err(FIELD_COUNT_ERROR);
+                }
+                fields.iter().map(|x| parse_number(x)).collect()
+            });
+            match parsed {
+                Ok(row) => rows.push(row),
+                Err(message) => errors.push(RowError { line, message }),
+            }
+        };
+        if !has_header {
+            read_row(first_line, first);
+        }
+        for (line, text) in lines {
+            read_row(line, text);
+        }
+        if rows.is_empty() {
+            return Err(NO_DATA_ERROR);
+        }
+        let headers = match has_header {
+            true => header.iter().map(|x| x.trim().to_string()).collect(),
+            false => (0..width).map(column_letters).collect::<Vec<String>>(),
+        };
+        let columns = column_names(&headers)
+            .into_iter()
+            .zip(headers)
+            .enumerate()
+            .map(|(index, (name, header))| Column {
+                name,
+                header,
+                values: rows.iter().map(|x: &Vec<f64>| x[index]).collect(),
+            })
+            .collect();
+        Ok(Dataset { columns, errors })
+    }
+    /// Reads and parses the file at `path`.
+    pub fn load(path: &str) -> Result<Dataset, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|x| format!("Could not read {}: {}", path, x))?;
+        Dataset::parse(&text).map_err(String::from)
+    }
+    /// Every column becomes a list expressions can use by name
+    pub fn apply(&self, entries: &mut EntryList) {
+        for column in self.columns.iter() {
+            entries.set_list(&column.name, column.values.clone());
+        }
+    }
+}
+#[cfg(feature = "ui")]
+impl DatasetPanel {
+    /// A panel that imports `data.csv` until another path is typed.
+    pub fn new() -> DatasetPanel {
+        DatasetPanel::default()
+    }
+    fn import(&mut self, entries: &mut EntryList) {
+        let dataset = match Dataset::load(self.path.trim()) {
+            Ok(x) => x,
+            Err(x) => {
+                self.status = vec![x];
+                return;
+            }
+        };
+        dataset.apply(entries);
+        let rows = dataset.columns[0].values.len();
+        self.status = vec![format!("{} rows", rows)];
+        for column in dataset.columns.iter() {
+            self.status.push(match column.name == column.header {
+                true => format!("  {}", column.name),
+                false => format!("  {} ({})", column.name, column.header),
+            });
+        }
+        for error in dataset.errors.iter().take(SHOWN_ERRORS) {
+            let line = format!("  ! Line {}: {}", error.line, error.message);
+            self.status.push(line);
+        }
+        if dataset.errors.len() > SHOWN_ERRORS {
+            let rest = dataset.errors.len() - SHOWN_ERRORS;
+            self.status.push(format!("  ! ... {} more bad rows", rest));
+        }
+    }
+    /// Draws the window, importing into `entries` when asked.
+    pub fn create_ui(&mut self, entries: &mut EntryList, position: Vec2) {
+        let mut import = false;
+        widgets::Window::new(hash!(), position, vec2(300.0, 260.0))
+            .label("Data")
+            .movable(true)
+            .ui(&mut root_ui(), |ui| {
+                ui.input_text(hash!(), "File", &mut self.path);
+                if ui.button(None, "Import") {
+                    import = true;
+                }
+                for line in self.status.iter() {
+                    ui.label(None, line);
+                }
+            });
+        if import {
+            self.import(entries);
+        }
+    }
+}
+#[cfg(feature = "ui")]
+impl Default for DatasetPanel {
+    fn default() -> Self {
+        DatasetPanel {
+            path: "data.csv".to_string(),
+            status: Vec::new(),
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn headers_and_quotes() {
+        let text = "height,\"weight, kg\",x\n1.5,60,1\n\"1.75\",72.5,2\r\n\n2,1.2e3,3\n";
+        let dataset = Dataset::parse(text).unwrap();
+        let names = dataset
+            .columns
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(names, vec!["height", "weightkg", "colC"]);
+        assert_eq!(dataset.columns[1].header, "weight, kg");
+        assert_eq!(dataset.columns[0].values, vec![1.5, 1.75, 2.0]);
+        assert_eq!(dataset.columns[1].values, vec![60.0, 72.5, 1200.0]);
+        assert!(dataset.errors.is_empty());
+        // Decimals that are not exact in binary come out as the nearest f64
+        let dataset = Dataset::parse("a\n0.3\n123.456\n.5\n+2.\n").unwrap();
+        assert_eq!(dataset.columns[0].values, vec![0.3, 123.456, 0.5, 2.0]);
+        let dataset = Dataset::parse("1\t2\n3\t4\n").unwrap();
+        assert_eq!(dataset.columns[1].name, "colB");
+        assert_eq!(dataset.columns[1].values, vec![2.0, 4.0]);
+        assert_eq!(
+            split_fields("\"a \"\"b\"\"\",c", ','),
+            Ok(vec!["a \"b\"".to_string(), "c".to_string()])
+        );
+    }
+    #[test]
+    fn bad_rows_are_reported() {
+        let text = "a,b\n1,2\n3\n4,five\n5,\n\"6,7\n8,1e400\n9,10\n";
+        let dataset = Dataset::parse(text).unwrap();
+        assert_eq!(dataset.columns[1].values, vec![2.0, 10.0]);
+        let errors = dataset
+            .errors
+            .iter()
+            .map(|x| (x.line, x.message))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            errors,
+            vec![
+                (3, FIELD_COUNT_ERROR),
+                (4, NUMBER_ERROR),
+                (5, MISSING_ERROR),
+                (6, QUOTE_ERROR),
+                (7, RANGE_ERROR),
+            ]
+        );
+        // Suffixes are not read as magnitudes
+        let dataset = Dataset::parse("size\n5m\n3k\n2\n-4E-1\n").unwrap();
+        assert_eq!(dataset.columns[0].values, vec![2.0, -0.4]);
+        let errors = dataset.errors.iter().map(|x| (x.line, x.message));
+        assert!(errors.eq([(2, NUMBER_ERROR), (3, NUMBER_ERROR)]));
+        assert_eq!(Dataset::parse("\n\n"), Err(EMPTY_FILE_ERROR));
+        assert_eq!(Dataset::parse("a,b\n"), Err(NO_DATA_ERROR));
+    }
+    #[test]
+    fn columns_become_lists() {
+        let mut entries = EntryList::new();
+        entries.add("mean(height)");
+        let dataset = Dataset::parse("height\n1\n2\n6\n").unwrap();
+        dataset.apply(&mut entries);
+        entries.add("histogram(height, 2)");
+        let entry = &entries.entries[0];
+        assert!(entry.get_free_variables().is_empty());
+        assert_eq!(entry.evaluate(0.0, &entries.context), 3.0);
+        assert_eq!(entries.entries[1].get_error(&entries.context), None);
+    }
+}
@@ -1,29 +1,207 @@
+//! Symbolic derivatives of syntax trees.
 use crate::algebra_parser;
-use std::f32::consts::PI;
 
 type Order = i8;
-fn factorial(number: isize) -> isize {
-    return (1..=number).into_iter().product();
-}
-fn gamma(n: f32) -> f32 {
-    // Gergő's Formula
-    return n.powf(n) * (2.0 * PI * n).sqrt() * (1.0 / ((12.0 * n) + (2.0 / (5.0 * n))) - n).exp();
-}
-fn power_rule(order: Order, coefficient: f32, exponent: f32, variable: char) {
-    let mut buffer = String::new();
-    let new_exponent = exponent - order as f32;
-    let mut new_coefficient = coefficient;
-    if (exponent.floor() == exponent) {
-        new_coefficient =
-            coefficient * (factorial(exponent as isize) / factorial(order as isize)) as f32;
-    } else {
-        new_coefficient = coefficient * (gamma(exponent) / factorial(order.into()) as f32);
-    }
-    buffer.push_str(&coefficient.to_string());
-    buffer.push(variable);
-    buffer.push_str(&*("^".to_string() + &*new_exponent.to_string()));
-}
+// Differentiation Errors
+const UNKNOWN_DERIVATIVE_ERROR: &str = "Derivative of function is not known";
+const EXPRESSION_ERROR: &str = "Expression could not be parsed";
+const CONDITION_DERIVATIVE_ERROR: &str = "Conditions cannot be differentiated";
+//
+/// Differentiation rules and the simplifying builders they use.
 pub mod derivative {
     use super::*;
-    fn compute_expression(expression: &str, order: Order) {}
+    use algebra_parser::{generate_all_ast, Node, Operator};
+    use std::collections::HashMap;
+    // Builders that fold constants and drop the zeros and ones the rules below produce
+    fn number(x: f64) -> Node {
+        Node::Number(x)
+    }
+    fn call(name: &str, argument: Node) -> Node {
+        Node::Call(name.to_string(), vec![argument])
+    }
+    fn negate(a: Node) -> Node {
+        match a {
+            Node::Number(x) => number(-x),
+            Node::Negate(x) => *x,
+            x => Node::Negate(Box::new(x)),
+        }
+    }
+    fn add(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Number(x), Node::Number(y)) => number(x + y),
+            (Node::Number(0.0), b) => b,
+            (a, Node::Number(0.0)) => a,
+            (a, Node::Negate(b)) => sub(a, *b),
+            (a, b) => Node::Operation(Operator::Add, Box::new(a), Box::new(b)),
+        }
+    }
+    fn sub(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Number(x), Node::Number(y)) => number(x - y),
+            (Node::Number(0.0), b) => negate(b),
+            (a, Node::Number(0.0)) => a,
+            (a, b) => Node::Operation(Operator::Sub, Box::new(a), Box::new(b)),
+        }
+    }
+    fn mul(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Number(x), Node::Number(y)) => number(x * y),
+            (Node::Number(0.0), _) | (_, Node::Number(0.0)) => number(0.0),
+            (Node::Number(1.0), b) => b,
+            (a, Node::Number(1.0)) => a,
+            (Node::Number(-1.0), b) => negate(b),
+            (a, Node::Number(-1.0)) => negate(a),
+            (a, Node::Number(y)) => mul(Node::Number(y), a),
+            (Node::Negate(a), b) => negate(mul(*a, b)),
+            (a, Node::Negate(b)) => negate(mul(a, *b)),
+            (a, b) => Node::Operation(Operator::Mul, Box::new(a), Box::new(b)),
+        }
+    }
+    fn div(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (Node::Number(0.0), _) => number(0.0),
+            (a, Node::Number(1.0)) => a,
+            (a, b) => Node::Operation(Operator::Div, Box::new(a), Box::new(b)),
+        }
+    }
+    fn power(a: Node, b: Node) -> Node {
+        match (a, b) {
+            (_, Node::Number(0.0)) => number(1.0),
+            (a, Node::Number(1.0)) => a,
+            (a, b) => Node::Operation(Operator::Exponent, Box::new(a), Box::new(b)),
+        }
+    }
+    fn depends_on(node: &Node, variable: &str) -> bool {
+        node.get_variables().iter().any(|x| x == variable)
+    }
+    // Derivative of the outer function evaluated at `u`, the chain rule multiplies in du
+    fn outer_derivative(name: &str, u: &Node) -> Result<Node, &'static str> {
+        let u = u.clone();
+        let one_minus_square = sub(number(1.0), power(u.clone(), number(2.0)));
+        Ok(match name {
+            "sin" => call("cos", u),
+            "cos" => negate(call("sin", u)),
+            "tan" => power(call("sec", u), number(2.0)),
+            "sec" => mul(call("sec", u.clone()), call("tan", u)),
+            "csc" => negate(mul(call("csc", u.clone()), call("cot", u))),
+            "cot" => negate(power(call("csc", u), number(2.0))),
+            "asin" | "arcsin" => div(number(1.0), call("sqrt", one_minus_square)),
+            "acos" | "arccos" => negate(div(number(1.0), call("sqrt", one_minus_square))),
+            "atan" | "arctan" => div(number(1.0), add(number(1.0), power(u, number(2.0)))),
+            "sinh" => call("cosh", u),
+            "cosh" => call("sinh", u),
+            "tanh" => div(number(1.0), power(call("cosh", u), number(2.0))),
+            "sqrt" => div(number(1.0), mul(number(2.0), call("sqrt", u))),
+            "abs" => call("sign", u),
+            "ln" => div(number(1.0), u),
+            "log" => div(number(1.0), mul(u, call("ln", number(10.0)))),
+            "exp" => call("exp", u),
+            // Flat between their jumps
+            "floor" | "ceil" | "round" | "sign" => number(0.0),
+            _ => return Err(UNKNOWN_DERIVATIVE_ERROR),
+        })
+    }
+    /// The derivative of `node` with respect to `variable`, simplified as it is built.
+    pub fn differentiate(node: &Node, variable: &str) -> Result<Node, &'static str> {
+        if !depends_on(node, variable) {
+            return Ok(number(0.0));
+        }
+        Ok(match node {
+            Node::Number(_) => number(0.0),
+            Node::Variable(x) => number(if x == variable { 1.0 } else { 0.0 }),
+            Node::Negate(a) => negate(differentiate(a, variable)?),
+            Node::Operation(operator, a, b) => {
+                let (a, b) = (a.as_ref().clone(), b.as_ref().clone());
+                let (da, db) = (differentiate(&a, variable)?, differentiate(&b, variable)?);
+                match operator {
+                    Operator::Add => add(da, db),
+                    Operator::Sub => sub(da, db),
+                    Operator::Mul => add(mul(da, b.clone()), mul(a, db)),
+                    Operator::Div => {
+                        div(sub(mul(da, b.clone()), mul(a, db)), power(b, number(2.0)))
+                    }
+                    // Power rule when only the base varies
+                    Operator::Exponent if (!depends_on(&b, variable)) => {
+                        mul(mul(b.clone(), power(a, sub(b, number(1.0)))), da)
+                    }
+                    // a^b = e^(b ln a)
+                    Operator::Exponent => mul(
+                        power(a.clone(), b.clone()),
+                        add(mul(db, call("ln", a.clone())), div(mul(b, da), a)),
+                    ),
+                }
+            }
+            Node::Call(name, arguments) => match arguments.as_slice() {
+                [u] => mul(outer_derivative(name, u)?, differentiate(u, variable)?),
+                _ => return Err(UNKNOWN_DERIVATIVE_ERROR),
+            },
+            Node::Tuple(components) => Node::Tuple(
+                components
+                    .iter()
+                    .map(|x| differentiate(x, variable))
+                    .collect::<Result<Vec<Node>, &'static str>>()?,
+            ),
+            Node::List(items) => Node::List(
+                items
+                    .iter()
+                    .map(|x| differentiate(x, variable))
+                    .collect::<Result<Vec<Node>, &'static str>>()?,
+            ),
+            Node::Compare(..) | Node::And(..) | Node::Or(..) => {
+                return Err(CONDITION_DERIVATIVE_ERROR)
+            }
+            // Each piece is differentiated on its own, the conditions stay as they are
+            Node::Piecewise(pieces) => Node::Piecewise(
+                pieces
+                    .iter()
+                    .map(|(condition, value)| {
+                        Ok((condition.clone(), differentiate(value, variable)?))
+                    })
+                    .collect::<Result<Vec<(Node, Node)>, &'static str>>()?,
+            ),
+        })
+    }
+    /// Parses `expression` and differentiates it `order` times with respect to x.
+    pub fn compute_expression(expression: &str, order: Order) -> Result<Node, String> {
+        let data = HashMap::from([("f", expression.to_string())]);
+        let mut node = generate_all_ast(&data)
+            .remove("f")
+            .unwrap_or(Err(EXPRESSION_ERROR.to_string()))?;
+        for _ in 0..order {
+            node = differentiate(&node, "x")?;
+        }
+        Ok(node)
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::derivative::*;
+    use super::*;
+    use algebra_parser::{
+        evaluator::{evaluate, Context},
+        Node,
+    };
+    fn slope_at(expression: &str, x: f64) -> f64 {
+        let node = compute_expression(expression, 1).unwrap();
+        evaluate(&node, &Context::default().with_variable("x", x)).unwrap()
+    }
+    #[test]
+    fn derivative_rules() {
+        assert_eq!(slope_at("x^3", 2.0), 12.0);
+        assert_eq!(slope_at("5", 2.0), 0.0);
+        assert!((slope_at("sin(2x)", 0.5) - 2.0 * 1.0f64.cos()).abs() < 1e-12);
+        assert!((slope_at("x/(x+1)", 1.0) - 0.25).abs() < 1e-12);
+        assert!((slope_at("2^x", 3.0) - 8.0 * 2.0f64.ln()).abs() < 1e-12);
+        assert!((slope_at("x^x", 2.0) - 4.0 * (2.0f64.ln() + 1.0)).abs() < 1e-12);
+        assert!((slope_at("ln(x^2 + 1)", 1.0) - 1.0).abs() < 1e-12);
+        assert_eq!(slope_at("{x < 0: -x, x^2}", -1.0), -1.0);
+        assert_eq!(slope_at("{x < 0: -x, x^2}", 3.0), 6.0);
+    }
+    #[test]
+    fn derivatives_are_simplified() {
+        let node = compute_expression("3x + 2", 1).unwrap();
+        assert_eq!(node, Node::Number(3.0));
+        let node = compute_expression("x^2", 2).unwrap();
+        assert_eq!(node, Node::Number(2.0));
+    }
 }
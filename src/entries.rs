@@ -0,0 +1,1250 @@
+//! The expressions typed into the app and how each of them is plotted.
+use crate::algebra_parser::{
+    evaluator::{self, Context, Scope},
+    generate_all_ast_with_names, Node, CONSTANTS,
+};
+use crate::big_number::BigVec2;
+use crate::camera::Camera;
+use crate::derivative_solver::derivative;
+use crate::implicit;
+use crate::intersections;
+use crate::interval::{self, Interval};
+use crate::plotter::{self, DashStyle};
+use crate::prelude::*;
+use crate::scene::{self, Scene, TextAnchor};
+use crate::shapes::{Marker, MarkerShape, ShapeCache};
+use crate::statistics::{self, Bin, Summary};
+use itertools::Itertools;
+#[cfg(feature = "ui")]
+use macroquad::ui::{hash, root_ui, widgets};
+use std::collections::HashMap;
+
+/// Colors new entries take in turn.
+pub const PALETTE: [Color; 6] = [
+    Color::new(0.78, 0.27, 0.25, 1.0),
+    Color::new(0.18, 0.44, 0.7, 1.0),
+    Color::new(0.22, 0.55, 0.27, 1.0),
+    Color::new(0.38, 0.26, 0.65, 1.0),
+    Color::new(0.98, 0.49, 0.1, 1.0),
+    BLACK,
+];
+#[cfg(feature = "ui")]
+const PALETTE_NAMES: [&str; 6] = ["Red", "Blue", "Green", "Purple", "Orange", "Black"];
+#[cfg(feature = "ui")]
+const CUSTOM_COLOR_NAME: &str = "Custom";
+const LEGEND_FONT_SIZE: f32 = 18.0;
+const LEGEND_SAMPLE_LENGTH: f32 = 36.0;
+const LEGEND_PADDING: f32 = 8.0;
+const PARAMETRIC_COMPONENT_ERROR: &str = "Parametric curves need exactly two components";
+const POLAR_TUPLE_ERROR: &str = "Polar curves need a single radius";
+const POINT_COMPONENT_ERROR: &str = "Points need (x, y), (x, y, dy) or (x, y, dx, dy)";
+const HISTOGRAM_ARGUMENT_ERROR: &str = "Use histogram(list) or histogram(list, bin width)";
+const BOX_PLOT_ARGUMENT_ERROR: &str = "Use boxplot(list) or boxplot(list, y)";
+const EMPTY_PLOT_ERROR: &str = "List has no finite values to plot";
+
+// Seconds the animated point takes to run through the whole parameter range
+const ANIMATION_SECONDS: f64 = 6.0;
+const ANIMATION_RADIUS: f32 = 6.0;
+// Opacity of shaded regions, low enough that overlapping regions mix into a readable color
+const REGION_ALPHA: f32 = 0.22;
+const ENDPOINT_RADIUS: f32 = 5.0;
+const ERROR_BAR_THICKNESS: f32 = 1.5;
+// Box plots are one unit tall
+const BOX_HALF_HEIGHT: f64 = 0.5;
+// Half the width of the caps at the ends of an error bar, in pixels
+const ERROR_BAR_CAP: f32 = 4.0;
+// Samples across the screen when looking for the boundaries between pieces
+const ENDPOINT_SAMPLES: usize = 512;
+
+/// How an entry is plotted, decided from the shape of its expression.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryKind {
+    /// y = f(x)
+    Function,
+    /// (x(t), y(t))
+    Parametric,
+    /// r = f(θ)
+    Polar,
+    /// F(x, y) = G(x, y)
+    Implicit,
+    /// Regions such as `y < x^2` or `x^2 + y^2 <= 9 and y > 0`
+    Inequality,
+    /// `[(x, y), ...]`, measured data drawn as markers
+    Points,
+    /// `histogram(list, width)`
+    Histogram,
+    /// `boxplot(list, y)`, drawn sideways at height y
+    BoxPlot,
+}
+/// Range of the parameter of a curve and the width of the intervals sampling starts from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParameterRange {
+    /// Where the parameter starts
+    pub min: f64,
+    /// Where the parameter ends
+    pub max: f64,
+    /// Width of the intervals sampling starts from
+    pub step: f64,
+}
+/// How an entry looks on the graph.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryStyle {
+    /// Color of curves, markers and shaded regions
+    pub color: Color,
+    /// Curve thickness in pixels
+    pub line_width: f32,
+    /// Solid, dashed or dotted curves
+    pub dash: DashStyle,
+    /// Hidden entries are still evaluated but not drawn
+    pub visible: bool,
+    /// Shape of the markers of a point list
+    pub marker: MarkerShape,
+    /// Marker radius in pixels
+    pub marker_size: f32,
+    /// Joins the points of a list in order
+    pub connect: bool,
+}
+/// One point of a list, with the half widths of its error bars
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DataPoint {
+    /// Horizontal coordinate
+    pub x: f64,
+    /// Vertical coordinate
+    pub y: f64,
+    /// Half width of the horizontal error bar, 0 for none
+    pub error_x: f64,
+    /// Half height of the vertical error bar, 0 for none
+    pub error_y: f64,
+}
+/// Where a piece of a piecewise function starts or ends, closed when the point itself
+/// belongs to that piece
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Endpoint {
+    /// Horizontal coordinate
+    pub x: f64,
+    /// Vertical coordinate
+    pub y: f64,
+    /// Drawn filled when the point belongs to the piece
+    pub closed: bool,
+}
+/// One expression typed into the list, together with everything parsed from it.
+pub struct ExpressionEntry {
+    /// Stays the same while entries are added and removed around it
+    pub id: usize,
+    /// Bumped on every re-parse so caches can tell when the expression changed
+    pub revision: usize,
+    /// Name shown in the list and the legend, e.g. `f1`
+    pub label: String,
+    /// The text as typed
+    pub expression: String,
+    /// How the entry is drawn
+    pub style: EntryStyle,
+    /// Range of the parameter for parametric and polar curves
+    pub range: ParameterRange,
+    /// How the entry is plotted
+    pub kind: EntryKind,
+    /// Moves a point along parametric curves
+    pub animate: bool,
+    /// The parsed expression, or why it could not be parsed
+    pub ast: Result<Node, String>,
+    /// The derivative of a function with respect to x
+    pub derivative: Result<Node, String>,
+    /// Multi-letter names, e.g. imported columns, read as one term instead of a product
+    pub names: Vec<String>,
+}
+// Helper Functions
+fn push_polylines(
+    scene: &mut Scene,
+    polylines: Vec<plotter::Polyline>,
+    style: &EntryStyle,
+    dash: DashStyle,
+) {
+    for polyline in polylines {
+        scene.polyline(polyline, style.line_width, style.color, dash);
+    }
+}
+/// Splits at the first `=` that is not part of `<=` or `>=`
+pub fn split_equation(expression: &str) -> Option<(&str, &str)> {
+    let mut previous = None;
+    for (position, char) in expression.char_indices() {
+        if char == '=' && !matches!(previous, Some('<') | Some('>')) {
+            return Some((&expression[..position], &expression[position + 1..]));
+        }
+        if !char.is_whitespace() {
+            previous = Some(char);
+        }
+    }
+    None
+}
+//
+
+impl ExpressionEntry {
+    /// An entry that is parsed right away.
+    pub fn new(id: usize, label: &str, expression: &str, color: Color) -> ExpressionEntry {
+        let mut entry = ExpressionEntry {
+            id,
+            revision: 0,
+            label: label.to_string(),
+            expression: expression.to_string(),
+            style: EntryStyle {
+                color,
+                line_width: 3.0,
+                dash: DashStyle::Solid,
+                visible: true,
+                marker: MarkerShape::Circle,
+                marker_size: 5.0,
+                connect: false,
+            },
+            range: ParameterRange {
+                min: 0.0,
+                max: std::f64::consts::TAU,
+                step: 0.1,
+            },
+            kind: EntryKind::Function,
+            animate: false,
+            ast: Err(String::new()),
+            derivative: Err(String::new()),
+            names: Vec::new(),
+        };
+        entry.reparse();
+        entry
+    }
+    /// Replaces the text and parses it again.
+    pub fn set_expression(&mut self, expression: &str) {
+        self.expression = expression.to_string();
+        self.reparse();
+    }
+    /// How the entry is plotted.
+    pub fn get_kind(&self) -> EntryKind {
+        self.kind
+    }
+    /// The variable the curve is drawn over
+    pub fn get_parameter(&self) -> &'static str {
+        match self.get_kind() {
+            EntryKind::Function => "x",
+            EntryKind::Parametric => "t",
+            EntryKind::Polar => "θ",
+            EntryKind::Implicit
+            | EntryKind::Inequality
+            | EntryKind::Points
+            | EntryKind::Histogram
+            | EntryKind::BoxPlot => "x",
+        }
+    }
+    // Drawn over the whole plane in x and y rather than along one parameter
+    fn is_relation(&self) -> bool {
+        matches!(self.kind, EntryKind::Implicit | EntryKind::Inequality)
+    }
+    /// Variables that are neither drawn over nor constants, these need a value from outside
+    pub fn get_free_variables(&self) -> Vec<String> {
+        let ast = match self.ast {
+            Ok(ref x) => x,
+            Err(_) => return Vec::new(),
+        };
+        let parameter = self.get_parameter();
+        ast.get_variables()
+            .into_iter()
+            .filter(|x| !(x == parameter || CONSTANTS.contains(&x.as_str())))
+            .filter(|x| !(self.is_relation() && x == "y"))
+            .filter(|x| !self.names.contains(x))
+            .collect()
+    }
+    /// Parse errors first, then variables that nothing gives a value to
+    pub fn get_error(&self, context: &Context) -> Option<String> {
+        let ast = match self.ast {
+            Ok(ref x) => x,
+            Err(ref x) => return Some(x.clone()),
+        };
+        if let Node::List(ref items) = ast {
+            let valid = items.iter().all(|x| match x {
+                Node::Tuple(components) => (2..=4).contains(&components.len()),
+                _ => false,
+            });
+            if !valid {
+                return Some(POINT_COMPONENT_ERROR.to_string());
+            }
+        }
+        if let Node::Tuple(ref components) = ast {
+            match self.kind {
+                EntryKind::Polar => return Some(POLAR_TUPLE_ERROR.to_string()),
+                _ if (components.len() != 2) => {
+                    return Some(PARAMETRIC_COMPONENT_ERROR.to_string())
+                }
+                _ => {}
+            }
+        }
+        let unknown = self
+            .get_free_variables()
+            .into_iter()
+            .filter(|x| !context.variables.contains_key(x))
+            .collect::<Vec<String>>();
+        if !unknown.is_empty() {
+            return Some(format!("Undefined: {}", unknown.join(", ")));
+        }
+        let plotted = match self.kind {
+            EntryKind::Histogram => self.get_histogram(context).map(|x| !x.is_empty()),
+            EntryKind::BoxPlot => self.get_box_plot(context).map(|x| x.is_some()),
+            _ => Ok(true),
+        };
+        match plotted {
+            Ok(true) => None,
+            Ok(false) => Some(EMPTY_PLOT_ERROR.to_string()),
+            Err(x) => Some(x.to_string()),
+        }
+    }
+    /// Parses the expression again, e.g. after the known names changed.
+    pub fn reparse(&mut self) {
+        self.revision += 1;
+        let names = self.names.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+        let parse = |body: String| {
+            let data = HashMap::from([(self.label.as_str(), body)]);
+            generate_all_ast_with_names(&data, &names)
+                .remove(self.label.as_str())
+                .unwrap_or(Err(String::new()))
+        };
+        // `y = ...` and a bare right hand side both describe y as a function of x,
+        // `r = ...` is polar and may spell θ out as theta, any other equation is a
+        // relation F(x, y) = left - right = 0
+        let (kind, ast) = match split_equation(&self.expression) {
+            Some((left, right)) if (left.trim() == "r") => {
+                (Some(EntryKind::Polar), parse(right.replace("theta", "θ")))
+            }
+            Some((left, right)) if (left.trim() == "y") => match parse(right.to_string()) {
+                Ok(x) if (x.get_variables().iter().any(|x| x == "y")) => {
+                    (Some(EntryKind::Implicit), parse(format!("(y)-({})", right)))
+                }
+                x => (None, x),
+            },
+            Some((left, right)) => (
+                Some(EntryKind::Implicit),
+                parse(format!("({})-({})", left, right)),
+            ),
+            None => (None, parse(self.expression.clone())),
+        };
+        self.kind = match (kind, &ast) {
+            (Some(x), _) => x,
+            (None, Ok(Node::Tuple(_))) => EntryKind::Parametric,
+            (None, Ok(Node::List(_))) => EntryKind::Points,
+            (None, Ok(Node::Call(name, _))) if (name == "histogram") => EntryKind::Histogram,
+            (None, Ok(Node::Call(name, _))) if (name == "boxplot") => EntryKind::BoxPlot,
+            (None, Ok(Node::Compare(..) | Node::And(..) | Node::Or(..))) => EntryKind::Inequality,
+            (None, _) => EntryKind::Function,
+        };
+        self.ast = ast;
+        let parameter = self.get_parameter();
+        self.derivative = match self.ast {
+            Ok(ref x) => derivative::differentiate(x, parameter).map_err(String::from),
+            Err(ref x) => Err(x.clone()),
+        };
+    }
+    /// NaN wherever the expression cannot be evaluated so the plot simply breaks there
+    pub fn evaluate(&self, x: f64, context: &Context) -> f64 {
+        let ast = match self.ast {
+            Ok(ref x) => x,
+            Err(_) => return f64::NAN,
+        };
+        evaluator::evaluate_in(ast, &Scope::new(context, &[("x", x)])).unwrap_or(f64::NAN)
+    }
+    // Value of the relation at (x, y), the curve is where this is 0
+    fn evaluate_relation(&self, x: f64, y: f64, context: &Context) -> f64 {
+        let ast = match self.ast {
+            Ok(ref x) => x,
+            Err(_) => return f64::NAN,
+        };
+        let locals = [("x", x), ("y", y)];
+        let scope = Scope::new(context, &locals);
+        evaluator::evaluate_in(ast, &scope).unwrap_or(f64::NAN)
+    }
+    fn holds_at(&self, x: f64, y: f64, context: &Context) -> bool {
+        match self.ast {
+            Ok(ref ast) => {
+                let locals = [("x", x), ("y", y)];
+                let scope = Scope::new(context, &locals);
+                evaluator::evaluate_condition_in(ast, &scope).unwrap_or(false)
+            }
+            Err(_) => false,
+        }
+    }
+    fn bound_relation(&self, x: Interval, y: Interval, context: &Context) -> Interval {
+        match self.ast {
+            Ok(ref ast) => interval::evaluate(ast, &HashMap::from([("x", x), ("y", y)]), context),
+            Err(_) => Interval::ENTIRE,
+        }
+    }
+    /// Point of a parametric or polar curve at parameter value `t`
+    pub fn evaluate_point(&self, t: f64, context: &Context) -> Option<(f64, f64)> {
+        let ast = self.ast.as_ref().ok()?;
+        let locals = [(self.get_parameter(), t)];
+        let scope = Scope::new(context, &locals);
+        match (
+            self.kind,
+            evaluator::evaluate_tuple_in(ast, &scope).ok()?.as_slice(),
+        ) {
+            (EntryKind::Parametric, [x, y]) => Some((*x, *y)),
+            (EntryKind::Polar, [r]) => Some((r * t.cos(), r * t.sin())),
+            _ => None,
+        }
+    }
+    /// Points of a list that evaluate to finite numbers, the other ones are skipped
+    pub fn get_data_points(&self, context: &Context) -> Vec<DataPoint> {
+        let items = match self.ast {
+            Ok(Node::List(ref x)) => x,
+            _ => return Vec::new(),
+        };
+        items
+            .iter()
+            .filter_map(|x| evaluator::evaluate_tuple(x, context).ok())
+            .filter(|x| x.iter().all(|x| x.is_finite()))
+            .filter_map(|x| match x.as_slice() {
+                [x, y] => Some((*x, *y, 0.0, 0.0)),
+                [x, y, dy] => Some((*x, *y, 0.0, *dy)),
+                [x, y, dx, dy] => Some((*x, *y, *dx, *dy)),
+                _ => None,
+            })
+            .map(|(x, y, error_x, error_y)| DataPoint {
+                x,
+                y,
+                error_x: error_x.abs(),
+                error_y: error_y.abs(),
+            })
+            .collect()
+    }
+    fn get_plot_arguments(&self, name: &str) -> Option<&Vec<Node>> {
+        match self.ast {
+            Ok(Node::Call(ref x, ref arguments)) if (x == name) => Some(arguments),
+            _ => None,
+        }
+    }
+    fn get_histogram(&self, context: &Context) -> Result<Vec<Bin>, &'static str> {
+        let arguments = match self.get_plot_arguments("histogram") {
+            Some(x) => x,
+            None => return Ok(Vec::new()),
+        };
+        let (list, width) = match arguments.as_slice() {
+            [list] => (list, None),
+            [list, width] => (list, Some(evaluator::evaluate(width, context)?)),
+            _ => return Err(HISTOGRAM_ARGUMENT_ERROR),
+        };
+        let values = evaluator::evaluate_list(list, context)?;
+        Ok(statistics::histogram(&values, width))
+    }
+    // Five number summary and the height the box is drawn at
+    fn get_box_plot(&self, context: &Context) -> Result<Option<(Summary, f64)>, &'static str> {
+        let arguments = match self.get_plot_arguments("boxplot") {
+            Some(x) => x,
+            None => return Ok(None),
+        };
+        let (list, y) = match arguments.as_slice() {
+            [list] => (list, 0.0),
+            [list, y] => (list, evaluator::evaluate(y, context)?),
+            _ => return Err(BOX_PLOT_ARGUMENT_ERROR),
+        };
+        let values = evaluator::evaluate_list(list, context)?;
+        Ok(statistics::summarize(&values).map(|x| (x, y)))
+    }
+    // Open and closed dots at the boundaries between the pieces of a piecewise function,
+    // the boundaries are the roots of every comparison in the conditions
+    fn get_endpoints(&self, x_min: f64, x_max: f64, context: &Context) -> Vec<Endpoint> {
+        let pieces = match self.ast {
+            Ok(Node::Piecewise(ref x)) if (self.kind == EntryKind::Function) => x,
+            _ => return Vec::new(),
+        };
+        let at = |node: &Node, x: f64| {
+            evaluator::evaluate_in(node, &Scope::new(context, &[("x", x)])).unwrap_or(f64::NAN)
+        };
+        let active = |x: f64| {
+            let locals = [("x", x)];
+            let scope = Scope::new(context, &locals);
+            pieces.iter().position(|(condition, _)| {
+                evaluator::evaluate_condition_in(condition, &scope) == Ok(true)
+            })
+        };
+        let mut boundaries = pieces
+            .iter()
+            .flat_map(|(condition, _)| condition.get_comparisons())
+            .flat_map(|(_, a, b)| {
+                intersections::find_intersections(
+                    |x| at(a, x),
+                    |x| at(b, x),
+                    x_min,
+                    x_max,
+                    ENDPOINT_SAMPLES,
+                )
+            })
+            .collect::<Vec<f64>>();
+        boundaries.sort_by(f64::total_cmp);
+        boundaries.dedup_by(|a, b| (*a - *b).abs() < 1e-9 * (1.0 + b.abs()));
+        let h = (x_max - x_min) * 1e-6;
+        let mut endpoints = Vec::new();
+        for x in boundaries {
+            let (left, here, right) = (active(x - h), active(x), active(x + h));
+            if left == here && here == right {
+                continue;
+            }
+            for index in [left, right].into_iter().flatten().unique() {
+                let y = at(&pieces[index].1, x);
+                if y.is_finite() {
+                    endpoints.push(Endpoint {
+                        x,
+                        y,
+                        closed: here == Some(index),
+                    });
+                }
+            }
+        }
+        endpoints
+    }
+    /// The derivative at `x`, NaN where it cannot be evaluated.
+    pub fn slope(&self, x: f64, context: &Context) -> f64 {
+        let derivative = match self.derivative {
+            Ok(ref x) => x,
+            // User defined functions have no symbolic derivative, fall back to a central difference
+            Err(_) => {
+                let h = 1e-6 * x.abs().max(1.0);
+                return (self.evaluate(x + h, context) - self.evaluate(x - h, context)) / (2.0 * h);
+            }
+        };
+        let locals = [("x", x)];
+        let scope = Scope::new(context, &locals);
+        evaluator::evaluate_in(derivative, &scope).unwrap_or(f64::NAN)
+    }
+}
+/// Every entry together with the values their expressions read.
+#[derive(Default)]
+pub struct EntryList {
+    /// The entries in list order
+    pub entries: Vec<ExpressionEntry>,
+    /// Slider values, named lists and user defined functions
+    pub context: Context,
+    created: usize,
+}
+impl EntryList {
+    /// An empty list.
+    pub fn new() -> EntryList {
+        EntryList::default()
+    }
+    /// Adds an entry with the next free label and palette color.
+    pub fn add(&mut self, expression: &str) {
+        let color = PALETTE[self.created % PALETTE.len()];
+        self.created += 1;
+        let label = format!("f{}", self.created);
+        let mut entry = ExpressionEntry::new(self.created, &label, expression, color);
+        if !self.context.lists.is_empty() {
+            entry.names = self.context.lists.keys().cloned().collect();
+            entry.reparse();
+        }
+        self.entries.push(entry);
+    }
+    /// Adds an entry under an id and label handed out earlier, e.g. by a loaded session.
+    /// Later entries keep counting past both so neither is given out twice, an id that is
+    /// already taken is replaced by a fresh one
+    pub fn add_labelled(
+        &mut self,
+        id: usize,
+        label: &str,
+        expression: &str,
+    ) -> &mut ExpressionEntry {
+        let is_taken = self.entries.iter().any(|x| x.id == id);
+        self.add(expression);
+        // `add` handed out `created` as the fresh id
+        let id = match is_taken {
+            true => self.created,
+            false => id,
+        };
+        self.created = self.created.max(id);
+        if let Some(x) = label
+            .strip_prefix('f')
+            .and_then(|x| x.parse::<usize>().ok())
+        {
+            self.created = self.created.max(x);
+        }
+        let entry = self.entries.last_mut().unwrap();
+        entry.id = id;
+        entry.label = label.to_string();
+        entry
+    }
+    /// Drops every entry together with the variables and lists they read
+    pub fn clear(&mut self) {
+        *self = EntryList::new();
+    }
+    /// Removes the entry at `index`, if there is one.
+    pub fn remove(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.entries.remove(index);
+        }
+    }
+    // Entries reading the variable count as changed so their caches are rebuilt
+    fn touch_readers(&mut self, name: &str) {
+        for entry in self.entries.iter_mut() {
+            if entry.get_free_variables().iter().any(|x| x == name) {
+                entry.revision += 1;
+            }
+        }
+    }
+    /// Sets a variable, e.g. from a slider.
+    pub fn set_variable(&mut self, name: &str, value: f64) {
+        if self.context.variables.get(name) != Some(&value) {
+            self.context.variables.insert(name.to_string(), value);
+            self.touch_readers(name);
+        }
+    }
+    /// New names change how expressions split into terms, so every entry is parsed again
+    pub fn set_list(&mut self, name: &str, values: Vec<f64>) {
+        self.context.lists.insert(name.to_string(), values);
+        for entry in self.entries.iter_mut() {
+            match entry.names.iter().any(|x| x == name) {
+                true => entry.revision += 1,
+                false => {
+                    entry.names.push(name.to_string());
+                    entry.reparse();
+                }
+            }
+        }
+    }
+    /// Forgets a variable, e.g. once its slider is gone.
+    pub fn remove_variable(&mut self, name: &str) {
+        if self.context.variables.remove(name).is_some() {
+            self.touch_readers(name);
+        }
+    }
+    /// `time` in seconds moves the animated points along their curves
+    pub fn push_to_scene(&self, scene: &mut Scene, camera: &Camera, time: f64) {
+        let screen = scene.size;
+        let visible = self
+            .entries
+            .iter()
+            .filter(|x| x.style.visible && x.ast.is_ok())
+            .collect::<Vec<&ExpressionEntry>>();
+        if visible.is_empty() {
+            return;
+        }
+        // The per frame budget is shared between every visible curve
+        let budget = plotter::DEFAULT_SAMPLE_BUDGET / visible.len();
+        for entry in visible.iter() {
+            let range = entry.range;
+            let polylines = match entry.get_kind() {
+                EntryKind::Function => plotter::sample_adaptive(
+                    camera,
+                    screen,
+                    |x| entry.evaluate(x, &self.context),
+                    budget,
+                ),
+                EntryKind::Parametric | EntryKind::Polar => plotter::sample_parametric(
+                    camera,
+                    screen,
+                    |t| entry.evaluate_point(t, &self.context),
+                    (range.min, range.max),
+                    range.step,
+                    budget,
+                ),
+                EntryKind::Implicit => implicit::trace_implicit(
+                    camera,
+                    screen,
+                    |x, y| entry.evaluate_relation(x, y, &self.context),
+                    |x, y| entry.bound_relation(x, y, &self.context),
+                    implicit::DEFAULT_CELL_BUDGET / visible.len(),
+                ),
+                EntryKind::Inequality => {
+                    let budget = implicit::DEFAULT_CELL_BUDGET / visible.len();
+                    self.push_region(scene, entry, camera, budget);
+                    continue;
+                }
+                // The markers themselves go through the shape cache, see `push_markers`
+                EntryKind::Points => {
+                    self.push_point_lines(scene, entry, camera);
+                    continue;
+                }
+                EntryKind::Histogram | EntryKind::BoxPlot => {
+                    self.push_statistics(scene, entry, camera);
+                    continue;
+                }
+            };
+            if entry.animate && entry.get_kind() != EntryKind::Function {
+                let progress = (time / ANIMATION_SECONDS).fract();
+                let t = range.min + (range.max - range.min) * progress;
+                if let Some((x, y)) = entry.evaluate_point(t, &self.context) {
+                    let point = camera.world_to_screen(&BigVec2::new_d(x, y), screen);
+                    scene.circle(point, ANIMATION_RADIUS, Some(entry.style.color), None);
+                }
+            }
+            push_polylines(scene, polylines, &entry.style, entry.style.dash);
+            if entry.get_kind() == EntryKind::Function {
+                self.push_endpoints(scene, entry, camera);
+            }
+        }
+    }
+    fn push_point_lines(&self, scene: &mut Scene, entry: &ExpressionEntry, camera: &Camera) {
+        let screen = scene.size;
+        let points = entry.get_data_points(&self.context);
+        let to_screen = |x: f64, y: f64| camera.world_to_screen(&BigVec2::new_d(x, y), screen);
+        if entry.style.connect {
+            let polyline = points
+                .iter()
+                .map(|point| to_screen(point.x, point.y))
+                .collect::<Vec<Vec2>>();
+            push_polylines(scene, vec![polyline], &entry.style, entry.style.dash);
+        }
+        for point in points.iter() {
+            let bars = [(point.error_x, 0.0), (0.0, point.error_y)];
+            for (dx, dy) in bars {
+                if dx == 0.0 && dy == 0.0 {
+                    continue;
+                }
+                let (a, b) = (
+                    to_screen(point.x - dx, point.y - dy),
+                    to_screen(point.x + dx, point.y + dy),
+                );
+                scene.line(a, b, ERROR_BAR_THICKNESS, entry.style.color);
+                // Caps are perpendicular to the bar and a fixed number of pixels wide
+                let cap = match dx == 0.0 {
+                    true => vec2(ERROR_BAR_CAP, 0.0),
+                    false => vec2(0.0, ERROR_BAR_CAP),
+                };
+                for end in [a, b] {
+                    scene.line(end - cap, end + cap, ERROR_BAR_THICKNESS, entry.style.color);
+                }
+            }
+        }
+    }
+    fn push_statistics(&self, scene: &mut Scene, entry: &ExpressionEntry, camera: &Camera) {
+        let screen = scene.size;
+        let to_screen = |x: f64, y: f64| camera.world_to_screen(&BigVec2::new_d(x, y), screen);
+        let (color, width) = (entry.style.color, entry.style.line_width / 2.0);
+        let fill = Color {
+            a: REGION_ALPHA,
+            ..color
+        };
+        let mut boxes = Vec::new();
+        let mut segments = Vec::new();
+        for bin in entry.get_histogram(&self.context).unwrap_or_default() {
+            boxes.push((
+                to_screen(bin.start, 0.0),
+                to_screen(bin.end, bin.count as f64),
+            ));
+        }
+        if let Ok(Some((summary, y))) = entry.get_box_plot(&self.context) {
+            let (low, high) = (y - BOX_HALF_HEIGHT, y + BOX_HALF_HEIGHT);
+            boxes.push((
+                to_screen(summary.lower_quartile, low),
+                to_screen(summary.upper_quartile, high),
+            ));
+            segments.push((
+                to_screen(summary.median, low),
+                to_screen(summary.median, high),
+            ));
+            segments.push((
+                to_screen(summary.min, y),
+                to_screen(summary.lower_quartile, y),
+            ));
+            segments.push((
+                to_screen(summary.upper_quartile, y),
+                to_screen(summary.max, y),
+            ));
+            // Whisker caps are half as tall as the box
+            for x in [summary.min, summary.max] {
+                let half = BOX_HALF_HEIGHT / 2.0;
+                segments.push((to_screen(x, y - half), to_screen(x, y + half)));
+            }
+        }
+        for (a, b) in boxes {
+            let corner = a.min(b);
+            let size = (a - b).abs();
+            let rect = Rect::new(corner.x, corner.y, size.x, size.y);
+            scene.rectangle(rect, Some(fill), Some((width, color)));
+        }
+        for (a, b) in segments {
+            scene.line(a, b, width, color);
+        }
+    }
+    /// Markers of every visible point list, drawn later in one go by the cache
+    pub fn push_markers(&self, shapes: &mut ShapeCache) {
+        let lists = self
+            .entries
+            .iter()
+            .filter(|x| x.style.visible && x.get_kind() == EntryKind::Points);
+        for entry in lists {
+            for point in entry.get_data_points(&self.context) {
+                shapes.push(Marker {
+                    x: point.x,
+                    y: point.y,
+                    shape: entry.style.marker,
+                    size: entry.style.marker_size,
+                    color: entry.style.color,
+                });
+            }
+        }
+    }
+    // Open dots go first so a closed dot on the same spot covers them
+    fn push_endpoints(&self, scene: &mut Scene, entry: &ExpressionEntry, camera: &Camera) {
+        let screen = scene.size;
+        let x_min = camera
+            .screen_to_world(vec2(0.0, 0.0), screen)
+            .x
+            .get_deserialized();
+        let x_max = camera.screen_to_world(screen, screen).x.get_deserialized();
+        let endpoints = entry
+            .get_endpoints(x_min, x_max, &self.context)
+            .into_iter()
+            .sorted_by_key(|x| x.closed);
+        for endpoint in endpoints {
+            let point = camera.world_to_screen(&BigVec2::new_d(endpoint.x, endpoint.y), screen);
+            let color = entry.style.color;
+            match endpoint.closed {
+                true => scene.circle(point, ENDPOINT_RADIUS, Some(color), None),
+                false => scene.circle(point, ENDPOINT_RADIUS, Some(WHITE), Some((2.0, color))),
+            }
+        }
+    }
+    // Shading goes first so the boundaries stay on top, strict comparisons leave their
+    // boundary out of the region and get a dashed line
+    fn push_region(
+        &self,
+        scene: &mut Scene,
+        entry: &ExpressionEntry,
+        camera: &Camera,
+        budget: usize,
+    ) {
+        let screen = scene.size;
+        let ast = match entry.ast {
+            Ok(ref x) => x,
+            Err(_) => return,
+        };
+        let condition = |x: f64, y: f64| entry.holds_at(x, y, &self.context);
+        let boxes = |x: Interval, y: Interval| HashMap::from([("x", x), ("y", y)]);
+        let rectangles = implicit::shade_region(
+            camera,
+            screen,
+            condition,
+            |x, y| interval::decide(ast, &boxes(x, y), &self.context),
+            budget / 2,
+        );
+        let fill = Color {
+            a: REGION_ALPHA,
+            ..entry.style.color
+        };
+        for rectangle in rectangles {
+            scene.rectangle(rectangle, Some(fill), None);
+        }
+        let comparisons = ast.get_comparisons();
+        for (comparison, a, b) in comparisons.iter() {
+            let difference = |x: f64, y: f64| {
+                let locals = [("x", x), ("y", y)];
+                let scope = Scope::new(&self.context, &locals);
+                match (
+                    evaluator::evaluate_in(a, &scope),
+                    evaluator::evaluate_in(b, &scope),
+                ) {
+                    (Ok(a), Ok(b)) => a - b,
+                    _ => f64::NAN,
+                }
+            };
+            let bounds = |x: Interval, y: Interval| {
+                let variables = boxes(x, y);
+                interval::evaluate(a, &variables, &self.context)
+                    - interval::evaluate(b, &variables, &self.context)
+            };
+            let boundary = implicit::trace_implicit(
+                camera,
+                screen,
+                difference,
+                bounds,
+                budget / 2 / comparisons.len(),
+            );
+            let border = implicit::region_border(camera, screen, boundary, condition);
+            let dash = match comparison.is_strict() {
+                true => DashStyle::Dashed,
+                false => entry.style.dash,
+            };
+            push_polylines(scene, border, &entry.style, dash);
+        }
+    }
+    /// Adds a legend with the label and style of every visible entry.
+    pub fn push_legend(&self, scene: &mut Scene) {
+        let visible = self
+            .entries
+            .iter()
+            .filter(|x| x.style.visible)
+            .collect::<Vec<&ExpressionEntry>>();
+        if visible.is_empty() {
+            return;
+        }
+        let row_height = LEGEND_FONT_SIZE + 4.0;
+        // Estimated so the legend looks the same with or without a window
+        let text_width = visible
+            .iter()
+            .map(|x| scene::estimate_text_width(&x.label, LEGEND_FONT_SIZE))
+            .fold(0.0, f32::max);
+        let size = vec2(
+            LEGEND_SAMPLE_LENGTH + text_width + LEGEND_PADDING * 3.0,
+            row_height * visible.len() as f32 + LEGEND_PADDING * 2.0,
+        );
+        let corner = vec2(scene.size.x - size.x - LEGEND_PADDING, LEGEND_PADDING);
+        scene.rectangle(
+            Rect::new(corner.x, corner.y, size.x, size.y),
+            Some(Color::new(1.0, 1.0, 1.0, 0.85)),
+            Some((1.0, LIGHTGRAY)),
+        );
+        for (index, entry) in visible.iter().enumerate() {
+            let y = corner.y + LEGEND_PADDING + row_height * (index as f32 + 0.5);
+            let start = vec2(corner.x + LEGEND_PADDING, y);
+            let sample = vec![start, start + vec2(LEGEND_SAMPLE_LENGTH, 0.0)];
+            push_polylines(scene, vec![sample], &entry.style, entry.style.dash);
+            let position = vec2(start.x + LEGEND_SAMPLE_LENGTH + LEGEND_PADDING, y);
+            scene.text(
+                &entry.label,
+                position,
+                LEGEND_FONT_SIZE,
+                DARKGRAY,
+                TextAnchor::Left,
+            );
+        }
+    }
+}
+#[cfg(feature = "ui")]
+impl EntryList {
+    /// Every keystroke re-parses the edited entry so the plot follows along while typing
+    pub fn create_expression_ui(&mut self, position: Vec2) {
+        let mut added = false;
+        widgets::Window::new(hash!(), position, vec2(360.0, 400.0))
+            .label("Expressions")
+            .movable(true)
+            .ui(&mut root_ui(), |ui| {
+                for entry in self.entries.iter_mut() {
+                    let before = entry.expression.clone();
+                    ui.input_text(
+                        hash!("expression", entry.id),
+                        &entry.label,
+                        &mut entry.expression,
+                    );
+                    if entry.expression != before {
+                        entry.reparse();
+                    }
+                    if let Some(error) = entry.get_error(&self.context) {
+                        ui.label(None, &format!("  ! {}", error));
+                    }
+                }
+                if ui.button(None, "New expression") {
+                    added = true;
+                }
+            });
+        if added {
+            self.add("");
+        }
+    }
+    /// Draws the window for adding, removing and styling entries.
+    pub fn create_ui(&mut self, position: Vec2) {
+        let mut removed = None;
+        let mut added = false;
+        widgets::Window::new(hash!(), position, vec2(340.0, 400.0))
+            .label("Graphs")
+            .movable(true)
+            .ui(&mut root_ui(), |ui| {
+                for (index, entry) in self.entries.iter_mut().enumerate() {
+                    ui.checkbox(
+                        hash!("visible", entry.id),
+                        "Visible",
+                        &mut entry.style.visible,
+                    );
+                    ui.input_text(hash!("label", entry.id), "Label", &mut entry.label);
+                    match entry.get_kind() {
+                        EntryKind::Function => {
+                            ui.label(None, &format!("y = {}", entry.expression));
+                        }
+                        EntryKind::Implicit
+                        | EntryKind::Inequality
+                        | EntryKind::Histogram
+                        | EntryKind::BoxPlot => {
+                            ui.label(None, &entry.expression);
+                        }
+                        EntryKind::Points => {
+                            ui.label(None, &entry.expression);
+                            let names = MarkerShape::ALL.map(|x| x.get_name());
+                            let mut marker_index = MarkerShape::ALL
+                                .iter()
+                                .position(|x| *x == entry.style.marker)
+                                .unwrap_or(0);
+                            ui.combo_box(
+                                hash!("marker", entry.id),
+                                "Marker",
+                                &names,
+                                &mut marker_index,
+                            );
+                            entry.style.marker = MarkerShape::ALL[marker_index];
+                            ui.slider(
+                                hash!("marker size", entry.id),
+                                "Size",
+                                2.0f32..12.0f32,
+                                &mut entry.style.marker_size,
+                            );
+                            ui.checkbox(
+                                hash!("connect", entry.id),
+                                "Connect",
+                                &mut entry.style.connect,
+                            );
+                        }
+                        kind => {
+                            let parameter = entry.get_parameter();
+                            match kind {
+                                EntryKind::Parametric => {
+                                    ui.label(None, &format!("(x, y) = {}", entry.expression));
+                                }
+                                _ => ui.label(None, &entry.expression),
+                            }
+                            let mut range = [
+                                entry.range.min as f32,
+                                entry.range.max as f32,
+                                entry.range.step as f32,
+                            ];
+                            ui.slider(
+                                hash!("t min", entry.id),
+                                &format!("{} min", parameter),
+                                -100.0..100.0,
+                                &mut range[0],
+                            );
+                            ui.slider(
+                                hash!("t max", entry.id),
+                                &format!("{} max", parameter),
+                                -100.0..100.0,
+                                &mut range[1],
+                            );
+                            ui.slider(
+                                hash!("t step", entry.id),
+                                &format!("{} step", parameter),
+                                0.001..1.0,
+                                &mut range[2],
+                            );
+                            ui.checkbox(hash!("animate", entry.id), "Animate", &mut entry.animate);
+                            entry.range = ParameterRange {
+                                min: range[0] as f64,
+                                max: range[1] as f64,
+                                step: range[2] as f64,
+                            };
+                        }
+                    }
+                    // Colors from a session that are not in the palette show as Custom and
+                    // are kept until another color is picked
+                    let current = PALETTE.iter().position(|x| *x == entry.style.color);
+                    let mut names = PALETTE_NAMES.to_vec();
+                    if current.is_none() {
+                        names.push(CUSTOM_COLOR_NAME);
+                    }
+                    let mut color_index = current.unwrap_or(PALETTE.len());
+                    ui.combo_box(hash!("color", entry.id), "Color", &names, &mut color_index);
+                    if Some(color_index) != current && color_index < PALETTE.len() {
+                        entry.style.color = PALETTE[color_index];
+                    }
+                    ui.slider(
+                        hash!("width", entry.id),
+                        "Width",
+                        1.0f32..8.0f32,
+                        &mut entry.style.line_width,
+                    );
+                    let names = DashStyle::ALL.map(|x| x.get_name());
+                    let mut dash_index = DashStyle::ALL
+                        .iter()
+                        .position(|x| *x == entry.style.dash)
+                        .unwrap_or(0);
+                    ui.combo_box(hash!("dash", entry.id), "Dash", &names, &mut dash_index);
+                    entry.style.dash = DashStyle::ALL[dash_index];
+                    if ui.button(None, "Remove") {
+                        removed = Some(index);
+                    }
+                    ui.separator();
+                }
+                if ui.button(None, "Add graph") {
+                    added = true;
+                }
+            });
+        if let Some(index) = removed {
+            self.remove(index);
+        }
+        if added {
+            self.add("x");
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn entries_parse_their_expression() {
+        let mut list = EntryList::new();
+        list.add("y = x^2");
+        list.add("2x + 1");
+        list.add("x +");
+        assert_eq!(list.entries[0].evaluate(3.0, &list.context), 9.0);
+        assert_eq!(list.entries[1].evaluate(3.0, &list.context), 7.0);
+        assert!(list.entries[2].ast.is_err());
+        assert!(list.entries[2].evaluate(3.0, &list.context).is_nan());
+        assert_ne!(list.entries[0].style.color, list.entries[1].style.color);
+    }
+    #[test]
+    fn editing_reparses() {
+        let mut list = EntryList::new();
+        list.add("x");
+        list.entries[0].set_expression("x +");
+        assert!(list.entries[0].get_error(&list.context).is_some());
+        list.entries[0].set_expression("x + a");
+        assert_eq!(
+            list.entries[0].get_error(&list.context),
+            Some("Undefined: a".to_string())
+        );
+        list.context.variables.insert("a".to_string(), 2.0);
+        assert_eq!(list.entries[0].get_error(&list.context), None);
+        assert_eq!(list.entries[0].evaluate(1.0, &list.context), 3.0);
+    }
+    #[test]
+    fn parametric_entries() {
+        let mut list = EntryList::new();
+        list.add("(cos(t), sin(t))");
+        list.add("(t, t, t)");
+        let entry = &list.entries[0];
+        assert_eq!(entry.get_kind(), EntryKind::Parametric);
+        assert_eq!(entry.get_error(&list.context), None);
+        assert_eq!(entry.evaluate_point(0.0, &list.context), Some((1.0, 0.0)));
+        assert!(entry.evaluate(0.0, &list.context).is_nan());
+        assert!(list.entries[1].get_error(&list.context).is_some());
+    }
+    #[test]
+    fn polar_entries() {
+        let mut list = EntryList::new();
+        list.add("r = 1 + cos(theta)");
+        list.add("r = (θ, θ)");
+        let entry = &list.entries[0];
+        assert_eq!(entry.get_kind(), EntryKind::Polar);
+        assert_eq!(entry.get_error(&list.context), None);
+        assert_eq!(entry.evaluate_point(0.0, &list.context), Some((2.0, 0.0)));
+        let (x, y) = entry
+            .evaluate_point(std::f64::consts::FRAC_PI_2, &list.context)
+            .unwrap();
+        assert!(x.abs() < 1e-12 && (y - 1.0).abs() < 1e-12);
+        assert!(list.entries[1].get_error(&list.context).is_some());
+    }
+    #[test]
+    fn implicit_entries() {
+        let mut list = EntryList::new();
+        list.add("x^2 + y^2 = 25");
+        list.add("y = xy + 1");
+        list.add("y = 2x");
+        let entry = &list.entries[0];
+        assert_eq!(entry.get_kind(), EntryKind::Implicit);
+        assert_eq!(entry.get_error(&list.context), None);
+        assert_eq!(entry.evaluate_relation(3.0, 4.0, &list.context), 0.0);
+        let range = entry.bound_relation(
+            Interval::new(0.0, 1.0),
+            Interval::new(0.0, 1.0),
+            &list.context,
+        );
+        assert!(!range.contains(0.0));
+        assert_eq!(list.entries[1].get_kind(), EntryKind::Implicit);
+        assert_eq!(
+            list.entries[1].evaluate_relation(1.0, 2.0, &list.context),
+            -1.0
+        );
+        assert_eq!(list.entries[2].get_kind(), EntryKind::Function);
+    }
+    #[test]
+    fn inequality_entries() {
+        let mut list = EntryList::new();
+        list.add("y < x^2 + 1");
+        list.add("x^2 + y^2 <= 9 and y >= 0");
+        let entry = &list.entries[0];
+        assert_eq!(entry.get_kind(), EntryKind::Inequality);
+        assert_eq!(entry.get_error(&list.context), None);
+        assert!(entry.holds_at(0.0, 0.5, &list.context));
+        assert!(!entry.holds_at(0.0, 1.0, &list.context));
+        let entry = &list.entries[1];
+        assert_eq!(entry.get_kind(), EntryKind::Inequality);
+        assert!(entry.holds_at(0.0, 3.0, &list.context));
+        assert!(!entry.holds_at(0.0, -1.0, &list.context));
+    }
+    #[test]
+    fn piecewise_entries() {
+        let mut list = EntryList::new();
+        list.add("{x < 0: -x, x >= 0: x^2 + 1}");
+        list.add("y = {x <= 1: 2, x > 1: 2}");
+        let entry = &list.entries[0];
+        assert_eq!(entry.get_kind(), EntryKind::Function);
+        assert_eq!(entry.evaluate(-3.0, &list.context), 3.0);
+        assert_eq!(entry.slope(2.0, &list.context), 4.0);
+        let endpoints = entry.get_endpoints(-5.0, 5.0, &list.context);
+        assert_eq!(endpoints.len(), 2);
+        assert!(endpoints[0].x.abs() < 1e-9 && endpoints[0].y.abs() < 1e-9);
+        assert!(!endpoints[0].closed);
+        assert!((endpoints[1].y - 1.0).abs() < 1e-9);
+        assert!(endpoints[1].closed);
+        // Pieces that join without a jump still mark where they meet
+        let endpoints = list.entries[1].get_endpoints(-5.0, 5.0, &list.context);
+        assert_eq!(endpoints.iter().filter(|x| x.closed).count(), 1);
+        assert!(list.entries[1]
+            .get_endpoints(2.0, 5.0, &list.context)
+            .is_empty());
+    }
+    #[test]
+    fn point_lists() {
+        let mut list = EntryList::new();
+        list.add("[(1, 2), (3, 4, 0.5), (5, -6, 1, 2), (1/0, 1)]");
+        list.add("[(1, 2), 3]");
+        let entry = &list.entries[0];
+        assert_eq!(entry.get_kind(), EntryKind::Points);
+        assert_eq!(entry.get_error(&list.context), None);
+        let points = entry.get_data_points(&list.context);
+        assert_eq!(points.len(), 3);
+        assert_eq!((points[1].error_x, points[1].error_y), (0.0, 0.5));
+        assert_eq!(
+            points[2],
+            DataPoint {
+                x: 5.0,
+                y: -6.0,
+                error_x: 1.0,
+                error_y: 2.0
+            }
+        );
+        assert!(list.entries[1].get_error(&list.context).is_some());
+        let mut shapes = ShapeCache::new();
+        list.push_markers(&mut shapes);
+        assert_eq!(shapes.len(), 4);
+    }
+    #[test]
+    fn statistics_plots() {
+        let mut list = EntryList::new();
+        list.add("histogram([1, 2, 2, 3, 7], 2)");
+        list.add("boxplot([1, 2, 3, 4, 5], 3)");
+        list.add("histogram(4)");
+        list.add("boxplot([])");
+        let entry = &list.entries[0];
+        assert_eq!(entry.get_kind(), EntryKind::Histogram);
+        assert_eq!(entry.get_error(&list.context), None);
+        let counts = entry
+            .get_histogram(&list.context)
+            .unwrap()
+            .iter()
+            .map(|x| x.count)
+            .collect::<Vec<usize>>();
+        assert_eq!(counts, vec![1, 3, 0, 1]);
+        let entry = &list.entries[1];
+        assert_eq!(entry.get_kind(), EntryKind::BoxPlot);
+        let (summary, y) = entry.get_box_plot(&list.context).unwrap().unwrap();
+        assert_eq!((summary.lower_quartile, summary.median, y), (2.0, 3.0, 3.0));
+        assert!(list.entries[2].get_error(&list.context).is_some());
+        assert_eq!(
+            list.entries[3].get_error(&list.context),
+            Some(EMPTY_PLOT_ERROR.to_string())
+        );
+    }
+    #[test]
+    fn removing_keeps_order() {
+        let mut list = EntryList::new();
+        list.add("x");
+        list.add("2x");
+        list.add("3x");
+        list.remove(1);
+        list.remove(7);
+        let labels = list
+            .entries
+            .iter()
+            .map(|x| x.label.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(labels, vec!["f1", "f3"]);
+    }
+}
@@ -0,0 +1,473 @@
+//! Grid lines, axes and their labels for the cartesian and polar grids.
+use crate::big_number::{BigNumber, BigVec2};
+use crate::camera::Camera;
+use crate::plotter::DashStyle;
+use crate::prelude::*;
+use crate::scene::{Scene, TextAnchor};
+
+const MIN_MAJOR_SPACING: f64 = 100.0;
+const LABEL_FONT_SIZE: f32 = 18.0;
+const LABEL_PADDING: f32 = 4.0;
+const MINOR_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.07);
+const MAJOR_COLOR: Color = Color::new(0.0, 0.0, 0.0, 0.2);
+const AXIS_COLOR: Color = BLACK;
+const LABEL_COLOR: Color = DARKGRAY;
+// Spokes every 15 degrees, labeled every 30
+const SPOKE_COUNT: i64 = 24;
+const LABELED_SPOKE_EVERY: i64 = 2;
+// Largest angle between two points of a ring and the sagitta allowed between them, in pixels
+const MAX_ARC_STEP: f32 = 0.1;
+const ARC_TOLERANCE: f32 = 0.25;
+
+/// How the spokes of the polar grid are labeled.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AngleUnit {
+    /// `30°`
+    Degrees,
+    /// `π/6`
+    Radians,
+}
+/// Which grid is drawn behind the graph.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GridMode {
+    /// Horizontal and vertical lines
+    Cartesian,
+    /// Rings around the origin and spokes out of it
+    Polar(AngleUnit),
+}
+impl GridMode {
+    /// Every mode, in the order the settings list them.
+    pub const ALL: [GridMode; 3] = [
+        GridMode::Cartesian,
+        GridMode::Polar(AngleUnit::Degrees),
+        GridMode::Polar(AngleUnit::Radians),
+    ];
+    /// The name shown in the settings.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            GridMode::Cartesian => "Cartesian",
+            GridMode::Polar(AngleUnit::Degrees) => "Polar (degrees)",
+            GridMode::Polar(AngleUnit::Radians) => "Polar (radians)",
+        }
+    }
+}
+
+/// Which weight a grid line is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LineKind {
+    /// Between two labeled values
+    Minor,
+    /// At a labeled value
+    Major,
+    /// The x or y axis
+    Axis,
+}
+/// Where a label sits relative to its position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LabelAnchor {
+    /// Label sits below its position (x ticks)
+    Top,
+    /// Label sits to the left of its position (y ticks)
+    Right,
+    /// Centered on its position (angle labels)
+    Center,
+}
+/// A straight grid line in screen space.
+pub struct GridLine {
+    /// One end, in pixels
+    pub start: Vec2,
+    /// The other end, in pixels
+    pub end: Vec2,
+    /// How heavy the line is
+    pub kind: LineKind,
+}
+/// The value written next to a major line.
+pub struct TickLabel {
+    /// The formatted value
+    pub text: String,
+    /// Where the label goes, in pixels
+    pub position: Vec2,
+    /// How the text is placed around `position`
+    pub anchor: LabelAnchor,
+}
+/// Rings of the polar grid, only the part of each ring near the screen is kept
+pub struct GridCurve {
+    /// The ring as a polyline, in pixels
+    pub points: Vec<Vec2>,
+    /// How heavy the ring is
+    pub kind: LineKind,
+}
+/// Everything the grid draws for one frame.
+pub struct GridGeometry {
+    /// Straight lines, including the axes
+    pub lines: Vec<GridLine>,
+    /// Rings of the polar grid
+    pub curves: Vec<GridCurve>,
+    /// Labels of the major lines
+    pub labels: Vec<TickLabel>,
+}
+// Spacing along one axis, expressed as mantissa * 10^exponent with mantissa in {1, 2, 5}
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TickSpacing {
+    mantissa: i64,
+    exponent: i32,
+    subdivisions: i64,
+}
+impl TickSpacing {
+    fn major(&self) -> f64 {
+        self.mantissa as f64 * 10f64.powi(self.exponent)
+    }
+    fn minor(&self) -> f64 {
+        self.major() / self.subdivisions as f64
+    }
+    // Builds the value of the n-th minor tick without accumulating floating point error
+    fn minor_value(&self, index: i64) -> f64 {
+        let numerator = index as f64 * self.mantissa as f64;
+        (numerator / self.subdivisions as f64) * 10f64.powi(self.exponent)
+    }
+}
+// Smallest step from the 1-2-5 sequence that is at least `min_step` world units
+fn nice_spacing(min_step: f64) -> TickSpacing {
+    let exponent = min_step.log10().floor() as i32;
+    let normalized = min_step / 10f64.powi(exponent);
+    let (mantissa, exponent) = match normalized {
+        x if (x <= 1.0) => (1, exponent),
+        x if (x <= 2.0) => (2, exponent),
+        x if (x <= 5.0) => (5, exponent),
+        _ => (1, exponent + 1),
+    };
+    TickSpacing {
+        mantissa,
+        exponent,
+        subdivisions: match mantissa {
+            2 => 4,
+            _ => 5,
+        },
+    }
+}
+fn format_tick(value: f64) -> String {
+    BigNumber::new_d(value).to_string()
+}
+/// The cartesian grid for the part of the plane on screen.
+pub fn compute(camera: &Camera, screen: Vec2) -> GridGeometry {
+    let spacing = nice_spacing(MIN_MAJOR_SPACING / camera.get_pixels_per_unit());
+    let to_screen_x = |x: f64| camera.world_to_screen(&BigVec2::new_d(x, 0.0), screen).x;
+    let to_screen_y = |y: f64| camera.world_to_screen(&BigVec2::new_d(0.0, y), screen).y;
+    let top_left = camera.screen_to_world(vec2(0.0, 0.0), screen);
+    let bottom_right = camera.screen_to_world(screen, screen);
+    let (left, top) = (top_left.x.get_deserialized(), top_left.y.get_deserialized());
+    let (right, bottom) = (
+        bottom_right.x.get_deserialized(),
+        bottom_right.y.get_deserialized(),
+    );
+
+    let mut lines = Vec::new();
+    let mut labels = Vec::new();
+    // Axis positions clamped so labels stay on screen when an axis scrolls away
+    let axis_x = to_screen_x(0.0).clamp(0.0, screen.x);
+    let axis_y = to_screen_y(0.0).clamp(0.0, screen.y - LABEL_FONT_SIZE);
+
+    for index in (left / spacing.minor()).ceil() as i64..=(right / spacing.minor()).floor() as i64 {
+        let value = spacing.minor_value(index);
+        let x = to_screen_x(value);
+        let kind = match (index == 0, index % spacing.subdivisions == 0) {
+            (true, _) => LineKind::Axis,
+            (false, true) => LineKind::Major,
+            (false, false) => LineKind::Minor,
+        };
+        lines.push(GridLine {
+            start: vec2(x, 0.0),
+            end: vec2(x, screen.y),
+            kind,
+        });
+        if kind == LineKind::Major {
+            labels.push(TickLabel {
+                text: format_tick(value),
+                position: vec2(x, axis_y + LABEL_PADDING),
+                anchor: LabelAnchor::Top,
+            });
+        }
+    }
+    for index in (bottom / spacing.minor()).ceil() as i64..=(top / spacing.minor()).floor() as i64 {
+        let value = spacing.minor_value(index);
+        let y = to_screen_y(value);
+        let kind = match (index == 0, index % spacing.subdivisions == 0) {
+            (true, _) => LineKind::Axis,
+            (false, true) => LineKind::Major,
+            (false, false) => LineKind::Minor,
+        };
+        lines.push(GridLine {
+            start: vec2(0.0, y),
+            end: vec2(screen.x, y),
+            kind,
+        });
+        if kind == LineKind::Major {
+            labels.push(TickLabel {
+                text: format_tick(value),
+                position: vec2(axis_x - LABEL_PADDING, y),
+                anchor: LabelAnchor::Right,
+            });
+        }
+    }
+    // Axes are drawn last so grid lines never cover them
+    lines.sort_by_key(|line| line.kind == LineKind::Axis);
+    GridGeometry {
+        lines,
+        curves: Vec::new(),
+        labels,
+    }
+}
+// Angle of the n-th of `SPOKE_COUNT` spokes, as "90°" or as a reduced fraction of π
+fn format_angle(index: i64, unit: AngleUnit) -> String {
+    match unit {
+        AngleUnit::Degrees => format!("{}°", index * 360 / SPOKE_COUNT),
+        AngleUnit::Radians => {
+            let (mut numerator, mut denominator) = (index * 2, SPOKE_COUNT);
+            let (mut a, mut b) = (numerator, denominator);
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            if a != 0 {
+                numerator /= a;
+                denominator /= a;
+            }
+            match (numerator, denominator) {
+                (0, _) => "0".to_string(),
+                (1, 1) => "π".to_string(),
+                (x, 1) => format!("{}π", x),
+                (1, y) => format!("π/{}", y),
+                (x, y) => format!("{}π/{}", x, y),
+            }
+        }
+    }
+}
+// Range of screen angles (counterclockwise, y up) under which the screen is seen from `origin`
+fn visible_angles(origin: Vec2, screen: Vec2) -> (f32, f32) {
+    let inside = (0.0..=screen.x).contains(&origin.x) && (0.0..=screen.y).contains(&origin.y);
+    if inside {
+        return (0.0, std::f32::consts::TAU);
+    }
+    let angle_to = |point: Vec2| (origin.y - point.y).atan2(point.x - origin.x);
+    let center = angle_to(screen / 2.0);
+    let corners = [
+        vec2(0.0, 0.0),
+        vec2(screen.x, 0.0),
+        vec2(0.0, screen.y),
+        screen,
+    ];
+    let (mut low, mut high) = (0.0f32, 0.0f32);
+    for corner in corners.iter() {
+        let mut delta = angle_to(*corner) - center;
+        if delta > std::f32::consts::PI {
+            delta -= std::f32::consts::TAU;
+        } else if delta < -std::f32::consts::PI {
+            delta += std::f32::consts::TAU;
+        }
+        low = low.min(delta);
+        high = high.max(delta);
+    }
+    (center + low, center + high)
+}
+// Where a ray from `origin` leaves the screen shrunk by `margin`, if it crosses it at all
+fn ray_exit(origin: Vec2, direction: Vec2, screen: Vec2, margin: f32) -> Option<Vec2> {
+    let (low, high) = (Vec2::splat(margin), screen - margin);
+    let first = (low - origin) / direction;
+    let second = (high - origin) / direction;
+    let near = first.min(second).max_element();
+    let far = first.max(second).min_element();
+    match far >= near.max(0.0) && far.is_finite() {
+        true => Some(origin + direction * far),
+        false => None,
+    }
+}
+/// The polar grid for the part of the plane on screen, with spokes labeled in `unit`.
+pub fn compute_polar(camera: &Camera, screen: Vec2, unit: AngleUnit) -> GridGeometry {
+    let pixels_per_unit = camera.get_pixels_per_unit();
+    let spacing = nice_spacing(MIN_MAJOR_SPACING / pixels_per_unit);
+    let origin = camera.world_to_screen(&BigVec2::new_d(0.0, 0.0), screen);
+    let mut geometry = GridGeometry {
+        lines: Vec::new(),
+        curves: Vec::new(),
+        labels: Vec::new(),
+    };
+    if !origin.is_finite() {
+        return geometry;
+    }
+    let corners = [
+        vec2(0.0, 0.0),
+        vec2(screen.x, 0.0),
+        vec2(0.0, screen.y),
+        screen,
+    ];
+    let far = corners
+        .iter()
+        .map(|x| x.distance(origin))
+        .fold(0.0, f32::max);
+    let near = origin.clamp(Vec2::ZERO, screen).distance(origin);
+    let (start, end) = visible_angles(origin, screen);
+
+    let first = (near as f64 / pixels_per_unit / spacing.minor())
+        .ceil()
+        .max(1.0) as i64;
+    let last = (far as f64 / pixels_per_unit / spacing.minor()).floor() as i64;
+    for index in first..=last {
+        let value = spacing.minor_value(index);
+        let radius = (value * pixels_per_unit) as f32;
+        let step = (2.0 * (2.0 * ARC_TOLERANCE / radius).sqrt()).min(MAX_ARC_STEP);
+        let count = ((end - start) / step).ceil().max(1.0) as usize;
+        let points = (0..=count)
+            .map(|i| start + (end - start) * i as f32 / count as f32)
+            .map(|angle| origin + vec2(angle.cos(), -angle.sin()) * radius)
+            .collect::<Vec<Vec2>>();
+        let kind = match index % spacing.subdivisions == 0 {
+            true => LineKind::Major,
+            false => LineKind::Minor,
+        };
+        geometry.curves.push(GridCurve { points, kind });
+        let label = origin + vec2(radius, 0.0);
+        if kind == LineKind::Major && label.x <= screen.x {
+            geometry.labels.push(TickLabel {
+                text: format_tick(value),
+                position: label + vec2(0.0, LABEL_PADDING),
+                anchor: LabelAnchor::Top,
+            });
+        }
+    }
+    for index in 0..SPOKE_COUNT {
+        let angle = std::f32::consts::TAU * index as f32 / SPOKE_COUNT as f32;
+        let direction = vec2(angle.cos(), -angle.sin());
+        let kind = match (
+            index % (SPOKE_COUNT / 4) == 0,
+            index % LABELED_SPOKE_EVERY == 0,
+        ) {
+            (true, _) => LineKind::Axis,
+            (false, true) => LineKind::Major,
+            (false, false) => LineKind::Minor,
+        };
+        geometry.lines.push(GridLine {
+            start: origin,
+            end: origin + direction * far,
+            kind,
+        });
+        if index % LABELED_SPOKE_EVERY != 0 {
+            continue;
+        }
+        if let Some(position) = ray_exit(origin, direction, screen, LABEL_FONT_SIZE * 1.5) {
+            geometry.labels.push(TickLabel {
+                text: format_angle(index, unit),
+                position,
+                anchor: LabelAnchor::Center,
+            });
+        }
+    }
+    geometry
+        .lines
+        .sort_by_key(|line| line.kind == LineKind::Axis);
+    geometry
+}
+fn line_style(kind: LineKind) -> (f32, Color) {
+    match kind {
+        LineKind::Minor => (1.0, MINOR_COLOR),
+        LineKind::Major => (1.0, MAJOR_COLOR),
+        LineKind::Axis => (2.0, AXIS_COLOR),
+    }
+}
+/// Adds the lines and labels to the scene, below anything pushed after them.
+pub fn push_to_scene(geometry: &GridGeometry, scene: &mut Scene) {
+    for curve in geometry.curves.iter() {
+        let (thickness, color) = line_style(curve.kind);
+        scene.polyline(curve.points.clone(), thickness, color, DashStyle::Solid);
+    }
+    for line in geometry.lines.iter() {
+        let (thickness, color) = line_style(line.kind);
+        scene.line(line.start, line.end, thickness, color);
+    }
+    for label in geometry.labels.iter() {
+        let anchor = match label.anchor {
+            LabelAnchor::Top => TextAnchor::Top,
+            LabelAnchor::Right => TextAnchor::Right,
+            LabelAnchor::Center => TextAnchor::Center,
+        };
+        scene.text(
+            &label.text,
+            label.position,
+            LABEL_FONT_SIZE,
+            LABEL_COLOR,
+            anchor,
+        );
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn camera_at(x: f64, y: f64, zoom: f64) -> Camera {
+        Camera {
+            position: BigVec2::new_d(x, y),
+            number_distance: BigNumber::new_d(zoom),
+        }
+    }
+    #[test]
+    fn spacing_follows_one_two_five() {
+        let steps = [0.9, 1.0, 1.5, 3.0, 7.0, 0.013, 420.0]
+            .iter()
+            .map(|&x| nice_spacing(x).major())
+            .collect::<Vec<f64>>();
+        let expected = [1.0, 1.0, 2.0, 5.0, 10.0, 0.02, 500.0];
+        for (step, expected) in steps.iter().zip(expected.iter()) {
+            assert!((step - expected).abs() < expected * 1e-9);
+        }
+        assert_eq!(nice_spacing(1.5).subdivisions, 4);
+        assert_eq!(nice_spacing(3.0).subdivisions, 5);
+    }
+    #[test]
+    fn labels_adapt_to_zoom() {
+        let screen = vec2(1000.0, 500.0);
+        let zoomed_in = compute(&camera_at(0.0, 0.0, 1e7), screen);
+        let zoomed_out = compute(&camera_at(0.0, 0.0, 1e-7), screen);
+        assert!(zoomed_in.labels.iter().any(|x| x.text == "1x10^-5"));
+        assert!(zoomed_out.labels.iter().any(|x| x.text == "1B"));
+        assert!(zoomed_out.labels.iter().any(|x| x.text == "-1B"));
+    }
+    #[test]
+    fn axes_only_when_visible() {
+        let screen = vec2(1000.0, 500.0);
+        let centered = compute(&camera_at(0.0, 0.0, 100.0), screen);
+        let shifted = compute(&camera_at(1000.0, 1000.0, 100.0), screen);
+        let axis_count = |geometry: &GridGeometry| {
+            geometry
+                .lines
+                .iter()
+                .filter(|x| x.kind == LineKind::Axis)
+                .count()
+        };
+        assert_eq!(axis_count(&centered), 2);
+        assert_eq!(axis_count(&shifted), 0);
+    }
+    #[test]
+    fn angle_labels() {
+        assert_eq!(format_angle(6, AngleUnit::Degrees), "90°");
+        assert_eq!(format_angle(0, AngleUnit::Radians), "0");
+        assert_eq!(format_angle(2, AngleUnit::Radians), "π/6");
+        assert_eq!(format_angle(12, AngleUnit::Radians), "π");
+        assert_eq!(format_angle(20, AngleUnit::Radians), "5π/3");
+    }
+    #[test]
+    fn polar_rings_stay_near_the_screen() {
+        let screen = vec2(1000.0, 500.0);
+        let centered = compute_polar(&camera_at(0.0, 0.0, 100.0), screen, AngleUnit::Degrees);
+        assert_eq!(centered.lines.len(), SPOKE_COUNT as usize);
+        assert!(centered.labels.iter().any(|x| x.text == "90°"));
+        // Rings every 0.2 units out to the farthest corner, about 5.6 units away
+        assert_eq!(centered.curves.len(), 27);
+        // Far from the origin only short arcs of huge rings are kept
+        let shifted = compute_polar(&camera_at(1e6, 0.0, 100.0), screen, AngleUnit::Radians);
+        assert!(!shifted.curves.is_empty());
+        for curve in shifted.curves.iter() {
+            assert!(curve.points.len() < 200);
+            assert!(curve
+                .points
+                .iter()
+                .all(|x| x.x > -1.0 && x.x < screen.x + 1.0));
+        }
+    }
+}
@@ -0,0 +1,534 @@
+//! Undo and redo of edits to the entries, sliders and camera.
+use crate::camera::Camera;
+use crate::entries::EntryList;
+use crate::session::{CameraState, EntryState, SliderState};
+use crate::sliders::SliderList;
+use serde::{Deserialize, Serialize};
+
+/// Oldest steps are dropped past this many
+pub const HISTORY_LIMIT: usize = 100;
+// Changes to the same thing closer together than this become one step, e.g. a slider
+// drag, typing an expression or scrolling the camera
+const MERGE_SECONDS: f64 = 0.75;
+
+/// The part of the app state edits can change, what each frame is compared against
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Every entry with its style
+    pub entries: Vec<EntryState>,
+    /// Every slider with its bounds
+    pub sliders: Vec<SliderState>,
+    /// Position and zoom
+    pub camera: CameraState,
+}
+/// What a command changed, only commands on the same target are merged
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Target {
+    /// One entry, by id
+    Entry(usize),
+    /// Several entries, or entries added or removed
+    Entries,
+    /// One slider, by name
+    Slider(String),
+    /// Several sliders, or sliders added or removed
+    Sliders,
+    /// Panning or zooming
+    Camera,
+}
+/// One entry, slider or the camera before and after a step, `None` where it did not exist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Change {
+    /// An entry edited, added or removed
+    Entry {
+        /// See [`ExpressionEntry::id`](crate::entries::ExpressionEntry::id)
+        id: usize,
+        /// Where it was before a removal, or where it is after any other change
+        index: usize,
+        /// The entry undoing goes back to
+        before: Option<EntryState>,
+        /// The entry redoing goes forward to
+        after: Option<EntryState>,
+    },
+    /// A slider edited, added or removed
+    Slider {
+        /// The variable the slider sets
+        name: String,
+        /// The slider undoing goes back to
+        before: Option<SliderState>,
+        /// The slider redoing goes forward to
+        after: Option<SliderState>,
+    },
+    /// Panning or zooming
+    Camera {
+        /// The position undoing goes back to
+        before: CameraState,
+        /// The position redoing goes forward to
+        after: CameraState,
+    },
+}
+/// One undoable step, only what it changed is kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Command {
+    /// What the step changed
+    pub target: Target,
+    /// Every entry, slider and camera the step changed
+    pub changes: Vec<Change>,
+    // When it last changed, for merging
+    #[serde(skip)]
+    time: f64,
+}
+/// The undo and redo stacks, saved with the session when the setting is on.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct History {
+    /// Steps that can be undone, the latest last
+    pub undo: Vec<Command>,
+    /// Steps that were undone and can be redone, the latest undo last
+    pub redo: Vec<Command>,
+    // State at the end of the last frame, what the next change is compared against
+    #[serde(skip)]
+    current: Option<Snapshot>,
+}
+
+// Helper Functions
+fn get_changes(before: &Snapshot, after: &Snapshot) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for (index, old) in before.entries.iter().enumerate() {
+        let new = after.entries.iter().find(|x| x.id == old.id);
+        if new != Some(old) {
+            changes.push(Change::Entry {
+                id: old.id,
+                index,
+                before: Some(old.clone()),
+                after: new.cloned(),
+            });
+        }
+    }
+    for (index, new) in after.entries.iter().enumerate() {
+        if before.entries.iter().all(|x| x.id != new.id) {
+            changes.push(Change::Entry {
+                id: new.id,
+                index,
+                before: None,
+                after: Some(new.clone()),
+            });
+        }
+    }
+    for old in before.sliders.iter() {
+        let new = after.sliders.iter().find(|x| x.name == old.name);
+        if new != Some(old) {
+            changes.push(Change::Slider {
+                name: old.name.clone(),
+                before: Some(old.clone()),
+                after: new.cloned(),
+            });
+        }
+    }
+    for new in after.sliders.iter() {
+        if before.sliders.iter().all(|x| x.name != new.name) {
+            changes.push(Change::Slider {
+                name: new.name.clone(),
+                before: None,
+                after: Some(new.clone()),
+            });
+        }
+    }
+    if before.camera != after.camera {
+        changes.push(Change::Camera {
+            before: before.camera.clone(),
+            after: after.camera.clone(),
+        });
+    }
+    changes
+}
+// Entries are what a step is about when they changed at all, a new variable also adds a slider
+fn get_target(changes: &[Change]) -> Target {
+    let entries = changes
+        .iter()
+        .filter(|x| matches!(x, Change::Entry { .. }))
+        .collect::<Vec<&Change>>();
+    let sliders = changes
+        .iter()
+        .filter(|x| matches!(x, Change::Slider { .. }))
+        .collect::<Vec<&Change>>();
+    match (entries.as_slice(), sliders.as_slice()) {
+        ([], []) => Target::Camera,
+        ([], [x]) | ([x], _) if x.is_edit() => x.get_target(),
+        ([], _) => Target::Sliders,
+        _ => Target::Entries,
+    }
+}
+// Playing sliders move on their own, that is not an edit
+fn ignore_playback(snapshot: &Snapshot, previous: &Snapshot) -> Snapshot {
+    let mut snapshot = snapshot.clone();
+    for slider in snapshot.sliders.iter_mut().filter(|x| x.playing) {
+        let before = previous.sliders.iter().find(|x| x.name == slider.name);
+        if let Some(before) = before.filter(|x| x.playing) {
+            slider.value = before.value;
+        }
+    }
+    snapshot
+}
+//
+
+/// Brings the app to the `after` side of every change, the other parts are left alone so
+/// undoing a camera move keeps the plots
+pub fn apply_changes(
+    changes: &[Change],
+    camera: &mut Camera,
+    entries: &mut EntryList,
+    sliders: &mut SliderList,
+) -> Result<(), String> {
+    // Removals first, then the rest in list order so each index is right when it is used
+    let mut restored = Vec::new();
+    for change in changes.iter() {
+        match change {
+            Change::Entry {
+                id, after: None, ..
+            } => {
+                if let Some(x) = entries.entries.iter().position(|x| x.id == *id) {
+                    entries.remove(x);
+                }
+            }
+            Change::Entry {
+                index,
+                after: Some(after),
+                ..
+            } => restored.push((*index, after)),
+            _ => (),
+        }
+    }
+    restored.sort_by_key(|(index, _)| *index);
+    for (index, state) in restored {
+        // Edited entries stay where they are
+        let position = entries.entries.iter().position(|x| x.id == state.id);
+        if let Some(x) = position {
+            entries.entries.remove(x);
+        }
+        state.restore(entries);
+        let entry = entries.entries.pop().unwrap();
+        let index = position.unwrap_or(index).min(entries.entries.len());
+        entries.entries.insert(index, entry);
+    }
+    for change in changes.iter() {
+        match change {
+            Change::Slider { name, after, .. } => {
+                let position = sliders.sliders.iter().position(|x| &x.name == name);
+                match (position, after) {
+                    (Some(x), Some(after)) => sliders.sliders[x] = after.restore(),
+                    (Some(x), None) => {
+                        sliders.sliders.remove(x);
+                    }
+                    (None, Some(after)) => sliders.sliders.push(after.restore()),
+                    (None, None) => (),
+                }
+            }
+            Change::Camera { after, .. } => after.restore(camera)?,
+            Change::Entry { .. } => (),
+        }
+    }
+    sliders.sync(entries);
+    Ok(())
+}
+impl Snapshot {
+    /// The current state.
+    pub fn capture(camera: &Camera, entries: &EntryList, sliders: &SliderList) -> Snapshot {
+        Snapshot {
+            entries: entries.entries.iter().map(EntryState::capture).collect(),
+            sliders: sliders.sliders.iter().map(SliderState::capture).collect(),
+            camera: CameraState::capture(camera),
+        }
+    }
+}
+impl Change {
+    /// The entry, slider or camera the change is to.
+    pub fn get_target(&self) -> Target {
+        match self {
+            Change::Entry { id, .. } => Target::Entry(*id),
+            Change::Slider { name, .. } => Target::Slider(name.clone()),
+            Change::Camera { .. } => Target::Camera,
+        }
+    }
+    /// Whether it existed on both sides, i.e. was neither added nor removed.
+    pub fn is_edit(&self) -> bool {
+        match self {
+            Change::Entry { before, after, .. } => before.is_some() && after.is_some(),
+            Change::Slider { before, after, .. } => before.is_some() && after.is_some(),
+            Change::Camera { .. } => true,
+        }
+    }
+    /// The same change the other way round, what undoing it applies.
+    pub fn reversed(&self) -> Change {
+        let mut change = self.clone();
+        match &mut change {
+            Change::Entry { before, after, .. } => std::mem::swap(before, after),
+            Change::Slider { before, after, .. } => std::mem::swap(before, after),
+            Change::Camera { before, after } => std::mem::swap(before, after),
+        }
+        change
+    }
+    // A later change to the same target, the earlier `before` stays
+    fn merge(&mut self, later: Change) {
+        match (self, later) {
+            (
+                Change::Entry { index, after, .. },
+                Change::Entry {
+                    index: later_index,
+                    after: later_after,
+                    ..
+                },
+            ) => {
+                *index = later_index;
+                *after = later_after;
+            }
+            (Change::Slider { after, .. }, Change::Slider { after: later, .. }) => *after = later,
+            (Change::Camera { after, .. }, Change::Camera { after: later, .. }) => *after = later,
+            _ => (),
+        }
+    }
+}
+impl Command {
+    /// The step from `before` to `after`, empty when nothing changed
+    pub fn new(before: &Snapshot, after: &Snapshot) -> Command {
+        let changes = get_changes(before, after);
+        Command {
+            target: get_target(&changes),
+            changes,
+            time: 0.0,
+        }
+    }
+}
+impl History {
+    /// Empty stacks, the first recorded state is the starting point.
+    pub fn new() -> History {
+        History::default()
+    }
+    /// Whether there is a step to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+    /// Whether there is a step to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+    /// Called once a frame with the state after all edits, anything that differs from the
+    /// last frame becomes a new command or is merged into the latest one
+    pub fn record(&mut self, snapshot: Snapshot, time: f64) {
+        let previous = match self.current.take() {
+            Some(x) => x,
+            None => {
+                self.current = Some(snapshot);
+                return;
+            }
+        };
+        if ignore_playback(&snapshot, &previous) == previous {
+            self.current = Some(snapshot);
+            return;
+        }
+        let command = Command {
+            time,
+            ..Command::new(&previous, &snapshot)
+        };
+        self.redo.clear();
+        match self.undo.last_mut() {
+            Some(last) if (last.target == command.target && time - last.time < MERGE_SECONDS) => {
+                for change in command.changes {
+                    let target = change.get_target();
+                    match last.changes.iter_mut().find(|x| x.get_target() == target) {
+                        Some(x) => x.merge(change),
+                        None => last.changes.push(change),
+                    }
+                }
+                last.time = time;
+            }
+            _ => {
+                self.undo.push(command);
+                if self.undo.len() > HISTORY_LIMIT {
+                    self.undo.remove(0);
+                }
+            }
+        }
+        self.current = Some(snapshot);
+    }
+    /// The changes that go back a step, which the caller applies with [`apply_changes`]
+    pub fn undo(&mut self) -> Option<Vec<Change>> {
+        let command = self.undo.pop()?;
+        let changes = command.changes.iter().map(Change::reversed).collect();
+        self.redo.push(command);
+        // The app records every frame, so the next state is the restored one
+        self.current = None;
+        Some(changes)
+    }
+    /// The changes that go forward a step, which the caller applies with [`apply_changes`].
+    pub fn redo(&mut self) -> Option<Vec<Change>> {
+        let command = self.redo.pop()?;
+        let changes = command.changes.clone();
+        self.undo.push(command);
+        self.current = None;
+        Some(changes)
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::big_number::{BigNumber, BigVec2};
+    use crate::prelude::vec2;
+    use crate::session::{Session, SettingsState};
+    fn test_camera() -> Camera {
+        Camera {
+            position: BigVec2::new_d(0.0, 0.0),
+            number_distance: BigNumber::new_d(50.0),
+        }
+    }
+    struct App {
+        camera: Camera,
+        entries: EntryList,
+        sliders: SliderList,
+    }
+    impl App {
+        fn new() -> App {
+            App {
+                camera: test_camera(),
+                entries: EntryList::new(),
+                sliders: SliderList::new(),
+            }
+        }
+        fn snapshot(&mut self) -> Snapshot {
+            self.sliders.sync(&mut self.entries);
+            Snapshot::capture(&self.camera, &self.entries, &self.sliders)
+        }
+        fn restore(&mut self, changes: Option<Vec<Change>>) {
+            let changes = changes.unwrap();
+            apply_changes(
+                &changes,
+                &mut self.camera,
+                &mut self.entries,
+                &mut self.sliders,
+            )
+            .unwrap();
+        }
+    }
+    #[test]
+    fn edits_undo_and_redo() {
+        let mut app = App::new();
+        let mut history = History::new();
+        history.record(app.snapshot(), 0.0);
+        assert!(!history.can_undo());
+        app.entries.add("a*x");
+        history.record(app.snapshot(), 1.0);
+        // A slider drag over several frames is one step
+        for (frame, value) in [0.2, 0.4, 0.6].iter().enumerate() {
+            app.sliders.sliders[0].value = *value;
+            history.record(app.snapshot(), 2.0 + frame as f64 * 0.1);
+        }
+        app.camera.pan(vec2(10.0, 0.0));
+        history.record(app.snapshot(), 5.0);
+        assert_eq!(history.undo.len(), 3);
+        assert_eq!(history.undo[1].target, Target::Slider("a".to_string()));
+        assert_eq!(history.undo[2].target, Target::Camera);
+        app.restore(history.undo());
+        assert_eq!(app.camera.position.x.get_deserialized(), 0.0);
+        assert_eq!(app.entries.context.variables["a"], 0.6);
+        app.restore(history.undo());
+        assert_eq!(app.entries.context.variables["a"], 1.0);
+        app.restore(history.undo());
+        assert!(app.entries.entries.is_empty() && app.sliders.sliders.is_empty());
+        assert_eq!(history.undo(), None);
+        // Restoring records nothing
+        history.record(app.snapshot(), 6.0);
+        assert_eq!(history.redo.len(), 3);
+        app.restore(history.redo());
+        history.record(app.snapshot(), 6.5);
+        assert_eq!(app.entries.entries[0].expression, "a*x");
+        app.entries.entries[0].set_expression("2x");
+        history.record(app.snapshot(), 7.0);
+        assert!(!history.can_redo());
+        assert_eq!(history.undo[0].target, Target::Entries);
+        assert_eq!(history.undo[1].target, Target::Entry(1));
+    }
+    #[test]
+    fn steps_keep_only_what_changed() {
+        let mut app = App::new();
+        let mut history = History::new();
+        for expression in ["x", "2x", "3x"] {
+            app.entries.add(expression);
+        }
+        app.entries.entries[2].label = "f1".to_string();
+        history.record(app.snapshot(), 0.0);
+        // Entries sharing a label are still separate steps
+        app.entries.entries[0].set_expression("x+1");
+        history.record(app.snapshot(), 1.0);
+        app.entries.entries[2].set_expression("3x+1");
+        history.record(app.snapshot(), 1.1);
+        assert_eq!(history.undo.len(), 2);
+        assert_eq!(history.undo[1].target, Target::Entry(3));
+        assert_eq!(history.undo[1].changes.len(), 1);
+        app.entries.remove(1);
+        history.record(app.snapshot(), 5.0);
+        assert_eq!(history.undo[2].changes.len(), 1);
+        // Undoing puts the entry back where it was
+        app.restore(history.undo());
+        history.record(app.snapshot(), 6.0);
+        let ids = app
+            .entries
+            .entries
+            .iter()
+            .map(|x| x.id)
+            .collect::<Vec<usize>>();
+        assert_eq!(ids, [1, 2, 3]);
+        assert_eq!(app.entries.entries[1].expression, "2x");
+        app.restore(history.undo());
+        assert_eq!(app.entries.entries[2].expression, "3x");
+        assert_eq!(app.entries.entries[0].expression, "x+1");
+        app.restore(history.redo());
+        app.restore(history.redo());
+        let expressions = app.entries.entries.iter().map(|x| x.expression.as_str());
+        assert_eq!(expressions.collect::<Vec<&str>>(), ["x+1", "3x+1"]);
+    }
+    #[test]
+    fn playback_and_limit() {
+        let mut app = App::new();
+        let mut history = History::new();
+        app.entries.add("a*x");
+        history.record(app.snapshot(), 0.0);
+        app.sliders.sliders[0].playing = true;
+        history.record(app.snapshot(), 1.0);
+        for frame in 0..10 {
+            app.sliders.advance();
+            history.record(app.snapshot(), 10.0 + frame as f64);
+        }
+        assert_eq!(history.undo.len(), 1);
+        for frame in 0..HISTORY_LIMIT + 5 {
+            app.camera.pan(vec2(1.0, 0.0));
+            history.record(app.snapshot(), 100.0 + frame as f64);
+        }
+        assert_eq!(history.undo.len(), HISTORY_LIMIT);
+    }
+    #[test]
+    fn saved_in_session() {
+        let mut app = App::new();
+        let mut history = History::new();
+        history.record(app.snapshot(), 0.0);
+        app.entries.add("a*x");
+        history.record(app.snapshot(), 1.0);
+        app.camera.pan(vec2(5.0, 5.0));
+        history.record(app.snapshot(), 2.0);
+        history.undo();
+        let settings = SettingsState {
+            position: [0.0, 0.0],
+            resolution: 1.0,
+            grid: "Cartesian".to_string(),
+            save_history: true,
+        };
+        let mut session = Session::capture(settings, &app.camera, &app.entries, &app.sliders);
+        session.history = Some(history.clone());
+        let loaded = Session::from_toml(&session.to_toml().unwrap()).unwrap();
+        let mut loaded = loaded.history.unwrap();
+        assert_eq!((loaded.undo.len(), loaded.redo.len()), (1, 1));
+        // Merge times are not saved
+        assert_eq!(loaded.undo[0].changes, history.undo[0].changes);
+        // The entry and the slider it added, nothing else
+        assert_eq!(loaded.undo[0].changes.len(), 2);
+        let changes = loaded.undo().unwrap();
+        assert!(matches!(changes[0], Change::Entry { after: None, .. }));
+    }
+}
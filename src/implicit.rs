@@ -0,0 +1,354 @@
+use crate::camera::Camera;
+use crate::interval::Interval;
+use crate::plotter::Polyline;
+use crate::prelude::*;
+use std::collections::{HashMap, VecDeque};
+
+// The screen is first covered with cells this many pixels wide
+const INITIAL_CELL_SIZE: f32 = 64.0;
+// Cells are split until they are this small, then the curve is traced through them
+const MIN_CELL_SIZE: f32 = 2.0;
+pub const DEFAULT_CELL_BUDGET: usize = 40000;
+// Endpoints closer than 1/256 of a pixel are the same point when joining segments
+const JOIN_PRECISION: f32 = 256.0;
+// Pixels to either side of a boundary that are checked to see if it borders the region
+const BORDER_OFFSET: f32 = 2.0;
+
+// Helper Functions
+// World coordinates of a screen pixel, set up once per frame instead of per sample
+fn world_mapping(camera: &Camera, screen: Vec2) -> impl Fn(Vec2) -> (f64, f64) {
+    let pixels_per_unit = camera.get_pixels_per_unit();
+    let center = (
+        camera.position.x.get_deserialized(),
+        camera.position.y.get_deserialized(),
+    );
+    move |pixel: Vec2| {
+        (
+            center.0 + (pixel.x - screen.x / 2.0) as f64 / pixels_per_unit,
+            center.1 + (screen.y / 2.0 - pixel.y) as f64 / pixels_per_unit,
+        )
+    }
+}
+fn initial_cells(screen: Vec2) -> Vec<(Vec2, f32)> {
+    let (columns, rows) = (
+        (screen.x / INITIAL_CELL_SIZE).ceil() as i32,
+        (screen.y / INITIAL_CELL_SIZE).ceil() as i32,
+    );
+    (0..columns)
+        .flat_map(|column| (0..rows).map(move |row| (column, row)))
+        .map(|(column, row)| {
+            (
+                vec2(column as f32, row as f32) * INITIAL_CELL_SIZE,
+                INITIAL_CELL_SIZE,
+            )
+        })
+        .collect()
+}
+fn split_cell(corner: Vec2, size: f32) -> [(Vec2, f32); 4] {
+    let half = size / 2.0;
+    [
+        (corner, half),
+        (corner + vec2(half, 0.0), half),
+        (corner + vec2(0.0, half), half),
+        (corner + vec2(half, half), half),
+    ]
+}
+// Point on the edge from `a` to `b` where the linear interpolation of the values crosses zero.
+// The corners are ordered first so both cells sharing an edge compute the exact same point
+fn edge_point(a: (Vec2, f64), b: (Vec2, f64)) -> Vec2 {
+    let ((a, value_a), (b, value_b)) = match (a.0.x, a.0.y) < (b.0.x, b.0.y) {
+        true => (a, b),
+        false => (b, a),
+    };
+    let t = (value_a / (value_a - value_b)) as f32;
+    a + (b - a) * t.clamp(0.0, 1.0)
+}
+// Marching squares inside one cell, corners go clockwise from the top left
+fn march<F: Fn(Vec2) -> f64>(corner: Vec2, size: f32, function: &F) -> Vec<(Vec2, Vec2)> {
+    let points = [
+        corner,
+        corner + vec2(size, 0.0),
+        corner + vec2(size, size),
+        corner + vec2(0.0, size),
+    ];
+    let values = points.map(function);
+    if values.iter().any(|x| !x.is_finite()) {
+        return Vec::new();
+    }
+    let case = values
+        .iter()
+        .enumerate()
+        .fold(0, |case, (index, value)| match *value > 0.0 {
+            true => case | (1 << index),
+            false => case,
+        });
+    // Edge n joins corner n to corner n + 1
+    let edge = |n: usize| {
+        edge_point(
+            (points[n], values[n]),
+            (points[(n + 1) % 4], values[(n + 1) % 4]),
+        )
+    };
+    let pairs: Vec<(usize, usize)> = match case {
+        0 | 15 => vec![],
+        1 | 14 => vec![(3, 0)],
+        2 | 13 => vec![(0, 1)],
+        3 | 12 => vec![(3, 1)],
+        4 | 11 => vec![(1, 2)],
+        6 | 9 => vec![(0, 2)],
+        7 | 8 => vec![(2, 3)],
+        // Saddles, the sign in the middle of the cell decides which corners are connected
+        5 | 10 => {
+            let center = function(corner + vec2(size, size) / 2.0) > 0.0;
+            match center == (case == 5) {
+                true => vec![(0, 1), (2, 3)],
+                false => vec![(3, 0), (1, 2)],
+            }
+        }
+        _ => unreachable!(),
+    };
+    pairs.into_iter().map(|(a, b)| (edge(a), edge(b))).collect()
+}
+// Chains segments that share endpoints into polylines so dash patterns run continuously
+pub fn join_segments(segments: Vec<(Vec2, Vec2)>) -> Vec<Polyline> {
+    let key = |x: Vec2| {
+        (
+            (x.x * JOIN_PRECISION).round() as i64,
+            (x.y * JOIN_PRECISION).round() as i64,
+        )
+    };
+    let mut ends: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+    for (index, (a, b)) in segments.iter().enumerate() {
+        ends.entry(key(*a)).or_default().push(index);
+        ends.entry(key(*b)).or_default().push(index);
+    }
+    let mut used = vec![false; segments.len()];
+    // Unused segment touching `point`, returned with its other endpoint
+    let take = |point: Vec2, used: &mut Vec<bool>| {
+        let index = *ends.get(&key(point))?.iter().find(|x| !used[**x])?;
+        used[index] = true;
+        let (a, b) = segments[index];
+        match key(a) == key(point) {
+            true => Some(b),
+            false => Some(a),
+        }
+    };
+    let mut polylines = Vec::new();
+    for index in 0..segments.len() {
+        if used[index] {
+            continue;
+        }
+        used[index] = true;
+        let (a, b) = segments[index];
+        let mut polyline = VecDeque::from([a, b]);
+        while let Some(next) = take(*polyline.back().unwrap(), &mut used) {
+            polyline.push_back(next);
+        }
+        while let Some(next) = take(*polyline.front().unwrap(), &mut used) {
+            polyline.push_front(next);
+        }
+        polylines.push(Vec::from(polyline));
+    }
+    polylines
+}
+//
+
+// Zero set of F(x, y) on screen. Cells whose `bounds` rule out a root are dropped, the rest
+// are split as a quadtree until they are a couple of pixels wide or `budget` cells have been
+// checked. Cells are laid out in screen space, so the detail follows the camera at any zoom
+pub fn trace_implicit<F, B>(
+    camera: &Camera,
+    screen: Vec2,
+    function: F,
+    bounds: B,
+    budget: usize,
+) -> Vec<Polyline>
+where
+    F: Fn(f64, f64) -> f64,
+    B: Fn(Interval, Interval) -> Interval,
+{
+    let to_world = world_mapping(camera, screen);
+    let value_at = |pixel: Vec2| {
+        let (x, y) = to_world(pixel);
+        function(x, y)
+    };
+    let mut cells = initial_cells(screen);
+    let mut checked = 0;
+    let mut segments = Vec::new();
+    while let Some((corner, size)) = cells.pop() {
+        let (left, top) = to_world(corner);
+        let (right, bottom) = to_world(corner + vec2(size, size));
+        checked += 1;
+        if !bounds(Interval::new(left, right), Interval::new(bottom, top)).contains(0.0) {
+            continue;
+        }
+        if size > MIN_CELL_SIZE && checked + cells.len() < budget {
+            cells.extend(split_cell(corner, size));
+            continue;
+        }
+        segments.extend(march(corner, size, &value_at));
+    }
+    join_segments(segments)
+}
+// Screen rectangles where the condition holds. Cells that `decide` settles are filled or
+// dropped whole, the rest are split down to a couple of pixels and sampled in the middle
+pub fn shade_region<C, D>(
+    camera: &Camera,
+    screen: Vec2,
+    condition: C,
+    decide: D,
+    budget: usize,
+) -> Vec<Rect>
+where
+    C: Fn(f64, f64) -> bool,
+    D: Fn(Interval, Interval) -> Option<bool>,
+{
+    let to_world = world_mapping(camera, screen);
+    let mut cells = initial_cells(screen);
+    let mut checked = 0;
+    let mut rectangles = Vec::new();
+    while let Some((corner, size)) = cells.pop() {
+        let (left, top) = to_world(corner);
+        let (right, bottom) = to_world(corner + vec2(size, size));
+        checked += 1;
+        let inside = match decide(Interval::new(left, right), Interval::new(bottom, top)) {
+            Some(x) => x,
+            None if (size > MIN_CELL_SIZE && checked + cells.len() < budget) => {
+                cells.extend(split_cell(corner, size));
+                continue;
+            }
+            None => {
+                let (x, y) = to_world(corner + vec2(size, size) / 2.0);
+                condition(x, y)
+            }
+        };
+        if inside {
+            rectangles.push(Rect::new(corner.x, corner.y, size, size));
+        }
+    }
+    rectangles
+}
+// Pieces of the boundary polylines that actually separate the region from the outside,
+// so the parts of a circle cut away by `and` are not drawn
+pub fn region_border<C: Fn(f64, f64) -> bool>(
+    camera: &Camera,
+    screen: Vec2,
+    polylines: Vec<Polyline>,
+    condition: C,
+) -> Vec<Polyline> {
+    let to_world = world_mapping(camera, screen);
+    let is_border = |point: Vec2| {
+        let sides = [
+            vec2(BORDER_OFFSET, 0.0),
+            vec2(-BORDER_OFFSET, 0.0),
+            vec2(0.0, BORDER_OFFSET),
+            vec2(0.0, -BORDER_OFFSET),
+        ]
+        .map(|offset| {
+            let (x, y) = to_world(point + offset);
+            condition(x, y)
+        });
+        sides.contains(&true) && sides.contains(&false)
+    };
+    let mut pieces = Vec::new();
+    for polyline in polylines.into_iter() {
+        let mut current: Polyline = Vec::new();
+        for point in polyline.into_iter() {
+            match is_border(point) {
+                true => current.push(point),
+                false => {
+                    if current.len() > 1 {
+                        pieces.push(std::mem::take(&mut current));
+                    }
+                    current.clear();
+                }
+            }
+        }
+        if current.len() > 1 {
+            pieces.push(current);
+        }
+    }
+    pieces
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::big_number::{BigNumber, BigVec2};
+    fn circle_at(zoom: f64) -> Vec<Polyline> {
+        let camera = Camera {
+            position: BigVec2::new_d(0.0, 0.0),
+            number_distance: BigNumber::new_d(zoom),
+        };
+        // x^2 + y^2 = (200 / zoom)^2 is always a circle of 200 pixels
+        let radius = 200.0 / zoom;
+        trace_implicit(
+            &camera,
+            vec2(1000.0, 800.0),
+            |x, y| x * x + y * y - radius * radius,
+            |x, y| x * x + y * y - Interval::point(radius * radius),
+            DEFAULT_CELL_BUDGET,
+        )
+    }
+    #[test]
+    fn traces_a_circle_at_any_zoom() {
+        for zoom in [100.0, 1e-20, 1e20] {
+            let polylines = circle_at(zoom);
+            let center = vec2(500.0, 400.0);
+            let points = polylines.iter().flatten().collect::<Vec<&Vec2>>();
+            assert!(points.len() > 100);
+            assert!(points
+                .iter()
+                .all(|x| (x.distance(center) - 200.0).abs() < 0.5));
+            // One closed loop, give or take breaks where neighbouring cells differ in size
+            assert!(polylines.len() < 20);
+        }
+    }
+    #[test]
+    fn shades_half_a_disc() {
+        let camera = Camera {
+            position: BigVec2::new_d(0.0, 0.0),
+            number_distance: BigNumber::new_d(100.0),
+        };
+        let screen = vec2(1000.0, 800.0);
+        // x^2 + y^2 <= 4 and y > 0
+        let condition = |x: f64, y: f64| x * x + y * y <= 4.0 && y > 0.0;
+        let decide = |x: Interval, y: Interval| {
+            let disc = x * x + y * y - Interval::point(4.0);
+            match (
+                disc.high <= 0.0 && y.low > 0.0,
+                disc.low > 0.0 || y.high <= 0.0,
+            ) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            }
+        };
+        let rectangles = shade_region(&camera, screen, condition, decide, DEFAULT_CELL_BUDGET);
+        let area = rectangles.iter().map(|x| x.w * x.h).sum::<f32>();
+        // Half of a disc with a radius of 200 pixels
+        let expected = std::f32::consts::PI * 200.0 * 200.0 / 2.0;
+        assert!((area - expected).abs() < expected * 0.02);
+        assert!(rectangles.iter().all(|x| x.y + x.h <= 400.0 + 1e-3));
+        // Only the upper half of the circle and the diameter border the region
+        let circle = trace_implicit(
+            &camera,
+            screen,
+            |x, y| x * x + y * y - 4.0,
+            |x, y| x * x + y * y - Interval::point(4.0),
+            DEFAULT_CELL_BUDGET,
+        );
+        let border = region_border(&camera, screen, circle, condition);
+        assert!(!border.is_empty());
+        assert!(border
+            .iter()
+            .flatten()
+            .all(|x| x.y <= 400.0 + BORDER_OFFSET));
+    }
+    #[test]
+    fn joins_shared_endpoints() {
+        let (a, b, c) = (vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0));
+        let polylines = join_segments(vec![(b, c), (a, b), (vec2(5.0, 5.0), vec2(6.0, 6.0))]);
+        assert_eq!(polylines.len(), 2);
+        assert_eq!(polylines[0], vec![a, b, c]);
+    }
+}
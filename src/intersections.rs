@@ -0,0 +1,305 @@
+//! Points where two plotted curves cross, found numerically and cached per pair.
+#[cfg(feature = "ui")]
+use crate::big_number::BigVec2;
+use crate::camera::Camera;
+use crate::entries::{EntryKind, EntryList};
+use crate::prelude::*;
+#[cfg(feature = "ui")]
+use macroquad::miniquad::window::clipboard_set;
+#[cfg(feature = "ui")]
+use macroquad::prelude::{
+    draw_circle, draw_circle_lines, draw_text, get_time, is_mouse_button_pressed, mouse_position,
+    MouseButton,
+};
+#[cfg(feature = "ui")]
+use macroquad::ui::root_ui;
+use std::collections::HashMap;
+
+// Pixels between two samples of `f - g` when looking for sign changes
+const SAMPLE_SPACING: f32 = 2.0;
+const ROOT_TOLERANCE: f64 = 1e-12;
+const MAX_ITERATIONS: usize = 100;
+// A refined root is only kept when the curves really meet there, which rules out poles
+const MATCH_TOLERANCE: f64 = 1e-6;
+#[cfg(feature = "ui")]
+const MARKER_RADIUS: f32 = 5.0;
+#[cfg(feature = "ui")]
+const COPIED_SECONDS: f64 = 1.5;
+#[cfg(feature = "ui")]
+const LABEL_FONT_SIZE: f32 = 18.0;
+
+/// A point where two function entries cross.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Intersection {
+    /// The ids of the two entries
+    pub entry_ids: (usize, usize),
+    /// Horizontal coordinate
+    pub x: f64,
+    /// Vertical coordinate
+    pub y: f64,
+}
+struct PairResult {
+    revisions: (usize, usize),
+    range: (f64, f64),
+    points: Vec<Intersection>,
+}
+/// The intersections of every pair of visible functions, solved again only when needed.
+#[derive(Default)]
+pub struct IntersectionCache {
+    pairs: HashMap<(usize, usize), PairResult>,
+    #[cfg(feature = "ui")]
+    copied: Option<(String, Vec2, f64)>,
+}
+
+// Helper Functions
+// Brent's method: inverse quadratic interpolation and secant steps, falling back to
+// bisection whenever they would leave the bracket or converge too slowly
+fn brent<F: Fn(f64) -> f64>(f: F, a: f64, b: f64, tolerance: f64) -> Option<f64> {
+    let (mut a, mut b) = (a, b);
+    let (mut fa, mut fb) = (f(a), f(b));
+    if !fa.is_finite() || !fb.is_finite() || fa * fb > 0.0 {
+        return None;
+    }
+    let (mut c, mut fc) = (b, fb);
+    let (mut d, mut e) = (0.0, 0.0);
+    for _ in 0..MAX_ITERATIONS {
+        if (fb > 0.0 && fc > 0.0) || (fb < 0.0 && fc < 0.0) {
+            c = a;
+            fc = fa;
+            d = b - a;
+            e = d;
+        }
+        if fc.abs() < fb.abs() {
+            a = b;
+            b = c;
+            c = a;
+            fa = fb;
+            fb = fc;
+            fc = fa;
+        }
+        let tolerance = 2.0 * f64::EPSILON * b.abs() + 0.5 * tolerance;
+        let middle = 0.5 * (c - b);
+        if middle.abs() <= tolerance || fb == 0.0 {
+            return Some(b);
+        }
+        if e.abs() >= tolerance && fa.abs() > fb.abs() {
+            let s = fb / fa;
+            let (mut p, mut q) = match a == c {
+                true => (2.0 * middle * s, 1.0 - s),
+                false => {
+                    let q = fa / fc;
+                    let r = fb / fc;
+                    (
+                        s * (2.0 * middle * q * (q - r) - (b - a) * (r - 1.0)),
+                        (q - 1.0) * (r - 1.0) * (s - 1.0),
+                    )
+                }
+            };
+            if p > 0.0 {
+                q = -q;
+            }
+            p = p.abs();
+            let limit = (3.0 * middle * q - (tolerance * q).abs()).min((e * q).abs());
+            if 2.0 * p < limit {
+                e = d;
+                d = p / q;
+            } else {
+                d = middle;
+                e = d;
+            }
+        } else {
+            d = middle;
+            e = d;
+        }
+        a = b;
+        fa = fb;
+        b += match d.abs() > tolerance {
+            true => d,
+            false => tolerance.copysign(middle),
+        };
+        fb = f(b);
+        if !fb.is_finite() {
+            return None;
+        }
+    }
+    None
+}
+/// Every x in [x_min, x_max] where f and g cross, in increasing order
+pub fn find_intersections<F, G>(f: F, g: G, x_min: f64, x_max: f64, samples: usize) -> Vec<f64>
+where
+    F: Fn(f64) -> f64,
+    G: Fn(f64) -> f64,
+{
+    let difference = |x: f64| f(x) - g(x);
+    let step = (x_max - x_min) / samples.max(1) as f64;
+    let grid = (0..=samples)
+        .map(|i| x_min + step * i as f64)
+        .map(|x| (x, difference(x)))
+        .collect::<Vec<(f64, f64)>>();
+    let mut roots: Vec<f64> = Vec::new();
+    for pair in grid.windows(2) {
+        let ((x0, d0), (x1, d1)) = (pair[0], pair[1]);
+        if !d0.is_finite() || !d1.is_finite() {
+            continue;
+        }
+        let root = match (d0 == 0.0, d0 * d1 < 0.0) {
+            (true, _) => Some(x0),
+            (false, true) => brent(difference, x0, x1, ROOT_TOLERANCE),
+            (false, false) => None,
+        };
+        let root = match root {
+            Some(x) => x,
+            None => continue,
+        };
+        let (fx, gx) = (f(root), g(root));
+        if (fx - gx).abs() > MATCH_TOLERANCE * (1.0 + fx.abs()) {
+            continue;
+        }
+        // A root sitting exactly on a sample is found by both neighbouring brackets
+        if roots.last().is_some_and(|x| (root - x).abs() < step / 2.0) {
+            continue;
+        }
+        roots.push(root);
+    }
+    roots
+}
+//
+
+impl IntersectionCache {
+    /// An empty cache.
+    pub fn new() -> IntersectionCache {
+        IntersectionCache::default()
+    }
+    /// Every intersection found in the last update.
+    pub fn get_points(&self) -> Vec<&Intersection> {
+        self.pairs.values().flat_map(|x| x.points.iter()).collect()
+    }
+    /// Only pairs whose entries were re-parsed or whose viewport moved are solved again
+    pub fn update(&mut self, entries: &EntryList, camera: &Camera, screen: Vec2) {
+        let visible = entries
+            .entries
+            .iter()
+            .filter(|x| x.style.visible && x.ast.is_ok() && x.get_kind() == EntryKind::Function)
+            .collect::<Vec<_>>();
+        let range = (
+            camera
+                .screen_to_world(vec2(0.0, 0.0), screen)
+                .x
+                .get_deserialized(),
+            camera.screen_to_world(screen, screen).x.get_deserialized(),
+        );
+        let samples = (screen.x / SAMPLE_SPACING).ceil() as usize;
+        let mut pairs = HashMap::new();
+        for (index, a) in visible.iter().enumerate() {
+            for b in visible.iter().skip(index + 1) {
+                let key = (a.id, b.id);
+                let revisions = (a.revision, b.revision);
+                let cached = self.pairs.remove(&key);
+                let result = match cached {
+                    Some(x) if (x.revisions == revisions && x.range == range) => x,
+                    _ => PairResult {
+                        revisions,
+                        range,
+                        points: find_intersections(
+                            |x| a.evaluate(x, &entries.context),
+                            |x| b.evaluate(x, &entries.context),
+                            range.0,
+                            range.1,
+                            samples,
+                        )
+                        .into_iter()
+                        .map(|x| Intersection {
+                            entry_ids: key,
+                            x,
+                            y: a.evaluate(x, &entries.context),
+                        })
+                        .collect(),
+                    },
+                };
+                pairs.insert(key, result);
+            }
+        }
+        self.pairs = pairs;
+    }
+}
+#[cfg(feature = "ui")]
+impl IntersectionCache {
+    /// Clicking a marker copies its coordinates to the clipboard
+    pub fn handle_click(&mut self, camera: &Camera, screen: Vec2) {
+        let mouse = Vec2::from(mouse_position());
+        if !is_mouse_button_pressed(MouseButton::Left) || root_ui().is_mouse_over(mouse) {
+            return;
+        }
+        let clicked = self.get_points().into_iter().find(|x| {
+            let position = camera.world_to_screen(&BigVec2::new_d(x.x, x.y), screen);
+            position.distance(mouse) <= MARKER_RADIUS * 2.0
+        });
+        if let Some(point) = clicked {
+            let text = format!("({}, {})", point.x, point.y);
+            clipboard_set(&text);
+            self.copied = Some((text, mouse, get_time()));
+        }
+    }
+    /// Draws a marker on every intersection and the note after a copy.
+    pub fn draw(&self, camera: &Camera, screen: Vec2) {
+        for point in self.get_points() {
+            let position = camera.world_to_screen(&BigVec2::new_d(point.x, point.y), screen);
+            draw_circle(position.x, position.y, MARKER_RADIUS, WHITE);
+            draw_circle_lines(position.x, position.y, MARKER_RADIUS, 2.0, DARKGRAY);
+        }
+        if let Some((ref text, position, time)) = self.copied {
+            if get_time() - time < COPIED_SECONDS {
+                draw_text(
+                    &format!("Copied {}", text),
+                    position.x + MARKER_RADIUS * 2.0,
+                    position.y - MARKER_RADIUS * 2.0,
+                    LABEL_FONT_SIZE,
+                    DARKGRAY,
+                );
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::big_number::{BigNumber, BigVec2};
+    #[test]
+    fn brent_converges() {
+        let root = brent(|x| x.cos() - x, 0.0, 1.0, 1e-14).unwrap();
+        assert!((root - 0.7390851332151607).abs() < 1e-12);
+        assert_eq!(brent(|x| x * x + 1.0, -1.0, 1.0, 1e-14), None);
+    }
+    #[test]
+    fn finds_every_crossing() {
+        let roots = find_intersections(|x| x * x, |x| x + 2.0, -5.0, 5.0, 500);
+        assert_eq!(roots.len(), 2);
+        assert!((roots[0] + 1.0).abs() < 1e-10);
+        assert!((roots[1] - 2.0).abs() < 1e-10);
+        // Roots that land on a sample are not reported twice
+        assert_eq!(find_intersections(|x| x, |_| 0.0, -1.0, 1.0, 10), vec![0.0]);
+    }
+    #[test]
+    fn skips_poles() {
+        let roots = find_intersections(|x| x.tan(), |_| 0.0, -4.0, 4.0, 800);
+        assert_eq!(roots.len(), 3);
+        assert!(roots.iter().all(|x| x.tan().abs() < 1e-9));
+    }
+    #[test]
+    fn cache_follows_entry_changes() {
+        let mut entries = EntryList::new();
+        entries.add("x^2");
+        entries.add("1");
+        let camera = Camera {
+            position: BigVec2::new_d(0.0, 0.0),
+            number_distance: BigNumber::new_d(100.0),
+        };
+        let screen = vec2(1000.0, 800.0);
+        let mut cache = IntersectionCache::new();
+        cache.update(&entries, &camera, screen);
+        assert_eq!(cache.get_points().len(), 2);
+        entries.entries[1].set_expression("-1");
+        cache.update(&entries, &camera, screen);
+        assert_eq!(cache.get_points().len(), 0);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::algebra_parser::{evaluator::Context, Comparison, Node, Operator};
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+use std::ops;
+
+// Closed range of values an expression can take over a box of inputs. Every operation
+// widens rather than narrows, so "0 is not inside" is a proof that the expression has no root
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub low: f64,
+    pub high: f64,
+}
+
+// Helper Functions
+// Smallest interval holding every value, anything undefined gives up and returns everything
+fn hull(values: &[f64]) -> Interval {
+    if values.iter().any(|x| x.is_nan()) {
+        return Interval::ENTIRE;
+    }
+    Interval {
+        low: values.iter().cloned().fold(f64::INFINITY, f64::min),
+        high: values.iter().cloned().fold(f64::NEG_INFINITY, f64::max),
+    }
+}
+// Whether some `offset + k * period` lies inside the interval
+fn hits(interval: Interval, offset: f64, period: f64) -> bool {
+    ((interval.low - offset) / period).ceil() <= ((interval.high - offset) / period).floor()
+}
+//
+
+impl Interval {
+    pub const ENTIRE: Interval = Interval {
+        low: f64::NEG_INFINITY,
+        high: f64::INFINITY,
+    };
+    pub fn new(a: f64, b: f64) -> Interval {
+        hull(&[a, b])
+    }
+    pub fn point(x: f64) -> Interval {
+        Interval::new(x, x)
+    }
+    pub fn contains(&self, x: f64) -> bool {
+        self.low <= x && x <= self.high
+    }
+    pub fn width(&self) -> f64 {
+        self.high - self.low
+    }
+    fn monotonic(&self, function: fn(f64) -> f64) -> Interval {
+        hull(&[function(self.low), function(self.high)])
+    }
+    pub fn powf(self, exponent: Interval) -> Interval {
+        if exponent.low == exponent.high && exponent.low.fract() == 0.0 {
+            let n = exponent.low as i32;
+            let ends = [self.low.powi(n), self.high.powi(n)];
+            return match (n % 2 == 0, n >= 0) {
+                // Even powers bottom out at 0, negative even powers blow up there
+                (true, true) if (self.contains(0.0)) => hull(&[0.0, ends[0], ends[1]]),
+                (true, false) if (self.contains(0.0)) => Interval::new(0.0, f64::INFINITY),
+                (false, false) if (self.contains(0.0)) => Interval::ENTIRE,
+                _ => hull(&ends),
+            };
+        }
+        if self.low < 0.0 {
+            return Interval::ENTIRE;
+        }
+        hull(&[
+            self.low.powf(exponent.low),
+            self.low.powf(exponent.high),
+            self.high.powf(exponent.low),
+            self.high.powf(exponent.high),
+        ])
+    }
+    fn sin(self) -> Interval {
+        if self.width() >= TAU {
+            return Interval::new(-1.0, 1.0);
+        }
+        let mut values = vec![self.low.sin(), self.high.sin()];
+        if hits(self, FRAC_PI_2, TAU) {
+            values.push(1.0);
+        }
+        if hits(self, -FRAC_PI_2, TAU) {
+            values.push(-1.0);
+        }
+        hull(&values)
+    }
+    fn tan(self) -> Interval {
+        match hits(self, FRAC_PI_2, PI) {
+            true => Interval::ENTIRE,
+            false => self.monotonic(f64::tan),
+        }
+    }
+    fn call(self, name: &str) -> Interval {
+        match name {
+            "sin" => self.sin(),
+            "cos" => (self + Interval::point(FRAC_PI_2)).sin(),
+            "tan" => self.tan(),
+            "cot" => (Interval::point(FRAC_PI_2) - self).tan(),
+            "sec" => Interval::point(1.0) / (self + Interval::point(FRAC_PI_2)).sin(),
+            "csc" => Interval::point(1.0) / self.sin(),
+            "asin" | "arcsin" => self.monotonic(f64::asin),
+            "acos" | "arccos" => self.monotonic(f64::acos),
+            "atan" | "arctan" => self.monotonic(f64::atan),
+            "sinh" => self.monotonic(f64::sinh),
+            "tanh" => self.monotonic(f64::tanh),
+            "cosh" | "abs" => {
+                let function = match name {
+                    "cosh" => f64::cosh,
+                    _ => f64::abs,
+                };
+                match self.contains(0.0) {
+                    true => hull(&[function(0.0), function(self.low), function(self.high)]),
+                    false => self.monotonic(function),
+                }
+            }
+            // Clamp to the domain so a box that only partly leaves it still has a range
+            "sqrt" => Interval::new(self.low.max(0.0), self.high.max(0.0)).monotonic(f64::sqrt),
+            "ln" => Interval::new(self.low.max(0.0), self.high.max(0.0)).monotonic(f64::ln),
+            "log" => Interval::new(self.low.max(0.0), self.high.max(0.0)).monotonic(f64::log10),
+            "exp" => self.monotonic(f64::exp),
+            "floor" => self.monotonic(f64::floor),
+            "ceil" => self.monotonic(f64::ceil),
+            "round" => self.monotonic(f64::round),
+            "sign" => self.monotonic(f64::signum),
+            _ => Interval::ENTIRE,
+        }
+    }
+}
+impl ops::Add for Interval {
+    type Output = Interval;
+    fn add(self, other: Interval) -> Interval {
+        hull(&[self.low + other.low, self.high + other.high])
+    }
+}
+impl ops::Sub for Interval {
+    type Output = Interval;
+    fn sub(self, other: Interval) -> Interval {
+        hull(&[self.low - other.high, self.high - other.low])
+    }
+}
+impl ops::Mul for Interval {
+    type Output = Interval;
+    fn mul(self, other: Interval) -> Interval {
+        hull(&[
+            self.low * other.low,
+            self.low * other.high,
+            self.high * other.low,
+            self.high * other.high,
+        ])
+    }
+}
+impl ops::Div for Interval {
+    type Output = Interval;
+    fn div(self, other: Interval) -> Interval {
+        match other.contains(0.0) {
+            true => Interval::ENTIRE,
+            false => self * hull(&[1.0 / other.low, 1.0 / other.high]),
+        }
+    }
+}
+impl ops::Neg for Interval {
+    type Output = Interval;
+    fn neg(self) -> Interval {
+        Interval::new(-self.high, -self.low)
+    }
+}
+// Range of `node` while each variable in `variables` roams its interval, the rest of the
+// variables come from `context`
+pub fn evaluate(node: &Node, variables: &HashMap<&str, Interval>, context: &Context) -> Interval {
+    match node {
+        Node::Number(x) => Interval::point(*x),
+        Node::Variable(name) => match variables.get(name.as_str()) {
+            Some(x) => *x,
+            None => match (context.variables.get(name), name.as_str()) {
+                (Some(x), _) => Interval::point(*x),
+                (None, "pi" | "π") => Interval::point(PI),
+                (None, "e") => Interval::point(std::f64::consts::E),
+                (None, _) => Interval::ENTIRE,
+            },
+        },
+        Node::Negate(x) => -evaluate(x, variables, context),
+        Node::Operation(operator, a, b) => {
+            let (a, b) = (
+                evaluate(a, variables, context),
+                evaluate(b, variables, context),
+            );
+            match operator {
+                Operator::Add => a + b,
+                Operator::Sub => a - b,
+                Operator::Mul => a * b,
+                Operator::Div => a / b,
+                Operator::Exponent => a.powf(b),
+            }
+        }
+        Node::Call(name, arguments) => match (arguments.as_slice(), context.functions.get(name)) {
+            ([x], None) => evaluate(x, variables, context).call(name),
+            // User defined functions are not expanded, so nothing can be ruled out
+            _ => Interval::ENTIRE,
+        },
+        Node::Tuple(_) | Node::List(_) => Interval::ENTIRE,
+        Node::Compare(..) | Node::And(..) | Node::Or(..) => {
+            match decide(node, variables, context) {
+                Some(true) => Interval::point(1.0),
+                Some(false) => Interval::point(0.0),
+                None => Interval::new(0.0, 1.0),
+            }
+        }
+        // Every piece that may apply somewhere in the box contributes its range
+        Node::Piecewise(pieces) => {
+            let mut ends = Vec::new();
+            for (condition, value) in pieces.iter() {
+                let applies = decide(condition, variables, context);
+                if applies == Some(false) {
+                    continue;
+                }
+                let range = evaluate(value, variables, context);
+                ends.extend([range.low, range.high]);
+                if applies == Some(true) {
+                    return hull(&ends);
+                }
+            }
+            // Parts of the box may have no value at all
+            Interval::ENTIRE
+        }
+    }
+}
+// Whether a condition holds everywhere in the box, nowhere, or `None` when it is not settled
+pub fn decide(node: &Node, variables: &HashMap<&str, Interval>, context: &Context) -> Option<bool> {
+    match node {
+        Node::Compare(comparison, a, b) => {
+            let difference = evaluate(a, variables, context) - evaluate(b, variables, context);
+            let (low, high) = (difference.low, difference.high);
+            match comparison {
+                Comparison::Less if (high < 0.0) => Some(true),
+                Comparison::Less if (low >= 0.0) => Some(false),
+                Comparison::LessEqual if (high <= 0.0) => Some(true),
+                Comparison::LessEqual if (low > 0.0) => Some(false),
+                Comparison::Greater if (low > 0.0) => Some(true),
+                Comparison::Greater if (high <= 0.0) => Some(false),
+                Comparison::GreaterEqual if (low >= 0.0) => Some(true),
+                Comparison::GreaterEqual if (high < 0.0) => Some(false),
+                _ => None,
+            }
+        }
+        Node::And(a, b) => match (decide(a, variables, context), decide(b, variables, context)) {
+            (Some(false), _) | (_, Some(false)) => Some(false),
+            (Some(true), Some(true)) => Some(true),
+            _ => None,
+        },
+        Node::Or(a, b) => match (decide(a, variables, context), decide(b, variables, context)) {
+            (Some(true), _) | (_, Some(true)) => Some(true),
+            (Some(false), Some(false)) => Some(false),
+            _ => None,
+        },
+        x => {
+            let range = evaluate(x, variables, context);
+            match (range.contains(0.0), range.low == range.high) {
+                (false, _) => Some(true),
+                (true, true) => Some(false),
+                (true, false) => None,
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::algebra_parser::generate_all_ast;
+    fn range_of(expression: &str, x: Interval) -> Interval {
+        let data = HashMap::from([("f", expression.to_string())]);
+        let node = generate_all_ast(&data).remove("f").unwrap().unwrap();
+        evaluate(&node, &HashMap::from([("x", x)]), &Context::default())
+    }
+    #[test]
+    fn ranges_enclose_the_values() {
+        assert_eq!(
+            range_of("x^2 - 1", Interval::new(-2.0, 1.0)),
+            Interval::new(-1.0, 3.0)
+        );
+        assert_eq!(range_of("sin(x)", Interval::new(0.0, PI)).high, 1.0);
+        assert!(range_of("sin(x)", Interval::new(0.0, PI)).low.abs() < 1e-15);
+        assert_eq!(range_of("1/x", Interval::new(-1.0, 1.0)), Interval::ENTIRE);
+        assert_eq!(
+            range_of("tan(x)", Interval::new(1.0, 2.0)),
+            Interval::ENTIRE
+        );
+        assert_eq!(
+            range_of("x - x", Interval::new(0.0, 1.0)),
+            Interval::new(-1.0, 1.0)
+        );
+    }
+    #[test]
+    fn conditions_are_decided() {
+        let data = HashMap::from([("f", "x^2 < 4 and x > 0".to_string())]);
+        let node = generate_all_ast(&data).remove("f").unwrap().unwrap();
+        let decide_on = |low: f64, high: f64| {
+            decide(
+                &node,
+                &HashMap::from([("x", Interval::new(low, high))]),
+                &Context::default(),
+            )
+        };
+        assert_eq!(decide_on(0.5, 1.5), Some(true));
+        assert_eq!(decide_on(-3.0, -1.0), Some(false));
+        assert_eq!(decide_on(1.0, 3.0), None);
+    }
+    #[test]
+    fn exclusion_proves_no_root() {
+        let range = range_of("x^2 + 1", Interval::new(-3.0, 3.0));
+        assert!(!range.contains(0.0));
+        let range = range_of("cos(x) - 2", Interval::new(-100.0, 100.0));
+        assert!(!range.contains(0.0));
+    }
+}
@@ -0,0 +1,124 @@
+//! The math and drawing behind the graphing calculator, usable without opening a window.
+//!
+//! The functions at the crate root cover the common cases: [`parse`] an expression,
+//! [`evaluate`] it with values in a [`Context`], [`differentiate`] it symbolically and
+//! print the result again with `Display`. [`BigNumber`] holds magnitudes far outside
+//! the range of an `f64` for the camera and for formatting results.
+//!
+//! ```
+//! use graphing_calculator::{differentiate, evaluate, parse, Context};
+//!
+//! let node = parse("x^3 + 2x").unwrap();
+//! let context = Context::default().with_variable("x", 2.0);
+//! assert_eq!(evaluate(&node, &context), Ok(12.0));
+//!
+//! let slope = differentiate(&node, "x").unwrap();
+//! assert_eq!(slope.to_string(), "3*x^2 + 2");
+//! assert_eq!(evaluate(&slope, &context), Ok(14.0));
+//! ```
+//!
+//! The modules hold everything else the app is built from: [`entries`] turns
+//! expressions into plots, [`scene`] collects what a frame draws, and [`svg`] and
+//! [`raster`] write a scene to a file.
+//!
+//! Window panels, input handling and on-screen drawing sit behind the default `ui`
+//! feature. Without it macroquad is not a dependency at all, and [`prelude`] supplies the
+//! vector, rectangle and color types in its place.
+#![warn(missing_docs)]
+pub mod algebra_parser;
+pub mod big_number;
+pub mod camera;
+#[cfg(feature = "ui")]
+pub mod cooldown;
+pub mod dataset;
+pub mod derivative_solver;
+pub mod entries;
+pub mod grid;
+pub mod history;
+mod implicit;
+pub mod intersections;
+mod interval;
+pub mod plotter;
+pub mod prelude;
+pub mod raster;
+pub mod regression;
+pub mod scene;
+pub mod session;
+pub mod shapes;
+pub mod sliders;
+mod statistics;
+pub mod svg;
+#[cfg(feature = "ui")]
+pub mod table;
+#[cfg(feature = "ui")]
+pub mod trace;
+
+pub use algebra_parser::evaluator::{Context, Value};
+pub use algebra_parser::Node;
+pub use big_number::{BigNumber, BigVec2};
+
+use std::collections::HashMap;
+
+/// Parses one expression into a syntax tree.
+///
+/// Runs of letters are split into known functions and single letter variables, so `ab`
+/// is `a*b` and `asin` is the function. Use [`parse_with_names`] for longer names.
+///
+/// ```
+/// let node = graphing_calculator::parse("2sin(x)").unwrap();
+/// assert_eq!(node.to_string(), "2*sin(x)");
+/// assert!(graphing_calculator::parse("(1 + 2").is_err());
+/// ```
+pub fn parse(expression: &str) -> Result<Node, String> {
+    parse_with_names(expression, &[])
+}
+/// Like [`parse`], but each of `names` is read as one variable, or as a call when it is
+/// followed by parentheses.
+///
+/// ```
+/// use graphing_calculator::{evaluate, parse_with_names, Context};
+///
+/// let node = parse_with_names("rate*2", &["rate"]).unwrap();
+/// let context = Context::default().with_variable("rate", 1.5);
+/// assert_eq!(evaluate(&node, &context), Ok(3.0));
+/// ```
+pub fn parse_with_names(expression: &str, names: &[&str]) -> Result<Node, String> {
+    let data = HashMap::from([("expression", expression.to_string())]);
+    algebra_parser::generate_all_ast_with_names(&data, names)
+        .remove("expression")
+        .unwrap_or(Err(String::new()))
+}
+/// Evaluates an expression to one number.
+///
+/// Lists, tuples and variables missing from `context` are errors. Results outside a
+/// function's domain, such as `ln(-1)`, are NaN rather than errors.
+pub fn evaluate(node: &Node, context: &Context) -> Result<f64, String> {
+    algebra_parser::evaluator::evaluate(node, context).map_err(String::from)
+}
+/// Like [`evaluate`], but lists and list functions such as `sort` are allowed.
+///
+/// ```
+/// use graphing_calculator::{evaluate_value, parse, Context, Value};
+///
+/// let node = parse("sort([3, 1, 2])").unwrap();
+/// let value = evaluate_value(&node, &Context::default());
+/// assert_eq!(value, Ok(Value::List(vec![1.0, 2.0, 3.0])));
+/// ```
+pub fn evaluate_value(node: &Node, context: &Context) -> Result<Value, String> {
+    algebra_parser::evaluator::evaluate_value(node, context).map_err(String::from)
+}
+/// The symbolic derivative with respect to `variable`, simplified.
+///
+/// Conditions and user defined functions have no derivative. Piecewise expressions are
+/// differentiated piece by piece.
+///
+/// ```
+/// use graphing_calculator::{differentiate, evaluate, parse, Context};
+///
+/// let slope = differentiate(&parse("x*ln(x)").unwrap(), "x").unwrap();
+/// let context = Context::default().with_variable("x", 1.0);
+/// assert_eq!(evaluate(&slope, &context), Ok(1.0));
+/// ```
+pub fn differentiate(node: &Node, variable: &str) -> Result<Node, String> {
+    derivative_solver::derivative::differentiate(node, variable).map_err(String::from)
+}
@@ -2,37 +2,33 @@
 #![allow(unused_parens)]
 #![allow(unused_variables)]
 use std::collections::HashMap;
-use std::f64::consts::PI;
-use std::iter::Scan;
-use std::ops::Add;
 
-use big_number::BigNumber;
-use big_number::BigVec2;
-use cooldown::*;
-use macroquad::color::Color;
-use macroquad::miniquad::window::screen_size;
+use graphing_calculator::camera::Camera;
+use graphing_calculator::dataset::DatasetPanel;
+use graphing_calculator::entries::EntryList;
+use graphing_calculator::grid::GridMode;
+use graphing_calculator::history::{apply_changes, History, Snapshot};
+use graphing_calculator::intersections::IntersectionCache;
+use graphing_calculator::regression::RegressionPanel;
+use graphing_calculator::scene::Scene;
+use graphing_calculator::session::{Session, SettingsState};
+use graphing_calculator::shapes::ShapeCache;
+use graphing_calculator::sliders::SliderList;
+use graphing_calculator::table::ValueTable;
+use graphing_calculator::trace::Tracer;
+use graphing_calculator::{cooldown, grid, raster, session, sliders, svg};
 use macroquad::prelude::*;
-use macroquad::text::*;
-use macroquad::ui::{
-    hash, root_ui,
-    widgets::{self, Group},
-    Drag, Ui,
-};
-mod algebra_parser;
-mod big_number;
-mod cooldown;
-mod derivative_solver;
+use macroquad::ui::{hash, root_ui, widgets};
+mod cli;
+mod repl;
 type NumberDependency = f64;
+const ZOOM_FACTOR: NumberDependency = 1.1;
+// Moving the Settings window after a session load needs its id outside `create_ui`
+const SETTINGS_WINDOW: &str = "settings";
 static mut SETTINGS_POSITION: Vec2 = vec2(0.0, 0.0);
 type CanvasDimensions<'a> = &'a mut NumberDependency;
 
 // Helper Functions
-fn area_of_circle(radius: NumberDependency) -> NumberDependency {
-    return (PI as NumberDependency) * radius.powf(2.0);
-}
-fn radius_from_area_of_circle(area: NumberDependency) -> NumberDependency {
-    return (area / PI).sqrt();
-}
 fn negate_vector(vector: Vec2) -> Vec2 {
     vec2(-vector.x, -vector.y)
 }
@@ -41,84 +37,106 @@ fn negate_vector(vector: Vec2) -> Vec2 {
 struct AppState<'a, 'b> {
     // Settings
     settings_position: &'b mut Vec2,
+    // From the Settings window corner to where its content starts, read on the first frame
+    settings_offset: Option<Vec2>,
     resolution_slider_value: &'a mut f32,
+    grid_mode: GridMode,
     //
     old_screen_width: &'a mut f32,
     old_screen_height: &'a mut f32,
     current_fps: i32,
+    entries: EntryList,
+    sliders: SliderList,
+    table: ValueTable,
+    regression: RegressionPanel,
+    datasets: DatasetPanel,
+    // Session file and exports
+    session_path: String,
+    file_status: Option<String>,
+    file_action: Option<FileAction>,
+    // Why saving on quit failed, the app stays open until the user decides
+    quit_error: Option<String>,
+    quit_confirmed: bool,
+    //
+    history: History,
+    save_history: bool,
+}
+// Asked for from the Settings window, carried out in the main loop where the camera lives
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FileAction {
+    SaveSession,
+    LoadSession,
+    ExportSvg,
+    ExportPng,
 }
 impl<'a, 'b> AppState<'a, 'b> {
     fn update_fps(&mut self) {
         self.current_fps = get_fps();
     }
-}
-trait ShapeScale {
-    fn draw_figure(&self, color: Color);
-}
-#[derive(Copy, Clone)]
-struct Circle {
-    radius: NumberDependency,
-    x_pos: NumberDependency,
-    y_pos: NumberDependency,
-
-    original_width: NumberDependency,
-    original_height: NumberDependency,
-}
-impl ShapeScale for Circle {
-    fn draw_figure(&self, color: Color) {
-        let screen_width = screen_width() as NumberDependency;
-        let screen_height = screen_height() as NumberDependency;
-        let old_area = self.original_width * self.original_height;
-        let new_area = screen_width * screen_height;
-        // println!("{}", radius_from_area_of_circle(((area_of_circle(self.radius) / old_area) * new_area)));
-        draw_circle(
-            ((self.x_pos / self.original_width) * screen_width) as f32,
-            ((self.y_pos / self.original_height) * screen_height) as f32,
-            radius_from_area_of_circle(((area_of_circle(self.radius) / old_area) * new_area))
-                as f32,
-            color,
-        );
+    fn capture_session(&self, camera: &Camera) -> Session {
+        let settings = SettingsState {
+            position: self.settings_position.to_array(),
+            resolution: *self.resolution_slider_value,
+            grid: self.grid_mode.get_name().to_string(),
+            save_history: self.save_history,
+        };
+        let mut session = Session::capture(settings, camera, &self.entries, &self.sliders);
+        if (self.save_history) {
+            session.history = Some(self.history.clone());
+        }
+        session
     }
-}
-fn create_circle(
-    circle_radius: NumberDependency,
-    circle_x_pos: NumberDependency,
-    circle_y_pos: NumberDependency,
-) -> Circle {
-    Circle {
-        radius: circle_radius,
-        x_pos: circle_x_pos,
-        y_pos: circle_y_pos,
-        original_width: screen_width() as NumberDependency,
-        original_height: screen_height() as NumberDependency,
+    fn apply_session(&mut self, session: &Session, camera: &mut Camera) -> Result<(), String> {
+        session.restore(camera, &mut self.entries, &mut self.sliders)?;
+        *self.settings_position = Vec2::from_array(session.settings.position);
+        // Movable windows ignore the position they are created with after the first frame
+        root_ui().move_window(hash!(SETTINGS_WINDOW), *self.settings_position);
+        *self.resolution_slider_value = session.settings.resolution;
+        self.grid_mode = session.settings.get_grid_mode();
+        self.save_history = session.settings.save_history;
+        self.history = session.history.clone().unwrap_or_default();
+        Ok(())
     }
-}
-struct CircleCache {
-    cache: Vec<Circle>,
-}
-impl CircleCache {
-    fn push(&mut self, new_circle: Circle) -> bool {
-        let index = self.cache.iter().position(|&item| {
-            (item.radius == new_circle.radius)
-                && (item.x_pos == new_circle.x_pos)
-                && (item.y_pos == new_circle.y_pos)
-        });
-        let mut flag = false;
-        match index {
-            Some(_t) => {
-                // println!("Already inserted circle into cache!");
+    fn handle_file_action(&mut self, camera: &mut Camera, scene: &Scene) {
+        let path = self.session_path.trim().to_string();
+        let result = match self.file_action.take() {
+            Some(FileAction::SaveSession) => self
+                .capture_session(camera)
+                .save(&path)
+                .map(|_| format!("Saved {}", path)),
+            Some(FileAction::LoadSession) => Session::load(&path)
+                .and_then(|x| self.apply_session(&x, camera))
+                .map(|_| format!("Loaded {}", path)),
+            Some(FileAction::ExportSvg) => {
+                svg::export(scene, svg::EXPORT_PATH).map(|_| format!("Saved {}", svg::EXPORT_PATH))
             }
-            None => {
-                flag = true;
-                self.cache.push(new_circle.clone());
-            }
-        }
-        flag
+            Some(FileAction::ExportPng) => raster::export(scene, raster::EXPORT_PATH)
+                .map(|_| format!("Saved {}", raster::EXPORT_PATH)),
+            None => return,
+        };
+        self.file_status = Some(result.unwrap_or_else(|x| x));
     }
-    fn execute_drawings(&self) {
-        for circle in self.cache.iter() {
-            circle.draw_figure(BLACK);
+    // Ctrl+Z undoes, Ctrl+Shift+Z or Ctrl+Y redoes, then whatever changed this frame is recorded
+    fn update_history(&mut self, camera: &mut Camera) {
+        let control = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+        let changes = match (control, shift) {
+            (true, false) if (is_key_pressed(KeyCode::Z)) => self.history.undo(),
+            (true, true) if (is_key_pressed(KeyCode::Z)) => self.history.redo(),
+            (true, _) if (is_key_pressed(KeyCode::Y)) => self.history.redo(),
+            _ => None,
+        };
+        if let Some(changes) = changes {
+            let restored = apply_changes(&changes, camera, &mut self.entries, &mut self.sliders);
+            if let Err(x) = restored {
+                self.file_status = Some(x);
+            }
         }
+        // Sliders for new variables are added now rather than next frame, so they are
+        // part of the same step as the entry that introduced them
+        self.sliders.sync(&mut self.entries);
+        let snapshot = Snapshot::capture(camera, &self.entries, &self.sliders);
+        self.history.record(snapshot, get_time());
     }
 }
 fn create_ui(global_state: &mut AppState) {
@@ -136,18 +154,85 @@ fn create_ui(global_state: &mut AppState) {
         screen_height() / 12.0,
         params,
     );
-    widgets::Window::new(hash!(), *global_state.settings_position, vec2(320.0, 400.0))
-        .label("Settings")
-        .movable(true)
-        .ui(&mut *root_ui(), |ui| {
-            ui.label(None, "Resolution Slider:");
-            ui.slider(
-                hash!(),
-                "(0 .. 1)",
-                0.0f32..1.0f32,
-                global_state.resolution_slider_value,
-            );
-        });
+    widgets::Window::new(
+        hash!(SETTINGS_WINDOW),
+        *global_state.settings_position,
+        vec2(320.0, 400.0),
+    )
+    .label("Settings")
+    .movable(true)
+    .ui(&mut root_ui(), |ui| {
+        // Dragging moves the window inside macroquad, its position is read back from where
+        // the content starts
+        let content = ui.canvas().cursor() - ui.scroll();
+        let offset = *global_state
+            .settings_offset
+            .get_or_insert(content - *global_state.settings_position);
+        *global_state.settings_position = content - offset;
+        ui.label(None, "Resolution Slider:");
+        ui.slider(
+            hash!(),
+            "(0 .. 1)",
+            0.0f32..1.0f32,
+            global_state.resolution_slider_value,
+        );
+        let names = GridMode::ALL.map(|x| x.get_name());
+        let mut mode_index = GridMode::ALL
+            .iter()
+            .position(|x| *x == global_state.grid_mode)
+            .unwrap_or(0);
+        ui.combo_box(hash!(), "Grid", &names, &mut mode_index);
+        global_state.grid_mode = GridMode::ALL[mode_index];
+        ui.input_text(hash!(), "Session", &mut global_state.session_path);
+        if (ui.button(None, "Save")) {
+            global_state.file_action = Some(FileAction::SaveSession);
+        }
+        ui.same_line(0.0);
+        if (ui.button(None, "Load")) {
+            global_state.file_action = Some(FileAction::LoadSession);
+        }
+        if (ui.button(None, "Export SVG")) {
+            global_state.file_action = Some(FileAction::ExportSvg);
+        }
+        ui.same_line(0.0);
+        if (ui.button(None, "Export PNG")) {
+            global_state.file_action = Some(FileAction::ExportPng);
+        }
+        ui.checkbox(hash!(), "Save undo history", &mut global_state.save_history);
+        if let Some(ref status) = global_state.file_status {
+            ui.label(None, status);
+        }
+        global_state.sliders.create_ui(ui);
+    });
+    if let Some(error) = global_state.quit_error.clone() {
+        let size = vec2(420.0, 110.0);
+        let position = (vec2(screen_width(), screen_height()) - size) / 2.0;
+        widgets::Window::new(hash!(), position, size)
+            .label("Session not saved")
+            .ui(&mut root_ui(), |ui| {
+                ui.label(None, &error);
+                if (ui.button(None, "Quit without saving")) {
+                    global_state.quit_confirmed = true;
+                }
+                ui.same_line(0.0);
+                if (ui.button(None, "Cancel")) {
+                    global_state.quit_error = None;
+                }
+            });
+    }
+    global_state
+        .entries
+        .create_ui(*global_state.settings_position + vec2(330.0, 0.0));
+    global_state.entries.create_expression_ui(vec2(20.0, 200.0));
+    global_state
+        .table
+        .create_ui(&mut global_state.entries, vec2(20.0, 620.0));
+    global_state
+        .regression
+        .create_ui(&mut global_state.entries, vec2(450.0, 620.0));
+    global_state
+        .datasets
+        .create_ui(&mut global_state.entries, vec2(800.0, 620.0));
 }
 fn update_resolution(global_state: &mut AppState) {
     let is_width_too_small = (1920.0 * *global_state.resolution_slider_value) < 500.0;
@@ -166,45 +251,104 @@ fn update_resolution(global_state: &mut AppState) {
         1080.0 * *global_state.resolution_slider_value,
     );
 }
-struct Camera {
-    position: BigVec2,
-    number_distance: f32,
-}
-impl Camera {
-    fn new() -> Self {
-        Camera {
-            position: BigVec2 {
-                x: BigNumber::new_d(0.0),
-                y: BigNumber::new_d(0.0),
-            },
-            number_distance: screen_width() / 10.0,
+fn update_camera(camera: &mut Camera, last_mouse_position: &mut Option<Vec2>) {
+    let screen = vec2(screen_width(), screen_height());
+    let mouse = Vec2::from(mouse_position());
+    if (root_ui().is_mouse_over(mouse)) {
+        *last_mouse_position = None;
+        return;
+    }
+    let (_, wheel) = mouse_wheel();
+    if (wheel != 0.0) {
+        camera.zoom_at(
+            mouse,
+            ZOOM_FACTOR.powf(wheel.signum() as NumberDependency),
+            screen,
+        );
+    }
+    if (is_mouse_button_down(MouseButton::Left)) {
+        if let Some(last) = *last_mouse_position {
+            camera.pan(mouse - last);
         }
+        *last_mouse_position = Some(mouse);
+    } else {
+        *last_mouse_position = None;
     }
 }
-fn update_grid(camera: &Camera) {
-    let origin_offset = camera.position.clone();
-    // let tl_corner = origin_offset + BigVec2::new((camera.number_distance * 5.0), BigNumber::new());
+// Everything the graph shows, drawn on screen every frame and written out by the exports
+fn build_scene(
+    global_state: &AppState,
+    camera: &Camera,
+    shapes: &mut ShapeCache,
+    screen: Vec2,
+    time: f64,
+) -> Scene {
+    let mut scene = Scene::new(screen);
+    let geometry = match global_state.grid_mode {
+        GridMode::Cartesian => grid::compute(camera, screen),
+        GridMode::Polar(unit) => grid::compute_polar(camera, screen, unit),
+    };
+    grid::push_to_scene(&geometry, &mut scene);
+    global_state.entries.push_to_scene(&mut scene, camera, time);
+    shapes.clear();
+    global_state.entries.push_markers(shapes);
+    global_state.table.push_markers(shapes);
+    shapes.push_to_scene(&mut scene, camera);
+    global_state.entries.push_legend(&mut scene);
+    scene
 }
-#[macroquad::main("GRAPHING_CALCULATOR")]
-async fn main() {
+// Commands on the command line run in batch mode without ever opening a window
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<String>>();
+    if (!args.is_empty()) {
+        std::process::exit(cli::run(&args));
+    }
+    macroquad::Window::new("GRAPHING_CALCULATOR", run_window());
+}
+async fn run_window() {
     let mut resolution_slider_value = 1.0f32;
     let mut old_screen_width = screen_width();
     let mut old_screen_height = screen_height();
     let mut settings_position = vec2(400.0, 200.0);
-    let mut GlobalState = AppState {
+    let mut global_state = AppState {
         resolution_slider_value: &mut resolution_slider_value,
         old_screen_width: &mut old_screen_width,
         old_screen_height: &mut old_screen_height,
         settings_position: &mut settings_position,
+        settings_offset: None,
+        grid_mode: GridMode::Cartesian,
         current_fps: get_fps(),
+        entries: EntryList::new(),
+        sliders: SliderList::new(),
+        table: ValueTable::new(),
+        regression: RegressionPanel::new(),
+        datasets: DatasetPanel::new(),
+        session_path: session::get_default_path(),
+        file_status: None,
+        file_action: None,
+        quit_error: None,
+        quit_confirmed: false,
+        history: History::new(),
+        save_history: false,
     };
-    let mut circle_cache = CircleCache { cache: Vec::new() };
     request_new_screen_size(1920.0, 1080.0);
     let mut is_first_iteration = true;
     let mut cooldown_storage = HashMap::new();
-    let resolution_cooldown = cooldown::job::add(&mut cooldown_storage, "resolution", 2);
-    let fps_cooldown = cooldown::job::add(&mut cooldown_storage, "fps", 1);
-    let camera = Camera::new();
+    cooldown::job::add(&mut cooldown_storage, "resolution", 2);
+    cooldown::job::add(&mut cooldown_storage, "fps", 1);
+    cooldown::job::add_seconds(&mut cooldown_storage, "sliders", sliders::PLAY_INTERVAL);
+    let mut camera = Camera::new();
+    // The last session comes back on start and is written again on exit
+    let default_path = session::get_default_path();
+    prevent_quit();
+    match Session::load(&default_path) {
+        Ok(x) if (global_state.apply_session(&x, &mut camera).is_ok()) => {}
+        _ => global_state.entries.add("sin(x)"),
+    }
+    let mut last_mouse_position = None;
+    let mut tracer = Tracer::new();
+    let mut intersections = IntersectionCache::new();
+    let mut shapes = ShapeCache::new();
     loop {
         // Code that must run at the beginning of the frame
         if (is_first_iteration) {
@@ -214,35 +358,43 @@ async fn main() {
             }
             continue;
         }
+        if (is_quit_requested()) {
+            match global_state.capture_session(&camera).save(&default_path) {
+                Ok(_) => break,
+                Err(x) => {
+                    eprintln!("{}", x);
+                    global_state.quit_error = Some(x);
+                }
+            }
+        }
+        if (global_state.quit_confirmed) {
+            break;
+        }
         cooldown::job::update(&mut cooldown_storage);
         clear_background(WHITE);
         if (cooldown::job::is_on(&cooldown_storage, "resolution")) {
-            update_resolution(&mut GlobalState);
+            update_resolution(&mut global_state);
         }
         if cooldown::job::is_on(&cooldown_storage, "fps") {
-            GlobalState.current_fps = get_fps();
+            global_state.current_fps = get_fps();
         }
-        create_ui(&mut GlobalState);
+        if cooldown::job::is_on(&cooldown_storage, "sliders") {
+            global_state.sliders.advance();
+        }
+        global_state.sliders.sync(&mut global_state.entries);
+        update_camera(&mut camera, &mut last_mouse_position);
         // Body Code
-        let circle_radius = 150.0;
-        let circle_x_pos = 200.0;
-        let circle_y_pos = 200.0;
-        let circle = create_circle(
-            NumberDependency::from(circle_radius),
-            NumberDependency::from(circle_x_pos),
-            NumberDependency::from(circle_y_pos),
-        );
-        circle_cache.push(circle);
-        update_grid(&camera);
-        println!(
-            "{}, lol",
-            (BigNumber::new_d(5000000.0) * 500000.0 * 5000000.0 * 500000000.0 * 10000.0)
-                .get_value()
-        );
-        println!(
-            "{}, afk",
-            (BigNumber::new_d(-5000.0) + BigNumber::new_d(-200.0)).get_value()
-        );
+        let screen = vec2(screen_width(), screen_height());
+        let scene = build_scene(&global_state, &camera, &mut shapes, screen, get_time());
+        scene.draw();
+        intersections.update(&global_state.entries, &camera, screen);
+        intersections.handle_click(&camera, screen);
+        intersections.draw(&camera, screen);
+        tracer.update(&global_state.entries, &camera, screen);
+        tracer.draw(&global_state.entries, &camera, screen);
+        create_ui(&mut global_state);
+        global_state.handle_file_action(&mut camera, &scene);
+        global_state.update_history(&mut camera);
         // Code that must run at the end of the frame
         cooldown::job::update_next(&mut cooldown_storage);
         next_frame().await;
@@ -0,0 +1,532 @@
+//! Sampling functions and curves into screen space polylines.
+use crate::big_number::{BigNumber, BigVec2};
+use crate::camera::Camera;
+use crate::prelude::*;
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+// Points further than this off screen are clipped so f32 never has to hold them
+const SCREEN_MARGIN: f32 = 1e4;
+
+/// Consecutive screen points, broken wherever the function is undefined
+pub type Polyline = Vec<Vec2>;
+
+// Adaptive sampling stops splitting once an interval is narrower than this many pixels
+const MIN_INTERVAL_WIDTH: f32 = 1e-3;
+// Pixel distance between the midpoint and the chord that is still drawn as a straight line
+const FLATNESS_TOLERANCE: f32 = 0.25;
+// Smallest turn (in radians) at the midpoint that counts as visible curvature
+const CURVATURE_TOLERANCE: f32 = 0.1;
+// Jumps taller than this that survive subdivision are treated as discontinuities
+const JUMP_THRESHOLD: f32 = 30.0;
+const INITIAL_INTERVAL_WIDTH: f32 = 8.0;
+/// Samples shared by every curve on screen in one frame.
+pub const DEFAULT_SAMPLE_BUDGET: usize = 4000;
+
+// Screen point of `function` at the given screen column. The x coordinate is built from the
+// camera in BigNumber space and only the final screen offset is converted to f32
+fn evaluate_at<F: Fn(f64) -> f64>(
+    camera: &Camera,
+    screen: Vec2,
+    function: &F,
+    pixel_x: f32,
+) -> Option<Vec2> {
+    let world_x = camera.screen_to_world(vec2(pixel_x, 0.0), screen).x;
+    let world_y = function(world_x.get_deserialized());
+    if !world_y.is_finite() {
+        return None;
+    }
+    let point = camera.world_to_screen(&BigVec2::new(world_x, BigNumber::new_d(world_y)), screen);
+    match point.y.is_finite() {
+        true => Some(vec2(pixel_x, point.y)),
+        false => Some(vec2(pixel_x, point.y.signum() * f32::MAX)),
+    }
+}
+/// Samples `function` once every `step` pixels across the screen
+pub fn sample_function<F: Fn(f64) -> f64>(
+    camera: &Camera,
+    screen: Vec2,
+    function: F,
+    step: f32,
+) -> Vec<Polyline> {
+    let mut polylines = Vec::new();
+    let mut current: Polyline = Vec::new();
+    let mut pixel_x = 0.0;
+    while pixel_x <= screen.x + step {
+        match evaluate_at(camera, screen, &function, pixel_x) {
+            Some(x) if (x.y.abs() < screen.y + SCREEN_MARGIN) => current.push(x),
+            _ => {
+                if current.len() > 1 {
+                    polylines.push(current);
+                }
+                current = Vec::new();
+            }
+        }
+        pixel_x += step;
+    }
+    if current.len() > 1 {
+        polylines.push(current);
+    }
+    polylines
+}
+#[derive(Clone, Copy)]
+struct Sample {
+    // Screen column for functions, t for parametric curves
+    parameter: f64,
+    point: Option<Vec2>,
+}
+struct Interval {
+    left: Sample,
+    middle: Sample,
+    right: Sample,
+    // Rough pixels per unit of the parameter, so widths can be compared to screen tolerances
+    scale: f32,
+    error: f32,
+}
+impl Interval {
+    fn new(left: Sample, middle: Sample, right: Sample, scale: f32, screen: Vec2) -> Interval {
+        let mut interval = Interval {
+            left,
+            middle,
+            right,
+            scale,
+            error: 0.0,
+        };
+        interval.error = interval.measure_error(screen);
+        interval
+    }
+    fn width(&self) -> f32 {
+        (self.right.parameter - self.left.parameter) as f32 * self.scale
+    }
+    // How badly a straight chord from left to right misrepresents the curve, in pixels
+    fn measure_error(&self, screen: Vec2) -> f32 {
+        if self.width() < MIN_INTERVAL_WIDTH {
+            return 0.0;
+        }
+        let (left, middle, right) = match (self.left.point, self.middle.point, self.right.point) {
+            (Some(l), Some(m), Some(r)) => (l, m, r),
+            (None, None, None) => return 0.0,
+            // Domain edge, keep narrowing it down
+            _ => return self.width(),
+        };
+        if self.is_hidden(screen) {
+            return 0.0;
+        }
+        let chord = right - left;
+        if chord.length() > JUMP_THRESHOLD {
+            // Either very steep or a jump, only narrowing it down tells them apart
+            return chord.length().min(screen.y * 2.0);
+        }
+        let deviation = match chord.length() > 0.0 {
+            true => (chord.perp_dot(middle - left) / chord.length()).abs(),
+            false => middle.distance(left),
+        };
+        let first = middle - left;
+        let second = right - middle;
+        let turn = first.angle_between(second).abs();
+        match (turn > CURVATURE_TOLERANCE) && (chord.length() > 1.0) {
+            true => deviation.max(FLATNESS_TOLERANCE * 2.0).min(screen.y * 2.0),
+            false => deviation.min(screen.y * 2.0),
+        }
+    }
+    // Entirely beyond one of the screen edges
+    fn is_hidden(&self, screen: Vec2) -> bool {
+        match (self.left.point, self.middle.point, self.right.point) {
+            (Some(l), Some(m), Some(r)) => {
+                let (low, high) = (l.min(m).min(r), l.max(m).max(r));
+                (high.x < 0.0) || (low.x > screen.x) || (high.y < 0.0) || (low.y > screen.y)
+            }
+            _ => false,
+        }
+    }
+    // A jump that is still taller than the threshold after subdividing is an asymptote or step
+    fn is_discontinuous(&self) -> bool {
+        match (self.left.point, self.middle.point, self.right.point) {
+            (Some(l), Some(m), Some(r)) => {
+                let chord = r - l;
+                // Where the middle sample projects onto the chord, outside [0, 1] means it
+                // overshot both ends the way a pole does
+                let along = (m - l).dot(chord) / chord.length_squared();
+                let outside = !(0.0..=1.0).contains(&along);
+                (chord.length() > JUMP_THRESHOLD)
+                    && ((self.width() < MIN_INTERVAL_WIDTH) || outside)
+            }
+            _ => true,
+        }
+    }
+}
+impl PartialEq for Interval {
+    fn eq(&self, other: &Self) -> bool {
+        self.error == other.error
+    }
+}
+impl Eq for Interval {}
+impl PartialOrd for Interval {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Interval {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.error.total_cmp(&other.error)
+    }
+}
+// Cuts a segment down to the part that lies within the margin around the screen
+fn clip_segment(a: Vec2, b: Vec2, screen: Vec2) -> Option<(Vec2, Vec2)> {
+    let (low, high) = (vec2(-SCREEN_MARGIN, -SCREEN_MARGIN), screen + SCREEN_MARGIN);
+    if (a.y < low.y && b.y < low.y) || (a.y > high.y && b.y > high.y) {
+        return None;
+    }
+    if (a.x < low.x && b.x < low.x) || (a.x > high.x && b.x > high.x) {
+        return None;
+    }
+    // Slides `from` towards `to` until it is inside the margin on the given axis
+    let clip = |from: Vec2, to: Vec2, axis: usize| {
+        let limit = from[axis].clamp(low[axis], high[axis]);
+        match limit == from[axis] {
+            true => from,
+            false => {
+                let t = ((limit - to[axis]) as f64 / (from[axis] - to[axis]) as f64) as f32;
+                let mut point = to + (from - to) * t;
+                point[axis] = limit;
+                point
+            }
+        }
+    };
+    let (a, b) = (clip(a, b, 1), clip(b, a, 1));
+    Some((clip(a, b, 0), clip(b, a, 0)))
+}
+// Splits the worst intervals first until every chord is within tolerance of the curve or
+// `budget` evaluations have been spent, then joins the chords into polylines
+fn refine<S: Fn(f64) -> Option<Vec2>>(
+    screen: Vec2,
+    point_at: S,
+    range: (f64, f64),
+    count: usize,
+    scale: f32,
+    budget: usize,
+) -> Vec<Polyline> {
+    let sample = |parameter: f64| Sample {
+        parameter,
+        point: point_at(parameter),
+    };
+    let width = (range.1 - range.0) / count as f64;
+    let mut evaluations = 0;
+    let mut queue = BinaryHeap::new();
+    let mut finished = Vec::new();
+    let mut left = sample(range.0);
+    evaluations += 1;
+    for index in 0..count {
+        let middle = sample(range.0 + (index as f64 + 0.5) * width);
+        let right = sample(range.0 + (index + 1) as f64 * width);
+        evaluations += 2;
+        queue.push(Interval::new(left, middle, right, scale, screen));
+        left = right;
+    }
+    while let Some(interval) = queue.pop() {
+        if (interval.error <= FLATNESS_TOLERANCE) || (evaluations + 2 > budget) {
+            finished.push(interval);
+            continue;
+        }
+        let first = sample((interval.left.parameter + interval.middle.parameter) / 2.0);
+        let second = sample((interval.middle.parameter + interval.right.parameter) / 2.0);
+        evaluations += 2;
+        queue.push(Interval::new(
+            interval.left,
+            first,
+            interval.middle,
+            scale,
+            screen,
+        ));
+        queue.push(Interval::new(
+            interval.middle,
+            second,
+            interval.right,
+            scale,
+            screen,
+        ));
+    }
+    finished.sort_by(|a, b| a.left.parameter.total_cmp(&b.left.parameter));
+
+    let mut polylines = Vec::new();
+    let mut current: Polyline = Vec::new();
+    let mut push_segment = |current: &mut Polyline, a: Option<Vec2>, b: Option<Vec2>| {
+        let clipped = match (a, b) {
+            (Some(a), Some(b)) => clip_segment(a, b, screen),
+            _ => None,
+        };
+        match clipped {
+            Some((a, b)) => {
+                if current.last() != Some(&a) {
+                    if current.len() > 1 {
+                        polylines.push(std::mem::take(current));
+                    }
+                    current.clear();
+                    current.push(a);
+                }
+                current.push(b);
+            }
+            None => {
+                if current.len() > 1 {
+                    polylines.push(std::mem::take(current));
+                }
+                current.clear();
+            }
+        }
+    };
+    for interval in finished.iter() {
+        if interval.is_hidden(screen) || interval.is_discontinuous() {
+            push_segment(&mut current, None, None);
+            continue;
+        }
+        push_segment(&mut current, interval.left.point, interval.middle.point);
+        push_segment(&mut current, interval.middle.point, interval.right.point);
+    }
+    push_segment(&mut current, None, None);
+    polylines
+}
+/// Adaptive sampling of y = f(x) across the screen, so features like sin(1/x) get the
+/// detail they need
+pub fn sample_adaptive<F: Fn(f64) -> f64>(
+    camera: &Camera,
+    screen: Vec2,
+    function: F,
+    budget: usize,
+) -> Vec<Polyline> {
+    let count = (screen.x / INITIAL_INTERVAL_WIDTH).ceil().max(1.0) as usize;
+    refine(
+        screen,
+        |pixel_x| evaluate_at(camera, screen, &function, pixel_x as f32),
+        (0.0, screen.x as f64),
+        count,
+        1.0,
+        budget,
+    )
+}
+/// Adaptive sampling of (x(t), y(t)) for t in `range`, starting from intervals `step` wide
+pub fn sample_parametric<F: Fn(f64) -> Option<(f64, f64)>>(
+    camera: &Camera,
+    screen: Vec2,
+    function: F,
+    range: (f64, f64),
+    step: f64,
+    budget: usize,
+) -> Vec<Polyline> {
+    if range.1 <= range.0 || step <= 0.0 {
+        return Vec::new();
+    }
+    let most = (budget / 2).max(1) as f64;
+    let count = ((range.1 - range.0) / step).ceil().clamp(1.0, most) as usize;
+    let point_at = |t: f64| {
+        let (x, y) = function(t)?;
+        if !x.is_finite() || !y.is_finite() {
+            return None;
+        }
+        let point = camera.world_to_screen(&BigVec2::new_d(x, y), screen);
+        let limit = |x: f32| match x.is_finite() {
+            true => x,
+            false => x.signum() * f32::MAX,
+        };
+        Some(vec2(limit(point.x), limit(point.y)))
+    };
+    let scale = screen.length() / (range.1 - range.0) as f32;
+    refine(screen, point_at, range, count, scale, budget)
+}
+/// How a curve is stroked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DashStyle {
+    /// One unbroken line
+    Solid,
+    /// Long dashes
+    Dashed,
+    /// Dots as wide as the line
+    Dotted,
+}
+impl DashStyle {
+    /// Every style, in the order the style settings list them.
+    pub const ALL: [DashStyle; 3] = [DashStyle::Solid, DashStyle::Dashed, DashStyle::Dotted];
+    /// The name shown in the style settings.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            DashStyle::Solid => "Solid",
+            DashStyle::Dashed => "Dashed",
+            DashStyle::Dotted => "Dotted",
+        }
+    }
+    /// Lengths of the drawn and skipped parts, scaled with the line so thick dashes stay readable
+    pub fn get_pattern(&self, thickness: f32) -> Option<(f32, f32)> {
+        match self {
+            DashStyle::Solid => None,
+            DashStyle::Dashed => Some((4.0 * thickness + 6.0, 2.0 * thickness + 4.0)),
+            DashStyle::Dotted => Some((thickness, 2.0 * thickness + 2.0)),
+        }
+    }
+}
+/// Cuts a polyline into the visible pieces of a dash pattern, carrying the phase across corners
+pub fn dash_polyline(polyline: &[Vec2], pattern: Option<(f32, f32)>) -> Vec<(Vec2, Vec2)> {
+    let (on, off) = match pattern {
+        Some(x) => x,
+        None => {
+            return polyline.windows(2).map(|x| (x[0], x[1])).collect();
+        }
+    };
+    let mut pieces = Vec::new();
+    let mut is_on = true;
+    let mut remaining = on;
+    for segment in polyline.windows(2) {
+        let (mut start, end) = (segment[0], segment[1]);
+        let mut length = start.distance(end);
+        while length > 0.0 {
+            let step = remaining.min(length);
+            let next = start + (end - start) * (step / length);
+            if is_on {
+                pieces.push((start, next));
+            }
+            remaining -= step;
+            length -= step;
+            start = next;
+            if remaining <= 0.0 {
+                is_on = !is_on;
+                remaining = match is_on {
+                    true => on,
+                    false => off,
+                };
+            }
+        }
+    }
+    pieces
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::Cell;
+    fn camera_at(x: f64, y: f64, zoom: f64) -> Camera {
+        Camera {
+            position: BigVec2::new_d(x, y),
+            number_distance: BigNumber::new_d(zoom),
+        }
+    }
+    #[test]
+    fn same_curve_at_different_zooms() {
+        let screen = vec2(1000.0, 500.0);
+        let function = |x: f64| 0.5 * x + x.sin();
+        for zoom in [1e-30, 1e-12, 1.0, 100.0, 1e12, 1e30] {
+            let camera = camera_at(3.0, function(3.0), zoom);
+            let polylines = sample_function(&camera, screen, function, 5.0);
+            assert_eq!(polylines.len(), 1, "{}", zoom);
+            assert_eq!(polylines[0].len(), 202, "{}", zoom);
+            // Every sample lies on the curve, measured in pixels at this zoom
+            for point in polylines[0].iter() {
+                let world = camera.screen_to_world(*point, screen);
+                let expected = BigNumber::new_d(function(world.x.get_deserialized()));
+                let error = (world.y - expected) * camera.number_distance.clone();
+                assert!(
+                    error.get_deserialized().abs() < 1e-2,
+                    "{} {:?}",
+                    zoom,
+                    point
+                );
+            }
+        }
+    }
+    #[test]
+    fn deep_zoom_around_a_point() {
+        let screen = vec2(1000.0, 500.0);
+        let mut camera = camera_at(1.5, 1.5f64.sin(), 100.0);
+        for _ in 0..100 {
+            camera.zoom_at(screen / 2.0, 2.0, screen);
+        }
+        let center = camera.world_to_screen(&BigVec2::new_d(1.5, 1.5f64.sin()), screen);
+        assert!((center - screen / 2.0).length() < 1e-2);
+        // This close sin is a straight line, any jitter would bend it
+        let polylines = sample_function(&camera, screen, f64::sin, 5.0);
+        assert_eq!(polylines.len(), 1);
+        for segment in polylines[0].windows(2) {
+            let slope = (segment[1].y - segment[0].y) / (segment[1].x - segment[0].x);
+            assert!((slope + 1.5f32.cos()).abs() < 1e-2, "{}", slope);
+        }
+    }
+    #[test]
+    fn adaptive_respects_budget() {
+        let screen = vec2(1000.0, 500.0);
+        let calls = Cell::new(0);
+        let function = |x: f64| {
+            calls.set(calls.get() + 1);
+            (1.0 / x).sin()
+        };
+        let polylines = sample_adaptive(&camera_at(0.0, 0.0, 400.0), screen, function, 3000);
+        assert!(calls.get() <= 3000);
+        assert!(!polylines.is_empty());
+        // A straight line should not need more than the initial pass
+        calls.set(0);
+        let function = |x: f64| {
+            calls.set(calls.get() + 1);
+            2.0 * x + 1.0
+        };
+        sample_adaptive(&camera_at(0.0, 0.0, 100.0), screen, function, 3000);
+        assert!(calls.get() <= 300);
+    }
+    #[test]
+    fn adaptive_refines_curvature() {
+        let screen = vec2(1000.0, 500.0);
+        let camera = camera_at(0.0, 0.0, 100.0);
+        let function = |x: f64| 2.0 * (3.0 * x).sin();
+        let polylines = sample_adaptive(&camera, screen, function, 4000);
+        let total = polylines.iter().map(|x| x.len()).sum::<usize>();
+        let uniform = sample_function(&camera, screen, function, 8.0);
+        assert!(total > uniform.iter().map(|x| x.len()).sum::<usize>());
+    }
+    #[test]
+    fn adaptive_breaks_at_asymptotes() {
+        let screen = vec2(1000.0, 500.0);
+        let polylines = sample_adaptive(&camera_at(0.0, 0.0, 100.0), screen, f64::tan, 4000);
+        // Of the branches within ±5 units only the three around ±pi/2 reach the screen
+        assert_eq!(polylines.len(), 3);
+        for polyline in polylines.iter() {
+            for segment in polyline.windows(2) {
+                // Each branch of tan is increasing, so screen y only ever goes up the screen
+                assert!(segment[1].y <= segment[0].y + 1e-3);
+            }
+        }
+        let steps = sample_adaptive(&camera_at(0.0, 0.0, 100.0), screen, f64::floor, 4000);
+        // Only the steps from -2 to 2 are on screen
+        assert_eq!(steps.len(), 5);
+    }
+    #[test]
+    fn parametric_circle_closes() {
+        let screen = vec2(1000.0, 500.0);
+        let circle = |t: f64| Some((2.0 * t.cos(), 2.0 * t.sin()));
+        let range = (0.0, std::f64::consts::TAU);
+        let polylines = sample_parametric(
+            &camera_at(0.0, 0.0, 100.0),
+            screen,
+            circle,
+            range,
+            0.5,
+            4000,
+        );
+        assert_eq!(polylines.len(), 1);
+        let center = screen / 2.0;
+        assert!(polylines[0]
+            .iter()
+            .all(|x| (x.distance(center) - 200.0).abs() < 1e-2));
+        assert!(polylines[0][0].distance(*polylines[0].last().unwrap()) < 1e-2);
+        // Chords within a quarter pixel of a radius 200 circle are about 20 pixels long
+        assert!(polylines[0].len() > 50);
+    }
+    #[test]
+    fn dashes_follow_the_pattern() {
+        let polyline = vec![vec2(0.0, 0.0), vec2(10.0, 0.0), vec2(10.0, 10.0)];
+        let pieces = dash_polyline(&polyline, Some((4.0, 2.0)));
+        let drawn = pieces.iter().map(|x| x.0.distance(x.1)).sum::<f32>();
+        // 20 pixels of 4 on / 2 off: [0, 4] [6, 10] [12, 16] [18, 20]
+        assert!((drawn - 14.0).abs() < 1e-4);
+        assert_eq!(dash_polyline(&polyline, None).len(), 2);
+    }
+    #[test]
+    fn breaks_on_undefined_values() {
+        let screen = vec2(1000.0, 500.0);
+        let polylines = sample_function(&camera_at(0.0, 0.0, 100.0), screen, f64::ln, 5.0);
+        assert_eq!(polylines.len(), 1);
+        assert!(polylines[0].iter().all(|x| x.x >= 500.0));
+    }
+}
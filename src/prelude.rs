@@ -0,0 +1,100 @@
+//! The vector, rectangle and color types the library draws with.
+//!
+//! Vectors come from glam, the crate macroquad builds on, so they are the window's own type.
+//! With the `ui` feature rectangles and colors are macroquad's as well. Without it plain
+//! stand-ins with the same fields and constructors are used, so the library builds without
+//! the window crate.
+pub use glam::{vec2, Vec2};
+#[cfg(feature = "ui")]
+pub use macroquad::color::{Color, BLACK, DARKGRAY, LIGHTGRAY, WHITE};
+#[cfg(feature = "ui")]
+pub use macroquad::math::Rect;
+
+/// A color with every channel in [0, 1].
+#[cfg(not(feature = "ui"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Color {
+    /// Red
+    pub r: f32,
+    /// Green
+    pub g: f32,
+    /// Blue
+    pub b: f32,
+    /// Opacity
+    pub a: f32,
+}
+#[cfg(not(feature = "ui"))]
+impl Color {
+    /// A color from its channels.
+    pub const fn new(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color { r, g, b, a }
+    }
+}
+#[cfg(not(feature = "ui"))]
+impl From<[f32; 4]> for Color {
+    fn from(channels: [f32; 4]) -> Color {
+        Color::new(channels[0], channels[1], channels[2], channels[3])
+    }
+}
+#[cfg(not(feature = "ui"))]
+impl From<Color> for [f32; 4] {
+    fn from(color: Color) -> [f32; 4] {
+        [color.r, color.g, color.b, color.a]
+    }
+}
+/// Opaque black.
+#[cfg(not(feature = "ui"))]
+pub const BLACK: Color = Color::new(0.0, 0.0, 0.0, 1.0);
+/// Opaque white.
+#[cfg(not(feature = "ui"))]
+pub const WHITE: Color = Color::new(1.0, 1.0, 1.0, 1.0);
+/// The gray macroquad calls `DARKGRAY`.
+#[cfg(not(feature = "ui"))]
+pub const DARKGRAY: Color = Color::new(0.31, 0.31, 0.31, 1.0);
+/// The gray macroquad calls `LIGHTGRAY`.
+#[cfg(not(feature = "ui"))]
+pub const LIGHTGRAY: Color = Color::new(0.78, 0.78, 0.78, 1.0);
+
+/// An axis aligned rectangle given by its top left corner and size.
+#[cfg(not(feature = "ui"))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    /// Left edge
+    pub x: f32,
+    /// Top edge
+    pub y: f32,
+    /// Width
+    pub w: f32,
+    /// Height
+    pub h: f32,
+}
+#[cfg(not(feature = "ui"))]
+impl Rect {
+    /// A rectangle from its top left corner and size.
+    pub const fn new(x: f32, y: f32, w: f32, h: f32) -> Rect {
+        Rect { x, y, w, h }
+    }
+    /// The top left corner.
+    pub fn point(&self) -> Vec2 {
+        vec2(self.x, self.y)
+    }
+    /// The width and height.
+    pub fn size(&self) -> Vec2 {
+        vec2(self.w, self.h)
+    }
+    /// The right edge.
+    pub fn right(&self) -> f32 {
+        self.x + self.w
+    }
+    /// The bottom edge.
+    pub fn bottom(&self) -> f32 {
+        self.y + self.h
+    }
+    /// Whether `point` is inside, the right and bottom edges are outside.
+    pub fn contains(&self, point: Vec2) -> bool {
+        (point.x >= self.x)
+            && (point.x < self.right())
+            && (point.y >= self.y)
+            && (point.y < self.bottom())
+    }
+}
@@ -0,0 +1,455 @@
+//! Rendering a [`Scene`] into an anti-aliased PNG without a window.
+use crate::plotter;
+use crate::prelude::*;
+use crate::scene::{self, Primitive, Scene};
+use image::codecs::png::PngEncoder;
+use image::{ColorType, ImageEncoder};
+use std::sync::OnceLock;
+
+/// Where the app writes PNG exports.
+pub const EXPORT_PATH: &str = "graph.png";
+// The font macroquad draws with by default, so exports match the window
+const FONT_DATA: &[u8] = include_bytes!("../assets/ProggyClean.ttf");
+const FONT_ERROR: &str = "Could not load the built in font";
+//
+// Lines thinner than a pixel are drawn one pixel wide and fainter instead
+const MIN_THICKNESS: f32 = 1.0;
+
+/// Software renderer for scenes, for exporting images without a window or GPU. Shapes
+/// are anti-aliased by how much of each pixel they cover
+pub struct Canvas {
+    /// Width in pixels
+    pub width: usize,
+    /// Height in pixels
+    pub height: usize,
+    pixels: Vec<Color>,
+    // Coverage of the primitive being drawn, blended into the pixels once it is
+    // complete so overlapping segments of one curve don't darken their joins
+    coverage: Vec<f32>,
+    // Area of `coverage` that has been touched, as x and y ranges
+    dirty: Option<(usize, usize, usize, usize)>,
+}
+
+// Helper Functions
+fn font() -> &'static fontdue::Font {
+    static FONT: OnceLock<fontdue::Font> = OnceLock::new();
+    FONT.get_or_init(|| {
+        fontdue::Font::from_bytes(FONT_DATA, fontdue::FontSettings::default()).expect(FONT_ERROR)
+    })
+}
+// Width and the height above the baseline, like `measure_text`
+fn measure(text: &str, size: f32) -> (f32, f32) {
+    let font = font();
+    text.chars().fold((0.0, 0.0), |(width, height), char| {
+        let metrics = font.metrics(char, size);
+        let top = (metrics.ymin + metrics.height as i32) as f32;
+        (width + metrics.advance_width, f32::max(height, top))
+    })
+}
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let direction = end - start;
+    let length_squared = direction.length_squared();
+    let t = match length_squared > 0.0 {
+        true => ((point - start).dot(direction) / length_squared).clamp(0.0, 1.0),
+        false => 0.0,
+    };
+    point.distance(start + direction * t)
+}
+// Signed distance to a convex polygon, negative inside
+fn distance_to_polygon(point: Vec2, vertices: &[Vec2]) -> f32 {
+    let clockwise = match vertices.len() >= 3 {
+        true => (vertices[1] - vertices[0]).perp_dot(vertices[2] - vertices[0]) > 0.0,
+        false => return f32::INFINITY,
+    };
+    let mut distance = f32::NEG_INFINITY;
+    for (index, start) in vertices.iter().enumerate() {
+        let end = vertices[(index + 1) % vertices.len()];
+        let edge = (end - *start).normalize_or_zero();
+        let outward = match clockwise {
+            true => vec2(edge.y, -edge.x),
+            false => vec2(-edge.y, edge.x),
+        };
+        distance = distance.max((point - *start).dot(outward));
+    }
+    distance
+}
+fn closed(mut points: Vec<Vec2>) -> Vec<Vec2> {
+    if let Some(first) = points.first() {
+        points.push(*first);
+    }
+    points
+}
+fn to_byte(x: f32) -> u8 {
+    (x.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+//
+
+impl Canvas {
+    /// A canvas filled with `background`.
+    pub fn new(width: usize, height: usize, background: Color) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: vec![background; width * height],
+            coverage: vec![0.0; width * height],
+            dirty: None,
+        }
+    }
+    /// The color at column `x` of row `y`, counted from the top left.
+    pub fn get_pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+    // Pixel ranges covering `min..max`, or None when it is off the canvas
+    fn clip(&self, min: Vec2, max: Vec2) -> Option<(usize, usize, usize, usize)> {
+        let (width, height) = (self.width as f32, self.height as f32);
+        if max.x < 0.0 || max.y < 0.0 || min.x >= width || min.y >= height || min.x.is_nan() {
+            return None;
+        }
+        let x = (min.x.max(0.0) as usize, (max.x.ceil().min(width)) as usize);
+        let y = (min.y.max(0.0) as usize, (max.y.ceil().min(height)) as usize);
+        Some((x.0, x.1, y.0, y.1))
+    }
+    // Runs `coverage_at` for the center of every pixel in `min..max`, keeping the largest
+    // coverage of each pixel
+    fn cover(&mut self, min: Vec2, max: Vec2, coverage_at: impl Fn(Vec2) -> f32) {
+        let (x0, x1, y0, y1) = match self.clip(min, max) {
+            Some(x) => x,
+            None => return,
+        };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let value = coverage_at(vec2(x as f32 + 0.5, y as f32 + 0.5));
+                let index = y * self.width + x;
+                self.coverage[index] = self.coverage[index].max(value);
+            }
+        }
+        self.touch((x0, x1, y0, y1));
+    }
+    fn touch(&mut self, (x0, x1, y0, y1): (usize, usize, usize, usize)) {
+        self.dirty = Some(match self.dirty {
+            Some((a, b, c, d)) => (a.min(x0), b.max(x1), c.min(y0), d.max(y1)),
+            None => (x0, x1, y0, y1),
+        });
+    }
+    fn blend(&mut self, color: Color) {
+        let (x0, x1, y0, y1) = match self.dirty.take() {
+            Some(x) => x,
+            None => return,
+        };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                let index = y * self.width + x;
+                let alpha = color.a * std::mem::take(&mut self.coverage[index]).min(1.0);
+                let pixel = &mut self.pixels[index];
+                pixel.r += (color.r - pixel.r) * alpha;
+                pixel.g += (color.g - pixel.g) * alpha;
+                pixel.b += (color.b - pixel.b) * alpha;
+                pixel.a += (1.0 - pixel.a) * alpha;
+            }
+        }
+    }
+    fn cover_segment(&mut self, start: Vec2, end: Vec2, thickness: f32) {
+        let radius = thickness.max(MIN_THICKNESS) / 2.0;
+        let strength = thickness.min(MIN_THICKNESS) / MIN_THICKNESS;
+        let margin = Vec2::splat(radius + 1.0);
+        self.cover(start.min(end) - margin, start.max(end) + margin, |point| {
+            (radius - distance_to_segment(point, start, end) + 0.5).clamp(0.0, 1.0) * strength
+        });
+    }
+    fn cover_polyline(&mut self, points: &[Vec2], thickness: f32, pattern: Option<(f32, f32)>) {
+        for (start, end) in plotter::dash_polyline(points, pattern) {
+            self.cover_segment(start, end, thickness);
+        }
+    }
+    fn draw_polygon(
+        &mut self,
+        center: Vec2,
+        sides: u8,
+        radius: f32,
+        rotation: f32,
+        fill: Option<Color>,
+        stroke: Option<(f32, Color)>,
+    ) {
+        let vertices = scene::polygon_points(center, sides, radius, rotation);
+        let is_circle = sides == scene::CIRCLE_SIDES;
+        if let Some(color) = fill {
+            let margin = Vec2::splat(radius + 1.0);
+            self.cover(center - margin, center + margin, |point| {
+                let distance = match is_circle {
+                    true => point.distance(center) - radius,
+                    false => distance_to_polygon(point, &vertices),
+                };
+                (0.5 - distance).clamp(0.0, 1.0)
+            });
+            self.blend(color);
+        }
+        if let Some((thickness, color)) = stroke {
+            match is_circle {
+                true => {
+                    let half = thickness.max(MIN_THICKNESS) / 2.0;
+                    let margin = Vec2::splat(radius + half + 1.0);
+                    self.cover(center - margin, center + margin, |point| {
+                        let distance = (point.distance(center) - radius).abs();
+                        (half - distance + 0.5).clamp(0.0, 1.0)
+                    });
+                }
+                false => self.cover_polyline(&closed(vertices), thickness, None),
+            }
+            self.blend(color);
+        }
+    }
+    fn draw_rectangle(&mut self, rect: Rect, fill: Option<Color>, stroke: Option<(f32, Color)>) {
+        if let Some(color) = fill {
+            // Exact overlap of the pixel square, so regions built from touching rectangles
+            // don't leave seams
+            let (x0, x1, y0, y1) = match self.clip(rect.point(), rect.point() + rect.size()) {
+                Some(x) => x,
+                None => return,
+            };
+            for y in y0..y1 {
+                let height = (rect.bottom().min(y as f32 + 1.0) - rect.y.max(y as f32)).max(0.0);
+                for x in x0..x1 {
+                    let width = (rect.right().min(x as f32 + 1.0) - rect.x.max(x as f32)).max(0.0);
+                    let alpha = color.a * width * height;
+                    let pixel = &mut self.pixels[y * self.width + x];
+                    pixel.r += (color.r - pixel.r) * alpha;
+                    pixel.g += (color.g - pixel.g) * alpha;
+                    pixel.b += (color.b - pixel.b) * alpha;
+                    pixel.a += (1.0 - pixel.a) * alpha;
+                }
+            }
+        }
+        if let Some((thickness, color)) = stroke {
+            // Inside the rectangle like `draw_rectangle_lines`
+            let inset = rect.w.min(rect.h).min(thickness) / 2.0;
+            let inner = Rect::new(
+                rect.x + inset,
+                rect.y + inset,
+                rect.w - inset * 2.0,
+                rect.h - inset * 2.0,
+            );
+            let corners = vec![
+                inner.point(),
+                vec2(inner.right(), inner.y),
+                inner.point() + inner.size(),
+                vec2(inner.x, inner.bottom()),
+            ];
+            self.cover_polyline(&closed(corners), thickness, None);
+            self.blend(color);
+        }
+    }
+    fn draw_text(&mut self, text: &str, corner: Vec2, baseline: f32, size: f32, color: Color) {
+        let font = font();
+        let mut pen = corner.x;
+        for char in text.chars() {
+            let (metrics, bitmap) = font.rasterize(char, size);
+            let left = pen.round() as i64 + metrics.xmin as i64;
+            let top = (baseline.round() as i64) - (metrics.ymin as i64 + metrics.height as i64);
+            let (width, height) = (self.width as i64, self.height as i64);
+            for row in 0..metrics.height as i64 {
+                for column in 0..metrics.width as i64 {
+                    let (x, y) = (left + column, top + row);
+                    if x < 0 || y < 0 || x >= width || y >= height {
+                        continue;
+                    }
+                    let value = bitmap[(row * metrics.width as i64 + column) as usize];
+                    let index = (y * width + x) as usize;
+                    self.coverage[index] = self.coverage[index].max(value as f32 / 255.0);
+                }
+            }
+            let min = vec2(left as f32, top as f32);
+            let max = min + vec2(metrics.width as f32, metrics.height as f32);
+            if let Some(bounds) = self.clip(min, max) {
+                self.touch(bounds);
+            }
+            pen += metrics.advance_width;
+        }
+        self.blend(color);
+    }
+    /// Draws one primitive over what is already there.
+    pub fn draw(&mut self, primitive: &Primitive) {
+        match primitive {
+            Primitive::Polyline {
+                points,
+                thickness,
+                color,
+                dash,
+            } => {
+                self.cover_polyline(points, *thickness, dash.get_pattern(*thickness));
+                self.blend(*color);
+            }
+            Primitive::Rectangle { rect, fill, stroke } => {
+                self.draw_rectangle(*rect, *fill, *stroke)
+            }
+            Primitive::Polygon {
+                center,
+                sides,
+                radius,
+                rotation,
+                fill,
+                stroke,
+            } => self.draw_polygon(*center, *sides, *radius, *rotation, *fill, *stroke),
+            Primitive::Text {
+                text,
+                position,
+                size,
+                color,
+                anchor,
+            } => {
+                let (width, height) = measure(text, *size);
+                let corner = scene::anchor_text(*position, width, height, *anchor);
+                self.draw_text(text, corner, corner.y + height, *size, *color);
+            }
+        }
+    }
+    /// 8 bit RGBA rows from the top
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|x| [to_byte(x.r), to_byte(x.g), to_byte(x.b), to_byte(x.a)])
+            .collect()
+    }
+    /// The canvas encoded as a PNG file.
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        PngEncoder::new(&mut bytes)
+            .write_image(
+                &self.to_rgba8(),
+                self.width as u32,
+                self.height as u32,
+                ColorType::Rgba8,
+            )
+            .map_err(|x| x.to_string())?;
+        Ok(bytes)
+    }
+}
+
+/// Draws every primitive of the scene onto a canvas of the scene's size and background.
+pub fn render(scene: &Scene) -> Canvas {
+    let (width, height) = (
+        scene.size.x.max(0.0) as usize,
+        scene.size.y.max(0.0) as usize,
+    );
+    let mut canvas = Canvas::new(width, height, scene.background);
+    for primitive in scene.primitives.iter() {
+        canvas.draw(primitive);
+    }
+    canvas
+}
+/// Renders the scene and writes it to `path` as a PNG.
+pub fn export(scene: &Scene, path: &str) -> Result<(), String> {
+    let bytes = render(scene).to_png()?;
+    std::fs::write(path, bytes).map_err(|x| format!("Could not save {}: {}", path, x))
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::big_number::{BigNumber, BigVec2};
+    use crate::camera::Camera;
+    use crate::entries::EntryList;
+    use crate::grid;
+    use crate::scene::TextAnchor;
+    // Channels may differ by this much out of 255 before a pixel counts as changed, so
+    // small floating point differences between machines don't fail the comparison
+    const CHANNEL_TOLERANCE: u8 = 3;
+    // Share of pixels allowed to change
+    const PIXEL_TOLERANCE: f32 = 0.002;
+    // Compares against `golden/<name>.png`, run with `UPDATE_GOLDEN=1` to rewrite the
+    // image after an intended change
+    fn check_golden(name: &str, canvas: &Canvas) {
+        let path = format!("{}/golden/{}.png", env!("CARGO_MANIFEST_DIR"), name);
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, canvas.to_png().unwrap()).unwrap();
+            return;
+        }
+        let golden = image::open(&path)
+            .unwrap_or_else(|x| panic!("{}: {}, run with UPDATE_GOLDEN=1", path, x))
+            .to_rgba8();
+        assert_eq!(
+            (golden.width() as usize, golden.height() as usize),
+            (canvas.width, canvas.height)
+        );
+        let changed = golden
+            .as_raw()
+            .chunks(4)
+            .zip(canvas.to_rgba8().chunks(4))
+            .filter(|(a, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+            })
+            .count();
+        let allowed = (PIXEL_TOLERANCE * (canvas.width * canvas.height) as f32) as usize;
+        assert!(
+            changed <= allowed,
+            "{} of {} pixels differ from {}",
+            changed,
+            canvas.width * canvas.height,
+            path
+        );
+    }
+    #[test]
+    fn shapes_are_antialiased() {
+        let mut scene = Scene::new(vec2(40.0, 20.0));
+        scene.line(vec2(0.0, 5.0), vec2(40.0, 5.0), 2.0, BLACK);
+        scene.rectangle(
+            Rect::new(10.5, 10.0, 10.0, 5.0),
+            Some(Color::new(1.0, 0.0, 0.0, 1.0)),
+            None,
+        );
+        scene.circle(
+            vec2(30.0, 14.0),
+            4.0,
+            Some(Color::new(0.0, 0.0, 1.0, 1.0)),
+            None,
+        );
+        let canvas = render(&scene);
+        // The line covers rows 4 and 5 fully and fades out either side
+        assert_eq!(canvas.get_pixel(20, 4), BLACK);
+        assert_eq!(canvas.get_pixel(20, 5), BLACK);
+        assert_eq!(canvas.get_pixel(20, 8), WHITE);
+        // Half of the first rectangle column is covered
+        let edge = canvas.get_pixel(10, 12);
+        assert!((edge.g - 0.5).abs() < 1e-6 && edge.r == 1.0);
+        assert_eq!(canvas.get_pixel(15, 12).g, 0.0);
+        assert_eq!(canvas.get_pixel(30, 14).r, 0.0);
+        let rim = canvas.get_pixel(33, 11);
+        assert!(rim.r > 0.0 && rim.r < 1.0);
+        let png = canvas.to_png().unwrap();
+        assert!(png.starts_with(b"\x89PNG"));
+    }
+    #[test]
+    fn text_is_drawn() {
+        let mut scene = Scene::new(vec2(60.0, 30.0));
+        scene.text("f1", vec2(30.0, 15.0), 18.0, BLACK, TextAnchor::Center);
+        let canvas = render(&scene);
+        let inked = canvas.pixels.iter().filter(|x| x.r < 0.5).count();
+        assert!(inked > 10);
+        // Centered text stays within its measured box
+        let (width, height) = measure("f1", 18.0);
+        let corner = scene::anchor_text(vec2(30.0, 15.0), width, height, TextAnchor::Center);
+        for y in 0..canvas.height {
+            for x in 0..canvas.width {
+                let outside = (x as f32) < corner.x - 1.0 || (x as f32) > corner.x + width + 1.0;
+                if outside {
+                    assert_eq!(canvas.get_pixel(x, y), WHITE);
+                }
+            }
+        }
+    }
+    #[test]
+    fn graph_matches_golden_image() {
+        let camera = Camera {
+            position: BigVec2::new_d(0.0, 0.0),
+            number_distance: BigNumber::new_d(50.0),
+        };
+        let mut entries = EntryList::new();
+        entries.add("sin(x)");
+        entries.add("y < x");
+        let mut scene = Scene::new(vec2(320.0, 240.0));
+        grid::push_to_scene(&grid::compute(&camera, scene.size), &mut scene);
+        entries.push_to_scene(&mut scene, &camera, 0.0);
+        entries.push_legend(&mut scene);
+        check_golden("graph", &render(&scene));
+    }
+}
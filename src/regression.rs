@@ -0,0 +1,580 @@
+//! Fitting models such as lines, polynomials or custom expressions to a list of points.
+use crate::algebra_parser::{
+    evaluator::{self, Context},
+    generate_all_ast, Node, CONSTANTS,
+};
+#[cfg(feature = "ui")]
+use crate::entries::{EntryKind, EntryList};
+#[cfg(feature = "ui")]
+use crate::prelude::*;
+#[cfg(feature = "ui")]
+use macroquad::ui::{hash, root_ui, widgets};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+const TOO_FEW_POINTS_ERROR: &str = "Not enough points for this model";
+const SINGULAR_ERROR: &str = "Points do not determine the model";
+const MODEL_ERROR: &str = "Model could not be parsed";
+const NO_PARAMETERS_ERROR: &str = "Model has no parameters to fit";
+const POSITIVE_X_ERROR: &str = "Model needs every x to be positive";
+#[cfg(feature = "ui")]
+const NO_POINTS_ERROR: &str = "Pick a point list first";
+//
+const MAX_ITERATIONS: usize = 200;
+// Attempts at a smaller step before an iteration gives up
+const MAX_DAMPING_TRIES: usize = 12;
+const INITIAL_DAMPING: f64 = 1e-3;
+// Relative decrease of the squared error below which the fit counts as converged
+const TOLERANCE: f64 = 1e-14;
+// Parameter names for templates, `e` and `x` are taken
+const PARAMETER_NAMES: [&str; 12] = ["a", "b", "c", "d", "f", "g", "h", "k", "m", "n", "p", "q"];
+#[cfg(feature = "ui")]
+const MAX_DEGREE: usize = PARAMETER_NAMES.len() - 1;
+// Residuals shown in the panel, the rest are summarised
+#[cfg(feature = "ui")]
+const SHOWN_RESIDUALS: usize = 8;
+
+/// The family of curves a fit picks from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Model {
+    /// `a*x + b`
+    Linear,
+    /// A polynomial of the given degree
+    Polynomial(usize),
+    /// `a*e^(b*x)`
+    Exponential,
+    /// `a + b*ln(x)`
+    Logarithmic,
+    /// `a*x^b`
+    Power,
+    /// `a*sin(b*x + c) + d`
+    Sinusoidal,
+    /// Any expression in x, every other variable in it is a parameter
+    Custom(String),
+}
+/// The result of fitting a model.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fit {
+    /// The model with the fitted parameters filled in, ready to add as an entry
+    pub expression: String,
+    /// Every parameter with its fitted value
+    pub parameters: Vec<(String, f64)>,
+    /// Coefficient of determination, 1 for a perfect fit
+    pub r_squared: f64,
+    /// `y - f(x)` for every point, in the order of the points
+    pub residuals: Vec<f64>,
+}
+/// The window for fitting a model to a point list.
+#[cfg(feature = "ui")]
+pub struct RegressionPanel {
+    list_index: usize,
+    model_index: usize,
+    degree_text: String,
+    custom_text: String,
+    result: Option<Result<Fit, &'static str>>,
+}
+
+// Helper Functions
+// Gaussian elimination with partial pivoting, `None` when the matrix is singular
+fn solve_linear(mut matrix: Vec<Vec<f64>>, mut vector: Vec<f64>) -> Option<Vec<f64>> {
+    let size = vector.len();
+    for column in 0..size {
+        let pivot = (column..size).max_by(|a, b| {
+            matrix[*a][column]
+                .abs()
+                .total_cmp(&matrix[*b][column].abs())
+        })?;
+        if matrix[pivot][column].abs() <= 1e-300 || matrix[pivot][column].is_nan() {
+            return None;
+        }
+        matrix.swap(column, pivot);
+        vector.swap(column, pivot);
+        for row in column + 1..size {
+            let factor = matrix[row][column] / matrix[column][column];
+            let (above, below) = matrix.split_at_mut(row);
+            for (target, source) in below[0][column..].iter_mut().zip(&above[column][column..]) {
+                *target -= factor * source;
+            }
+            vector[row] -= factor * vector[column];
+        }
+    }
+    let mut solution = vec![0.0; size];
+    for row in (0..size).rev() {
+        let sum = (row + 1..size)
+            .map(|k| matrix[row][k] * solution[k])
+            .sum::<f64>();
+        solution[row] = (vector[row] - sum) / matrix[row][row];
+    }
+    match solution.iter().all(|x| x.is_finite()) {
+        true => Some(solution),
+        false => None,
+    }
+}
+// Coefficients of `sum c_k basis_k(x)` through the normal equations
+fn linear_least_squares(
+    points: &[(f64, f64)],
+    basis: &[&dyn Fn(f64) -> f64],
+) -> Result<Vec<f64>, &'static str> {
+    if points.len() < basis.len() {
+        return Err(TOO_FEW_POINTS_ERROR);
+    }
+    let size = basis.len();
+    let mut matrix = vec![vec![0.0; size]; size];
+    let mut vector = vec![0.0; size];
+    for (x, y) in points.iter() {
+        let values = basis.iter().map(|f| f(*x)).collect::<Vec<f64>>();
+        for i in 0..size {
+            vector[i] += values[i] * y;
+            for j in 0..size {
+                matrix[i][j] += values[i] * values[j];
+            }
+        }
+    }
+    solve_linear(matrix, vector).ok_or(SINGULAR_ERROR)
+}
+fn squared_error<F: FnMut(f64, &[f64]) -> f64>(
+    model: &mut F,
+    points: &[(f64, f64)],
+    parameters: &[f64],
+) -> f64 {
+    points
+        .iter()
+        .map(|(x, y)| (y - model(*x, parameters)).powi(2))
+        .sum()
+}
+// Levenberg-Marquardt: Gauss-Newton steps on the squared error, damped towards gradient
+// descent whenever a step would make the fit worse. The Jacobian is taken numerically
+fn levenberg_marquardt<F: FnMut(f64, &[f64]) -> f64>(
+    mut model: F,
+    points: &[(f64, f64)],
+    initial: &[f64],
+) -> Result<Vec<f64>, &'static str> {
+    if points.len() < initial.len() {
+        return Err(TOO_FEW_POINTS_ERROR);
+    }
+    let size = initial.len();
+    let mut parameters = initial.to_vec();
+    let mut error = squared_error(&mut model, points, &parameters);
+    if !error.is_finite() {
+        return Err(SINGULAR_ERROR);
+    }
+    let mut damping = INITIAL_DAMPING;
+    for _ in 0..MAX_ITERATIONS {
+        let mut normal = vec![vec![0.0; size]; size];
+        let mut gradient = vec![0.0; size];
+        for (x, y) in points.iter() {
+            let value = model(*x, &parameters);
+            let row = (0..size)
+                .map(|j| {
+                    let h = 1e-7 * parameters[j].abs().max(1.0);
+                    let mut shifted = parameters.clone();
+                    shifted[j] += h;
+                    (model(*x, &shifted) - value) / h
+                })
+                .collect::<Vec<f64>>();
+            for i in 0..size {
+                gradient[i] += row[i] * (y - value);
+                for j in 0..size {
+                    normal[i][j] += row[i] * row[j];
+                }
+            }
+        }
+        let mut improved = None;
+        for _ in 0..MAX_DAMPING_TRIES {
+            let mut damped = normal.clone();
+            for i in 0..size {
+                damped[i][i] += damping * normal[i][i].max(1e-12);
+            }
+            let step = match solve_linear(damped, gradient.clone()) {
+                Some(x) => x,
+                None => {
+                    damping *= 10.0;
+                    continue;
+                }
+            };
+            let candidate = parameters
+                .iter()
+                .zip(step.iter())
+                .map(|(a, b)| a + b)
+                .collect::<Vec<f64>>();
+            let candidate_error = squared_error(&mut model, points, &candidate);
+            if candidate_error.is_finite() && candidate_error <= error {
+                improved = Some((candidate, candidate_error));
+                damping = (damping / 10.0).max(1e-12);
+                break;
+            }
+            damping *= 10.0;
+        }
+        let (candidate, candidate_error) = match improved {
+            Some(x) => x,
+            None => break,
+        };
+        let decrease = error - candidate_error;
+        parameters = candidate;
+        error = candidate_error;
+        if decrease <= TOLERANCE * error.max(1e-300) {
+            break;
+        }
+    }
+    Ok(parameters)
+}
+// Rough starting values for the nonlinear models, from fits of their linearised forms
+fn initial_guess(model: &Model, points: &[(f64, f64)]) -> Vec<Vec<f64>> {
+    let mean = points.iter().map(|x| x.1).sum::<f64>() / points.len() as f64;
+    match model {
+        Model::Exponential => {
+            let logarithm: Vec<(f64, f64)> = points.iter().map(|(x, y)| (*x, y.ln())).collect();
+            match linear_least_squares(&logarithm, &[&|_| 1.0, &|x| x]) {
+                Ok(x) if (points.iter().all(|x| x.1 > 0.0)) => vec![vec![x[0].exp(), x[1]]],
+                _ => vec![vec![mean, 0.0], vec![mean, 0.1], vec![mean, -0.1]],
+            }
+        }
+        Model::Power => {
+            let logarithm: Vec<(f64, f64)> = points.iter().map(|(x, y)| (x.ln(), y.ln())).collect();
+            match linear_least_squares(&logarithm, &[&|_| 1.0, &|x| x]) {
+                Ok(x) if (points.iter().all(|x| x.1 > 0.0)) => vec![vec![x[0].exp(), x[1]]],
+                _ => vec![vec![mean, 1.0], vec![mean, -1.0]],
+            }
+        }
+        // The frequency comes from how often the data crosses its mean
+        Model::Sinusoidal => {
+            let mut sorted = points.to_vec();
+            sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+            let span = sorted.last().unwrap().0 - sorted[0].0;
+            let crossings = sorted
+                .windows(2)
+                .filter(|x| (x[0].1 - mean) * (x[1].1 - mean) < 0.0)
+                .count()
+                .max(1);
+            let (low, high) = sorted
+                .iter()
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), x| {
+                    (low.min(x.1), high.max(x.1))
+                });
+            let amplitude = ((high - low) / 2.0).max(1e-12);
+            let frequency = match span > 0.0 {
+                true => PI * crossings as f64 / span,
+                false => 1.0,
+            };
+            let mut guesses = Vec::new();
+            for scale in [1.0, 0.5, 2.0] {
+                for phase in [0.0, PI / 2.0, PI, 3.0 * PI / 2.0] {
+                    guesses.push(vec![amplitude, frequency * scale, phase, mean]);
+                }
+            }
+            guesses
+        }
+        _ => Vec::new(),
+    }
+}
+fn parse_model(template: &str) -> Result<Node, &'static str> {
+    let data = HashMap::from([("model", template.to_string())]);
+    generate_all_ast(&data)
+        .remove("model")
+        .and_then(|x| x.ok())
+        .ok_or(MODEL_ERROR)
+}
+//
+
+impl Model {
+    /// Names of the models in the order the panel lists them.
+    pub const NAMES: [&'static str; 7] = [
+        "Linear",
+        "Polynomial",
+        "Exponential",
+        "Logarithmic",
+        "Power",
+        "Sinusoidal",
+        "Custom",
+    ];
+    /// The model as an expression, its parameters are every variable other than x
+    pub fn get_template(&self) -> String {
+        match self {
+            Model::Linear => "a*x + b".to_string(),
+            Model::Polynomial(degree) => (0..=*degree)
+                .rev()
+                .map(|power| match power {
+                    0 => PARAMETER_NAMES[power].to_string(),
+                    1 => format!("{}*x", PARAMETER_NAMES[power]),
+                    _ => format!("{}*x^{}", PARAMETER_NAMES[power], power),
+                })
+                .collect::<Vec<String>>()
+                .join(" + "),
+            Model::Exponential => "a*e^(b*x)".to_string(),
+            Model::Logarithmic => "a + b*ln(x)".to_string(),
+            Model::Power => "a*x^b".to_string(),
+            Model::Sinusoidal => "a*sin(b*x + c) + d".to_string(),
+            Model::Custom(x) => x.clone(),
+        }
+    }
+}
+/// Least squares fit of `model` to the points. Models that are linear in their parameters
+/// are solved directly, the others start from a few guesses and are refined with
+/// Levenberg-Marquardt
+pub fn fit(model: &Model, points: &[(f64, f64)], context: &Context) -> Result<Fit, &'static str> {
+    let node = parse_model(&model.get_template())?;
+    let names = node
+        .get_variables()
+        .into_iter()
+        .filter(|x| !(x == "x" || CONSTANTS.contains(&x.as_str())))
+        .collect::<Vec<String>>();
+    if names.is_empty() {
+        return Err(NO_PARAMETERS_ERROR);
+    }
+    if points.len() < names.len() {
+        return Err(TOO_FEW_POINTS_ERROR);
+    }
+    let needs_positive_x = matches!(model, Model::Logarithmic | Model::Power);
+    if needs_positive_x && points.iter().any(|x| x.0 <= 0.0) {
+        return Err(POSITIVE_X_ERROR);
+    }
+    let mut scratch = context.clone();
+    let mut evaluate = |x: f64, parameters: &[f64]| {
+        scratch.variables.insert("x".to_string(), x);
+        for (name, value) in names.iter().zip(parameters.iter()) {
+            scratch.variables.insert(name.clone(), *value);
+        }
+        evaluator::evaluate(&node, &scratch).unwrap_or(f64::NAN)
+    };
+    let parameters = match model {
+        Model::Linear => linear_least_squares(points, &[&|x| x, &|_| 1.0])?,
+        Model::Polynomial(degree) => {
+            let basis = (0..=*degree)
+                .map(|power| move |x: f64| x.powi(power as i32))
+                .collect::<Vec<_>>();
+            let basis = basis
+                .iter()
+                .map(|x| x as &dyn Fn(f64) -> f64)
+                .collect::<Vec<_>>();
+            // The template lists the highest power first but names it by its power
+            let solution = linear_least_squares(points, &basis)?;
+            names
+                .iter()
+                .map(|name| solution[PARAMETER_NAMES.iter().position(|x| x == name).unwrap()])
+                .collect()
+        }
+        Model::Logarithmic => linear_least_squares(points, &[&|_| 1.0, &|x| x.ln()])?,
+        _ => {
+            let mut guesses = initial_guess(model, points);
+            // Nothing is known about custom models, so a few signs and scales are tried
+            if guesses.is_empty() {
+                for value in [1.0, -1.0, 0.1, -0.1] {
+                    guesses.push(vec![value; names.len()]);
+                }
+            }
+            let mut best: Option<(f64, Vec<f64>)> = None;
+            for guess in guesses {
+                let parameters = match levenberg_marquardt(&mut evaluate, points, &guess) {
+                    Ok(x) => x,
+                    Err(_) => continue,
+                };
+                let error = squared_error(&mut evaluate, points, &parameters);
+                let better = match best {
+                    Some((x, _)) => error < x,
+                    None => error.is_finite(),
+                };
+                if better {
+                    best = Some((error, parameters));
+                }
+            }
+            best.ok_or(SINGULAR_ERROR)?.1
+        }
+    };
+    let residuals = points
+        .iter()
+        .map(|(x, y)| y - evaluate(*x, &parameters))
+        .collect::<Vec<f64>>();
+    let mean = points.iter().map(|x| x.1).sum::<f64>() / points.len() as f64;
+    let total = points.iter().map(|x| (x.1 - mean).powi(2)).sum::<f64>();
+    let residual_sum = residuals.iter().map(|x| x * x).sum::<f64>();
+    let r_squared = match total > 0.0 {
+        true => 1.0 - residual_sum / total,
+        false => (residual_sum == 0.0) as i32 as f64,
+    };
+    let parameters = names
+        .into_iter()
+        .zip(parameters)
+        .collect::<Vec<(String, f64)>>();
+    let values = parameters.iter().cloned().collect::<HashMap<String, f64>>();
+    Ok(Fit {
+        expression: node.substitute(&values).to_string(),
+        parameters,
+        r_squared,
+        residuals,
+    })
+}
+#[cfg(feature = "ui")]
+impl RegressionPanel {
+    /// A panel set to a linear fit of the first point list.
+    pub fn new() -> RegressionPanel {
+        RegressionPanel::default()
+    }
+    fn get_model(&self) -> Model {
+        match self.model_index {
+            0 => Model::Linear,
+            1 => Model::Polynomial(
+                self.degree_text
+                    .trim()
+                    .parse::<usize>()
+                    .unwrap_or(2)
+                    .clamp(1, MAX_DEGREE),
+            ),
+            2 => Model::Exponential,
+            3 => Model::Logarithmic,
+            4 => Model::Power,
+            5 => Model::Sinusoidal,
+            _ => Model::Custom(self.custom_text.clone()),
+        }
+    }
+    /// Draws the window, adding the fitted curve to `entries` when asked.
+    pub fn create_ui(&mut self, entries: &mut EntryList, position: Vec2) {
+        let lists = entries
+            .entries
+            .iter()
+            .filter(|x| x.get_kind() == EntryKind::Points)
+            .map(|x| (x.label.clone(), x.get_data_points(&entries.context)))
+            .collect::<Vec<_>>();
+        let mut fitted = false;
+        let mut add = false;
+        widgets::Window::new(hash!(), position, vec2(340.0, 400.0))
+            .label("Regression")
+            .movable(true)
+            .ui(&mut root_ui(), |ui| {
+                let labels = lists.iter().map(|x| x.0.as_str()).collect::<Vec<&str>>();
+                if labels.is_empty() {
+                    ui.label(None, "No point lists yet");
+                } else {
+                    self.list_index = self.list_index.min(labels.len() - 1);
+                    ui.combo_box(hash!(), "Data", &labels, &mut self.list_index);
+                }
+                ui.combo_box(hash!(), "Model", &Model::NAMES, &mut self.model_index);
+                match self.model_index {
+                    1 => ui.input_text(hash!(), "Degree", &mut self.degree_text),
+                    6 => ui.input_text(hash!(), "y =", &mut self.custom_text),
+                    _ => {}
+                }
+                if ui.button(None, "Fit") {
+                    fitted = true;
+                }
+                match self.result {
+                    Some(Ok(ref fit)) => {
+                        ui.label(None, &format!("y = {}", fit.expression));
+                        for (name, value) in fit.parameters.iter() {
+                            ui.label(None, &format!("{} = {}", name, value));
+                        }
+                        ui.label(None, &format!("R² = {}", fit.r_squared));
+                        ui.label(None, "Residuals:");
+                        for residual in fit.residuals.iter().take(SHOWN_RESIDUALS) {
+                            ui.label(None, &format!("  {}", residual));
+                        }
+                        if fit.residuals.len() > SHOWN_RESIDUALS {
+                            let rest = fit.residuals.len() - SHOWN_RESIDUALS;
+                            ui.label(None, &format!("  ... {} more", rest));
+                        }
+                        if ui.button(None, "Add curve") {
+                            add = true;
+                        }
+                    }
+                    Some(Err(error)) => ui.label(None, &format!("  ! {}", error)),
+                    None => {}
+                }
+            });
+        if fitted {
+            self.result = Some(match lists.get(self.list_index) {
+                Some((_, data)) => {
+                    let points = data.iter().map(|x| (x.x, x.y)).collect::<Vec<(f64, f64)>>();
+                    fit(&self.get_model(), &points, &entries.context)
+                }
+                None => Err(NO_POINTS_ERROR),
+            });
+        }
+        if let (true, Some(Ok(ref fit))) = (add, &self.result) {
+            entries.add(&fit.expression);
+        }
+    }
+}
+#[cfg(feature = "ui")]
+impl Default for RegressionPanel {
+    fn default() -> Self {
+        RegressionPanel {
+            list_index: 0,
+            model_index: 0,
+            degree_text: "2".to_string(),
+            custom_text: "a*exp(b*x) + c".to_string(),
+            result: None,
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entries::EntryList;
+    fn sample(f: impl Fn(f64) -> f64, xs: &[f64]) -> Vec<(f64, f64)> {
+        xs.iter().map(|x| (*x, f(*x))).collect()
+    }
+    fn value_of(fit: &Fit, name: &str) -> f64 {
+        fit.parameters.iter().find(|x| x.0 == name).unwrap().1
+    }
+    const XS: [f64; 9] = [0.5, 1.0, 1.5, 2.0, 3.0, 4.0, 5.0, 6.5, 8.0];
+    #[test]
+    fn linear_models_are_exact() {
+        let context = Context::default();
+        let fit = fit(&Model::Linear, &sample(|x| 3.0 * x - 2.0, &XS), &context).unwrap();
+        assert!((value_of(&fit, "a") - 3.0).abs() < 1e-10);
+        assert!((value_of(&fit, "b") + 2.0).abs() < 1e-10);
+        assert!((fit.r_squared - 1.0).abs() < 1e-12);
+        let points = sample(|x| 0.5 * x * x * x - x + 4.0, &XS);
+        let cubic = super::fit(&Model::Polynomial(3), &points, &context).unwrap();
+        assert!((value_of(&cubic, "d") - 0.5).abs() < 1e-8);
+        assert!((value_of(&cubic, "a") - 4.0).abs() < 1e-8);
+        assert!(cubic.residuals.iter().all(|x| x.abs() < 1e-8));
+        let points = sample(|x| 2.0 + 1.5 * x.ln(), &XS);
+        let logarithmic = super::fit(&Model::Logarithmic, &points, &context).unwrap();
+        assert!((value_of(&logarithmic, "b") - 1.5).abs() < 1e-10);
+    }
+    #[test]
+    fn nonlinear_models_converge() {
+        let context = Context::default();
+        let points = sample(|x| 1.5 * (0.4 * x).exp(), &XS);
+        let exponential = fit(&Model::Exponential, &points, &context).unwrap();
+        assert!((value_of(&exponential, "b") - 0.4).abs() < 1e-6);
+        let points = sample(|x| 2.0 * x.powf(-0.7), &XS);
+        let power = fit(&Model::Power, &points, &context).unwrap();
+        assert!((value_of(&power, "b") + 0.7).abs() < 1e-6);
+        let xs = (0..40).map(|i| i as f64 * 0.25).collect::<Vec<f64>>();
+        let points = sample(|x| 2.0 * (1.3 * x + 0.5).sin() - 1.0, &xs);
+        let sinusoidal = fit(&Model::Sinusoidal, &points, &context).unwrap();
+        assert!(sinusoidal.r_squared > 1.0 - 1e-9, "{:?}", sinusoidal);
+    }
+    #[test]
+    fn custom_models_with_free_parameters() {
+        let points = sample(|x| 0.8 * (-0.5 * x).exp() + 3.0, &XS);
+        let custom = Model::Custom("a*exp(b x) + c".to_string());
+        let fit = fit(&custom, &points, &Context::default()).unwrap();
+        assert!((value_of(&fit, "c") - 3.0).abs() < 1e-6);
+        assert!(fit.r_squared > 1.0 - 1e-9);
+        // The fitted expression is a plain function of x
+        let mut list = EntryList::new();
+        list.add(&fit.expression);
+        assert_eq!(list.entries[0].get_error(&list.context), None);
+        assert!((list.entries[0].evaluate(2.0, &list.context) - points[3].1).abs() < 1e-6);
+    }
+    #[test]
+    fn bad_inputs_are_reported() {
+        let context = Context::default();
+        let points = [(1.0, 1.0)];
+        assert_eq!(
+            fit(&Model::Linear, &points, &context),
+            Err(TOO_FEW_POINTS_ERROR)
+        );
+        let points = [(-1.0, 1.0), (1.0, 2.0)];
+        assert_eq!(fit(&Model::Power, &points, &context), Err(POSITIVE_X_ERROR));
+        let custom = Model::Custom("2x".to_string());
+        assert_eq!(fit(&custom, &points, &context), Err(NO_PARAMETERS_ERROR));
+        let points = [(1.0, 1.0), (1.0, 2.0)];
+        assert_eq!(fit(&Model::Linear, &points, &context), Err(SINGULAR_ERROR));
+        let solution = solve_linear(vec![vec![2.0, 1.0], vec![1.0, 3.0]], vec![3.0, 5.0]);
+        let solution = solution.unwrap();
+        assert!((solution[0] - 0.8).abs() < 1e-12 && (solution[1] - 1.4).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,415 @@
+use graphing_calculator::algebra_parser::evaluator::{self, Context, Value};
+use graphing_calculator::algebra_parser::{is_reserved_name, Node};
+use graphing_calculator::big_number::BigNumber;
+use graphing_calculator::entries::split_equation;
+use std::io::{BufRead, IsTerminal, Write};
+
+const ASSIGNMENT_ERROR: &str = "Left side of = must be a name or a function like f(x)";
+const RESERVED_NAME_ERROR: &str = "Name is already a built in function or constant";
+const RECURSION_ERROR: &str = "Functions cannot call themselves";
+const HISTORY_ERROR: &str = "No such history entry";
+const COMMAND_ERROR: &str = "Unknown command, :help lists them";
+const FORMAT_ERROR: &str = "Formats are haven and scientific";
+const INCOMPLETE_ERROR: &str = "Input ended in the middle of a statement";
+//
+const HELP: &str = "\
+  <expression>        Evaluate, the result is kept as ans
+  <name> = <expr>     Store a variable
+  f(x, y) = <expr>    Define a function
+  !<n>, !!            Run history entry n again, or the last one
+  :history            List everything entered so far
+  :vars               List variables and functions
+  :format <name>      Print numbers as haven (1.5K) or scientific (1.5x10^3)
+  :clear              Forget all variables and functions
+  :quit               Leave, end of input does the same
+Lines ending in \\ or with unclosed brackets continue on the next line.";
+const PROMPT: &str = "> ";
+const CONTINUE_PROMPT: &str = "... ";
+// The previous result, usable in the next expression
+const ANSWER: &str = "ans";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberFormat {
+    Haven,
+    Scientific,
+}
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Print(String),
+    // The statement is not finished, more lines are needed
+    Continue,
+    Quit,
+}
+// A terminal calculator over the same parser and evaluator the graph uses
+pub struct Repl {
+    pub context: Context,
+    pub history: Vec<String>,
+    pub format: NumberFormat,
+    pending: Vec<String>,
+}
+
+// Helper Functions
+fn is_name(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|x| x.is_alphabetic())
+}
+// `f(x, y)` -> ("f", ["x", "y"])
+fn parse_signature(text: &str) -> Option<(String, Vec<String>)> {
+    let (name, rest) = text.split_once('(')?;
+    let parameters = rest.strip_suffix(')')?;
+    let parameters = match parameters.trim().is_empty() {
+        true => Vec::new(),
+        false => parameters
+            .split(',')
+            .map(|x| x.trim().to_string())
+            .collect(),
+    };
+    match (is_name(name.trim()) && parameters.iter().all(|x| is_name(x))) {
+        true => Some((name.trim().to_string(), parameters)),
+        false => None,
+    }
+}
+// Open brackets left at the end of `text`
+fn open_brackets(text: &str) -> i32 {
+    text.chars().fold(0, |depth, char| match char {
+        '(' | '[' | '{' => depth + 1,
+        ')' | ']' | '}' => depth - 1,
+        _ => depth,
+    })
+}
+fn collect_calls(node: &Node, calls: &mut Vec<String>) {
+    match node {
+        Node::Number(_) | Node::Variable(_) => {}
+        Node::Negate(a) => collect_calls(a, calls),
+        Node::Operation(_, a, b) | Node::Compare(_, a, b) | Node::And(a, b) | Node::Or(a, b) => {
+            collect_calls(a, calls);
+            collect_calls(b, calls);
+        }
+        Node::Call(name, arguments) => {
+            calls.push(name.clone());
+            for argument in arguments.iter() {
+                collect_calls(argument, calls);
+            }
+        }
+        Node::Tuple(items) | Node::List(items) => {
+            for item in items.iter() {
+                collect_calls(item, calls);
+            }
+        }
+        Node::Piecewise(pieces) => {
+            for (condition, value) in pieces.iter() {
+                collect_calls(condition, calls);
+                collect_calls(value, calls);
+            }
+        }
+    }
+}
+//
+
+impl NumberFormat {
+    pub fn format(&self, value: f64) -> String {
+        if (!value.is_finite()) {
+            return "undefined".to_string();
+        }
+        let number = BigNumber::new_d(value);
+        match self {
+            NumberFormat::Haven => number.to_string(),
+            NumberFormat::Scientific => number.to_scientific_string(),
+        }
+    }
+    pub fn format_value(&self, value: &Value) -> String {
+        match value {
+            Value::Number(x) => self.format(*x),
+            Value::List(x) => format!(
+                "[{}]",
+                x.iter()
+                    .map(|x| self.format(*x))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        }
+    }
+}
+impl Repl {
+    pub fn new() -> Repl {
+        Repl {
+            context: Context::default(),
+            history: Vec::new(),
+            format: NumberFormat::Haven,
+            pending: Vec::new(),
+        }
+    }
+    // Multi-letter variables and every function have to be known to the lexer, otherwise
+    // `rate` reads as r*a*t*e and `f(2)` as f*2
+    fn get_names(&self, extra: &[String]) -> Vec<String> {
+        let variables = self
+            .context
+            .variables
+            .keys()
+            .filter(|x| x.chars().count() > 1);
+        variables
+            .chain(self.context.lists.keys())
+            .chain(self.context.functions.keys())
+            .chain(extra.iter())
+            .cloned()
+            .chain([ANSWER.to_string()])
+            .collect()
+    }
+    fn parse(&self, text: &str, extra: &[String]) -> Result<Node, String> {
+        let names = self.get_names(extra);
+        let names = names.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+        graphing_calculator::parse_with_names(text, &names)
+    }
+    fn calls_function(&self, node: &Node, name: &str, visited: &mut Vec<String>) -> bool {
+        let mut calls = Vec::new();
+        collect_calls(node, &mut calls);
+        calls.into_iter().any(|call| {
+            if (call == name) {
+                return true;
+            }
+            if (visited.contains(&call)) {
+                return false;
+            }
+            visited.push(call.clone());
+            match self.context.functions.get(&call) {
+                Some((_, body)) => self.calls_function(body, name, visited),
+                None => false,
+            }
+        })
+    }
+    fn store(&mut self, name: &str, value: Value) {
+        match value {
+            Value::Number(x) => {
+                self.context.lists.remove(name);
+                self.context.variables.insert(name.to_string(), x);
+            }
+            Value::List(x) => {
+                self.context.variables.remove(name);
+                self.context.lists.insert(name.to_string(), x);
+            }
+        }
+        self.context.functions.remove(name);
+    }
+    fn evaluate(&self, node: &Node) -> Result<Value, String> {
+        Ok(evaluator::evaluate_value(node, &self.context)?)
+    }
+    fn define(&mut self, left: &str, right: &str) -> Result<String, String> {
+        let left = left.trim();
+        if (is_name(left)) {
+            if (is_reserved_name(left)) {
+                return Err(RESERVED_NAME_ERROR.to_string());
+            }
+            let value = self.evaluate(&self.parse(right, &[])?)?;
+            let text = format!("{} = {}", left, self.format.format_value(&value));
+            self.store(left, value);
+            return Ok(text);
+        }
+        let (name, parameters) = parse_signature(left).ok_or(ASSIGNMENT_ERROR)?;
+        if (is_reserved_name(&name)) {
+            return Err(RESERVED_NAME_ERROR.to_string());
+        }
+        // The name is known while parsing so the body can be checked for calls to itself
+        let mut names = parameters
+            .iter()
+            .filter(|x| x.chars().count() > 1)
+            .cloned()
+            .collect::<Vec<String>>();
+        names.push(name.clone());
+        let body = self.parse(right, &names)?;
+        if (self.calls_function(&body, &name, &mut Vec::new())) {
+            return Err(RECURSION_ERROR.to_string());
+        }
+        let text = format!("{}({}) = {}", name, parameters.join(", "), body);
+        self.context.variables.remove(&name);
+        self.context.lists.remove(&name);
+        self.context.functions.insert(name, (parameters, body));
+        Ok(text)
+    }
+    fn run_statement(&mut self, statement: &str) -> Result<String, String> {
+        if let Some((left, right)) = split_equation(statement) {
+            return self.define(left, right);
+        }
+        let node = self.parse(statement, &[])?;
+        if let Node::Tuple(_) = node {
+            let components = evaluator::evaluate_tuple(&node, &self.context)?;
+            let components = components.iter().map(|x| self.format.format(*x));
+            return Ok(format!("({})", components.collect::<Vec<_>>().join(", ")));
+        }
+        let value = self.evaluate(&node)?;
+        let text = self.format.format_value(&value);
+        self.store(ANSWER, value);
+        Ok(text)
+    }
+    fn run_command(&mut self, command: &str) -> Result<Reply, String> {
+        let mut words = command.split_whitespace();
+        let text = match (words.next().unwrap_or(""), words.next()) {
+            ("help", _) => HELP.to_string(),
+            ("quit" | "exit", _) => return Ok(Reply::Quit),
+            ("history", _) => self
+                .history
+                .iter()
+                .enumerate()
+                .map(|(index, x)| format!("{:>4}  {}", index + 1, x))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            ("vars", _) => {
+                let mut lines = Vec::new();
+                for (name, value) in self.context.variables.iter() {
+                    lines.push(format!("{} = {}", name, self.format.format(*value)));
+                }
+                for (name, values) in self.context.lists.iter() {
+                    let values = Value::List(values.clone());
+                    lines.push(format!("{} = {}", name, self.format.format_value(&values)));
+                }
+                for (name, (parameters, body)) in self.context.functions.iter() {
+                    lines.push(format!("{}({}) = {}", name, parameters.join(", "), body));
+                }
+                lines.sort();
+                lines.join("\n")
+            }
+            ("format", None) => format!("{:?}", self.format).to_lowercase(),
+            ("format", Some(x)) => {
+                self.format = match x.to_lowercase().as_str() {
+                    "haven" => NumberFormat::Haven,
+                    "scientific" => NumberFormat::Scientific,
+                    _ => return Err(FORMAT_ERROR.to_string()),
+                };
+                String::new()
+            }
+            ("clear", _) => {
+                self.context = Context::default();
+                String::new()
+            }
+            _ => return Err(COMMAND_ERROR.to_string()),
+        };
+        Ok(Reply::Print(text))
+    }
+    // Feeds one line of input, statements run once they are complete
+    pub fn handle_line(&mut self, line: &str) -> Result<Reply, String> {
+        let line = line.trim_end();
+        let (line, continued) = match line.strip_suffix('\\') {
+            Some(x) => (x, true),
+            None => (line, false),
+        };
+        self.pending.push(line.to_string());
+        let statement = self.pending.join(" ");
+        if (continued || open_brackets(&statement) > 0) {
+            return Ok(Reply::Continue);
+        }
+        self.pending.clear();
+        let statement = statement.trim();
+        if let Some(command) = statement.strip_prefix(':') {
+            return self.run_command(command);
+        }
+        let statement = match statement {
+            "" => return Ok(Reply::Print(String::new())),
+            "!!" => self.history.last().ok_or(HISTORY_ERROR)?.clone(),
+            x if (x.starts_with('!')) => {
+                let index = x[1..].trim().parse::<usize>().map_err(|_| HISTORY_ERROR)?;
+                let entry = index.checked_sub(1).and_then(|x| self.history.get(x));
+                entry.ok_or(HISTORY_ERROR)?.clone()
+            }
+            x => x.to_string(),
+        };
+        self.history.push(statement.clone());
+        self.run_statement(&statement).map(Reply::Print)
+    }
+    pub fn is_continuing(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+// Reads statements from stdin until it ends or `:quit`, prompting only on a terminal so
+// piped scripts print nothing but results
+pub fn run() -> i32 {
+    let mut repl = Repl::new();
+    let stdin = std::io::stdin();
+    let interactive = stdin.is_terminal();
+    let (mut out, mut err) = (std::io::stdout(), std::io::stderr());
+    let mut lines = stdin.lock().lines();
+    loop {
+        if (interactive) {
+            let prompt = match repl.is_continuing() {
+                true => CONTINUE_PROMPT,
+                false => PROMPT,
+            };
+            let _ = write!(out, "{}", prompt);
+            let _ = out.flush();
+        }
+        let line = match lines.next() {
+            Some(Ok(x)) => x,
+            _ => break,
+        };
+        match repl.handle_line(&line) {
+            Ok(Reply::Print(text)) if (!text.is_empty()) => {
+                let _ = writeln!(out, "{}", text);
+            }
+            Ok(Reply::Quit) => break,
+            Ok(_) => {}
+            Err(x) => {
+                let _ = writeln!(err, "Error: {}", x);
+            }
+        }
+    }
+    if (repl.is_continuing()) {
+        let _ = writeln!(err, "Error: {}", INCOMPLETE_ERROR);
+    }
+    0
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn print(repl: &mut Repl, line: &str) -> String {
+        match repl.handle_line(line) {
+            Ok(Reply::Print(x)) => x,
+            x => panic!("{}: {:?}", line, x),
+        }
+    }
+    #[test]
+    fn variables_functions_and_ans() {
+        let mut repl = Repl::new();
+        assert_eq!(print(&mut repl, "rate = 1500"), "rate = 1.5K");
+        assert_eq!(print(&mut repl, "rate*2"), "3K");
+        assert_eq!(print(&mut repl, "ans + 1"), "3.001K");
+        assert_eq!(print(&mut repl, "area(w, h) = w*h"), "area(w, h) = w*h");
+        assert_eq!(
+            print(&mut repl, "f(x) = area(x, 2) + 1"),
+            "f(x) = area(x, 2) + 1"
+        );
+        assert_eq!(print(&mut repl, "f(3)"), "7");
+        assert_eq!(print(&mut repl, "data = [3, 1, 2]"), "data = [3, 1, 2]");
+        assert_eq!(print(&mut repl, "mean(data)"), "2");
+        assert_eq!(print(&mut repl, "(1, ans)"), "(1, 2)");
+        assert_eq!(
+            repl.handle_line("g(x) = f(x) + g(x)"),
+            Err(RECURSION_ERROR.to_string())
+        );
+        assert_eq!(
+            repl.handle_line("sin = 2"),
+            Err(RESERVED_NAME_ERROR.to_string())
+        );
+        assert_eq!(
+            repl.handle_line("2x = 1"),
+            Err(ASSIGNMENT_ERROR.to_string())
+        );
+    }
+    #[test]
+    fn lines_history_and_formats() {
+        let mut repl = Repl::new();
+        assert_eq!(repl.handle_line("(1 +"), Ok(Reply::Continue));
+        assert_eq!(repl.handle_line("2) * \\"), Ok(Reply::Continue));
+        assert!(repl.is_continuing());
+        assert_eq!(print(&mut repl, "1000"), "3K");
+        assert_eq!(print(&mut repl, ":format scientific"), "");
+        assert_eq!(print(&mut repl, "!1"), "3x10^3");
+        assert_eq!(print(&mut repl, "!!"), "3x10^3");
+        assert_eq!(repl.history.len(), 3);
+        assert_eq!(print(&mut repl, ":history").lines().count(), 3);
+        assert_eq!(repl.handle_line("!9"), Err(HISTORY_ERROR.to_string()));
+        assert_eq!(
+            repl.handle_line(":format roman"),
+            Err(FORMAT_ERROR.to_string())
+        );
+        assert_eq!(print(&mut repl, "ln(0 - 1)"), "undefined");
+        assert_eq!(repl.handle_line(":quit"), Ok(Reply::Quit));
+    }
+}
@@ -0,0 +1,270 @@
+//! What a frame draws, as a list of primitives that can go to the screen or to a file.
+#[cfg(feature = "ui")]
+use crate::plotter;
+use crate::plotter::DashStyle;
+use crate::prelude::*;
+#[cfg(feature = "ui")]
+use macroquad::prelude::{
+    draw_line, draw_poly, draw_poly_lines, draw_rectangle, draw_rectangle_lines, draw_text,
+    measure_text,
+};
+
+// Right anchored text is kept this far from the left edge
+const TEXT_PADDING: f32 = 4.0;
+// Rough width of a character relative to the font size, for when no font is loaded
+const CHARACTER_WIDTH: f32 = 0.55;
+
+/// Which point of the text sits at its position
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextAnchor {
+    /// Top center, e.g. labels below an x axis tick
+    Top,
+    /// Middle of the left edge
+    Left,
+    /// Middle of the right edge, e.g. labels left of a y axis tick
+    Right,
+    /// The middle of the text
+    Center,
+}
+/// Screen space drawing commands. Everything the graph shows is collected into a scene
+/// first, which is then drawn with macroquad or written to a file without a window
+#[derive(Debug, Clone, PartialEq)]
+pub enum Primitive {
+    /// Connected line segments
+    Polyline {
+        /// The corners in order
+        points: Vec<Vec2>,
+        /// Line width in pixels
+        thickness: f32,
+        /// Line color
+        color: Color,
+        /// Dash pattern
+        dash: DashStyle,
+    },
+    /// An axis aligned rectangle
+    Rectangle {
+        /// Position and size in pixels
+        rect: Rect,
+        /// Fill color, none for an outline only
+        fill: Option<Color>,
+        /// Thickness and color of the outline
+        stroke: Option<(f32, Color)>,
+    },
+    /// Regular polygon like `draw_poly`, `rotation` in degrees, a circle has 20 sides
+    Polygon {
+        /// Center in pixels
+        center: Vec2,
+        /// Number of corners
+        sides: u8,
+        /// Distance from the center to each corner, in pixels
+        radius: f32,
+        /// Rotation in degrees
+        rotation: f32,
+        /// Fill color, none for an outline only
+        fill: Option<Color>,
+        /// Thickness and color of the outline
+        stroke: Option<(f32, Color)>,
+    },
+    /// One line of text in the default font
+    Text {
+        /// What is written
+        text: String,
+        /// Where the text is anchored, in pixels
+        position: Vec2,
+        /// Font size in pixels
+        size: f32,
+        /// Text color
+        color: Color,
+        /// Which point of the text sits at `position`
+        anchor: TextAnchor,
+    },
+}
+/// Everything one frame or one export draws, in drawing order.
+pub struct Scene {
+    /// Size of the screen or image in pixels
+    pub size: Vec2,
+    /// Color behind everything
+    pub background: Color,
+    /// Drawn first to last
+    pub primitives: Vec<Primitive>,
+}
+
+pub(crate) const CIRCLE_SIDES: u8 = 20;
+
+// Helper Functions
+pub(crate) fn estimate_text_width(text: &str, size: f32) -> f32 {
+    text.chars().count() as f32 * size * CHARACTER_WIDTH
+}
+// Top left corner of text `width` wide and `height` tall placed at `position`
+pub(crate) fn anchor_text(position: Vec2, width: f32, height: f32, anchor: TextAnchor) -> Vec2 {
+    match anchor {
+        TextAnchor::Top => vec2(position.x - width / 2.0, position.y),
+        TextAnchor::Left => vec2(position.x, position.y - height / 2.0),
+        TextAnchor::Right => vec2(
+            (position.x - width).max(TEXT_PADDING),
+            position.y - height / 2.0,
+        ),
+        TextAnchor::Center => vec2(position.x - width / 2.0, position.y - height / 2.0),
+    }
+}
+pub(crate) fn polygon_points(center: Vec2, sides: u8, radius: f32, rotation: f32) -> Vec<Vec2> {
+    (0..sides)
+        .map(|i| {
+            let angle = i as f32 / sides as f32 * std::f32::consts::TAU + rotation.to_radians();
+            center + vec2(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+//
+
+impl Scene {
+    /// An empty white scene.
+    pub fn new(size: Vec2) -> Scene {
+        Scene {
+            size,
+            background: WHITE,
+            primitives: Vec::new(),
+        }
+    }
+    /// Adds a primitive on top of the others.
+    pub fn push(&mut self, primitive: Primitive) {
+        self.primitives.push(primitive);
+    }
+    /// Adds a solid line from `start` to `end`.
+    pub fn line(&mut self, start: Vec2, end: Vec2, thickness: f32, color: Color) {
+        self.polyline(vec![start, end], thickness, color, DashStyle::Solid);
+    }
+    /// Adds a polyline.
+    pub fn polyline(&mut self, points: Vec<Vec2>, thickness: f32, color: Color, dash: DashStyle) {
+        self.push(Primitive::Polyline {
+            points,
+            thickness,
+            color,
+            dash,
+        });
+    }
+    /// Adds a circle.
+    pub fn circle(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        fill: Option<Color>,
+        stroke: Option<(f32, Color)>,
+    ) {
+        self.push(Primitive::Polygon {
+            center,
+            sides: CIRCLE_SIDES,
+            radius,
+            rotation: 0.0,
+            fill,
+            stroke,
+        });
+    }
+    /// Adds a rectangle.
+    pub fn rectangle(&mut self, rect: Rect, fill: Option<Color>, stroke: Option<(f32, Color)>) {
+        self.push(Primitive::Rectangle { rect, fill, stroke });
+    }
+    /// Adds a line of text.
+    pub fn text(
+        &mut self,
+        text: &str,
+        position: Vec2,
+        size: f32,
+        color: Color,
+        anchor: TextAnchor,
+    ) {
+        self.push(Primitive::Text {
+            text: text.to_string(),
+            position,
+            size,
+            color,
+            anchor,
+        });
+    }
+}
+#[cfg(feature = "ui")]
+impl Scene {
+    /// Draws the scene with macroquad. Needs the window, exports go through the svg and
+    /// raster modules instead.
+    pub fn draw(&self) {
+        for primitive in self.primitives.iter() {
+            match primitive {
+                Primitive::Polyline {
+                    points,
+                    thickness,
+                    color,
+                    dash,
+                } => {
+                    for (start, end) in plotter::dash_polyline(points, dash.get_pattern(*thickness))
+                    {
+                        draw_line(start.x, start.y, end.x, end.y, *thickness, *color);
+                    }
+                }
+                Primitive::Rectangle { rect, fill, stroke } => {
+                    if let Some(color) = fill {
+                        draw_rectangle(rect.x, rect.y, rect.w, rect.h, *color);
+                    }
+                    if let Some((thickness, color)) = stroke {
+                        draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, *thickness, *color);
+                    }
+                }
+                Primitive::Polygon {
+                    center,
+                    sides,
+                    radius,
+                    rotation,
+                    fill,
+                    stroke,
+                } => {
+                    if let Some(color) = fill {
+                        draw_poly(center.x, center.y, *sides, *radius, *rotation, *color);
+                    }
+                    if let Some((thickness, color)) = stroke {
+                        let (x, y) = (center.x, center.y);
+                        draw_poly_lines(x, y, *sides, *radius, *rotation, *thickness, *color);
+                    }
+                }
+                Primitive::Text {
+                    text,
+                    position,
+                    size,
+                    color,
+                    anchor,
+                } => {
+                    let dimensions = measure_text(text, None, *size as u16, 1.0);
+                    let corner =
+                        anchor_text(*position, dimensions.width, dimensions.offset_y, *anchor);
+                    draw_text(
+                        text,
+                        corner.x,
+                        corner.y + dimensions.offset_y,
+                        *size,
+                        *color,
+                    );
+                }
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn anchors_and_polygons() {
+        let position = vec2(100.0, 50.0);
+        assert_eq!(
+            anchor_text(position, 20.0, 10.0, TextAnchor::Top),
+            vec2(90.0, 50.0)
+        );
+        assert_eq!(
+            anchor_text(position, 20.0, 10.0, TextAnchor::Right),
+            vec2(80.0, 45.0)
+        );
+        // Text running off the left edge is pulled back in
+        let corner = anchor_text(vec2(5.0, 50.0), 20.0, 10.0, TextAnchor::Right);
+        assert_eq!(corner.x, TEXT_PADDING);
+        let square = polygon_points(vec2(0.0, 0.0), 4, 2.0, 0.0);
+        assert_eq!(square.len(), 4);
+        assert!((square[1] - vec2(0.0, 2.0)).length() < 1e-6);
+    }
+}
@@ -0,0 +1,508 @@
+//! Saving and loading the entries, sliders, settings and camera as a TOML file.
+use crate::big_number::{BigNumber, BigVec2};
+use crate::camera::Camera;
+use crate::entries::{EntryList, ExpressionEntry};
+use crate::grid::GridMode;
+use crate::history::{Command, History, Snapshot};
+use crate::plotter::DashStyle;
+use crate::prelude::*;
+use crate::shapes::MarkerShape;
+use crate::sliders::{Slider, SliderList};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const MISSING_VERSION_ERROR: &str = "Session file has no version";
+const NEWER_VERSION_ERROR: &str = "Session file was saved by a newer version";
+const CAMERA_ERROR: &str = "Session file has an invalid camera";
+//
+/// The version written into new files, older files are migrated on load.
+pub const SESSION_VERSION: i64 = 3;
+/// File name of the session the app saves on quit, see `get_default_path` for where
+pub const SESSION_PATH: &str = "session.toml";
+const APP_DIRECTORY: &str = "graphing-calculator";
+// `MIGRATIONS[n]` upgrades a version n + 1 file to version n + 2, so a file from any
+// older version is brought up to date one step at a time before it is read
+const MIGRATIONS: [fn(&mut toml::Table); (SESSION_VERSION - 1) as usize] =
+    [add_entry_ids, store_changes];
+
+/// Everything that survives a restart, written as TOML so it can be read and edited by hand
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    /// The format version the file was written with
+    pub version: i64,
+    /// App settings
+    pub settings: SettingsState,
+    /// Position and zoom
+    pub camera: CameraState,
+    /// Every entry with its style
+    #[serde(default)]
+    pub entries: Vec<EntryState>,
+    /// Every slider with its bounds
+    #[serde(default)]
+    pub sliders: Vec<SliderState>,
+    /// Named lists, e.g. imported dataset columns
+    #[serde(default)]
+    pub lists: BTreeMap<String, Vec<f64>>,
+    /// Only written when the save history setting is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<History>,
+}
+/// The app settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingsState {
+    /// Where the settings window was
+    pub position: [f32; 2],
+    /// Window size as a fraction of 1920 by 1080
+    pub resolution: f32,
+    /// Name of the grid mode, see [`GridMode::get_name`]
+    pub grid: String,
+    /// Whether the undo history is saved too
+    #[serde(default)]
+    pub save_history: bool,
+}
+/// Exact `BigNumber` strings, f64s would lose deep zooms
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    /// World x in the middle of the screen
+    pub x: String,
+    /// World y in the middle of the screen
+    pub y: String,
+    /// Pixels per unit
+    pub number_distance: String,
+}
+/// One entry as it is saved, styles are stored by name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntryState {
+    /// Stays the same across saves, see [`ExpressionEntry::id`]
+    pub id: usize,
+    /// Label such as `f1`
+    pub label: String,
+    /// The text as typed
+    pub expression: String,
+    /// RGBA color
+    pub color: [f32; 4],
+    /// Curve thickness in pixels
+    pub line_width: f32,
+    /// Name of the dash style
+    pub dash: String,
+    /// Whether the entry is drawn
+    pub visible: bool,
+    /// Name of the marker shape
+    pub marker: String,
+    /// Marker radius in pixels
+    pub marker_size: f32,
+    /// Whether the points of a list are joined
+    pub connect: bool,
+    /// min, max and step of the parameter
+    pub range: [f64; 3],
+    /// Whether a point moves along the curve
+    pub animate: bool,
+}
+/// One slider as it is saved.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SliderState {
+    /// The variable the slider sets
+    pub name: String,
+    /// Current value
+    pub value: f64,
+    /// Lower bound
+    pub min: f64,
+    /// Upper bound
+    pub max: f64,
+    /// Step when playing
+    pub step: f64,
+    /// Whether the slider is playing
+    pub playing: bool,
+}
+
+// Helper Functions
+// Unknown names fall back to the first choice so files from newer versions still load
+fn find_by_name<T: Copy>(all: &[T], name: &str, get_name: fn(&T) -> &'static str) -> T {
+    *all.iter().find(|x| get_name(x) == name).unwrap_or(&all[0])
+}
+// Version 2 saves entry ids. Entries are numbered in list order the way a fresh load numbers
+// them, entries in the saved history get the id of the entry with the same label
+fn add_entry_ids(table: &mut toml::Table) {
+    let mut ids: BTreeMap<String, i64> = BTreeMap::new();
+    let mut add_ids = |entries: Option<&mut toml::Value>| {
+        let entries = entries.and_then(|x| x.as_array_mut()).into_iter().flatten();
+        for entry in entries.filter_map(|x| x.as_table_mut()) {
+            let label = entry
+                .get("label")
+                .and_then(|x| x.as_str())
+                .unwrap_or_default();
+            let next = ids.values().max().unwrap_or(&0) + 1;
+            let id = *ids.entry(label.to_string()).or_insert(next);
+            entry.insert("id".to_string(), toml::Value::Integer(id));
+        }
+    };
+    add_ids(table.get_mut("entries"));
+    let history = table.get_mut("history").and_then(|x| x.as_table_mut());
+    for stack in history
+        .into_iter()
+        .flat_map(|x| x.iter_mut())
+        .map(|(_, x)| x)
+    {
+        let commands = stack.as_array_mut().into_iter().flatten();
+        for command in commands.filter_map(|x| x.as_table_mut()) {
+            for snapshot in command.iter_mut().filter_map(|(_, x)| x.as_table_mut()) {
+                add_ids(snapshot.get_mut("entries"));
+            }
+        }
+    }
+}
+// Version 3 history commands keep only what changed instead of the state before and after.
+// Commands that do not read are left for `try_into` to report
+fn store_changes(table: &mut toml::Table) {
+    let history = table.get_mut("history").and_then(|x| x.as_table_mut());
+    for stack in history
+        .into_iter()
+        .flat_map(|x| x.iter_mut())
+        .map(|(_, x)| x)
+    {
+        for command in stack.as_array_mut().into_iter().flatten() {
+            let migrated = command.as_table().and_then(|x| {
+                let before = x.get("before")?.clone().try_into::<Snapshot>().ok()?;
+                let after = x.get("after")?.clone().try_into::<Snapshot>().ok()?;
+                toml::Value::try_from(Command::new(&before, &after)).ok()
+            });
+            if let Some(x) = migrated {
+                *command = x;
+            }
+        }
+    }
+}
+//
+
+/// The per-user session file, independent of where the app was started from:
+/// `$XDG_CONFIG_HOME` or `~/.config` on Linux, `~/Library/Application Support` on macOS and
+/// `%APPDATA%` on Windows, each in a `graphing-calculator` folder. Falls back to the
+/// working directory when none of these is set
+pub fn get_default_path() -> String {
+    let variable = |name: &str| std::env::var_os(name).filter(|x| !x.is_empty());
+    let home = |rest: &str| variable("HOME").map(|x| std::path::Path::new(&x).join(rest));
+    let directory = match std::env::consts::OS {
+        "windows" => variable("APPDATA").map(std::path::PathBuf::from),
+        "macos" => home("Library/Application Support"),
+        _ => variable("XDG_CONFIG_HOME")
+            .map(std::path::PathBuf::from)
+            .or_else(|| home(".config")),
+    };
+    match directory {
+        Some(x) => x
+            .join(APP_DIRECTORY)
+            .join(SESSION_PATH)
+            .to_string_lossy()
+            .to_string(),
+        None => SESSION_PATH.to_string(),
+    }
+}
+impl SettingsState {
+    /// The saved grid mode, cartesian when the name is unknown.
+    pub fn get_grid_mode(&self) -> GridMode {
+        find_by_name(&GridMode::ALL, &self.grid, GridMode::get_name)
+    }
+}
+impl CameraState {
+    /// The state of `camera`.
+    pub fn capture(camera: &Camera) -> CameraState {
+        CameraState {
+            x: camera.position.x.to_exact_string(),
+            y: camera.position.y.to_exact_string(),
+            number_distance: camera.number_distance.to_exact_string(),
+        }
+    }
+    /// Moves `camera` to the saved state, an error when a number does not parse.
+    pub fn restore(&self, camera: &mut Camera) -> Result<(), String> {
+        let parse = |x: &str| BigNumber::parse(x).ok_or(CAMERA_ERROR.to_string());
+        let number_distance = parse(&self.number_distance)?;
+        if !number_distance.is_positive || number_distance.base == 0.0 {
+            return Err(CAMERA_ERROR.to_string());
+        }
+        camera.position = BigVec2::new(parse(&self.x)?, parse(&self.y)?);
+        camera.number_distance = number_distance;
+        Ok(())
+    }
+}
+impl EntryState {
+    /// The state of `entry`.
+    pub fn capture(entry: &ExpressionEntry) -> EntryState {
+        let (style, range) = (&entry.style, &entry.range);
+        EntryState {
+            id: entry.id,
+            label: entry.label.clone(),
+            expression: entry.expression.clone(),
+            color: style.color.into(),
+            line_width: style.line_width,
+            dash: style.dash.get_name().to_string(),
+            visible: style.visible,
+            marker: style.marker.get_name().to_string(),
+            marker_size: style.marker_size,
+            connect: style.connect,
+            range: [range.min, range.max, range.step],
+            animate: entry.animate,
+        }
+    }
+    /// Adds the entry to `entries` under its saved id and label.
+    pub fn restore(&self, entries: &mut EntryList) {
+        let entry = entries.add_labelled(self.id, &self.label, &self.expression);
+        entry.style.color = Color::from(self.color);
+        entry.style.line_width = self.line_width;
+        entry.style.dash = find_by_name(&DashStyle::ALL, &self.dash, DashStyle::get_name);
+        entry.style.visible = self.visible;
+        entry.style.marker = find_by_name(&MarkerShape::ALL, &self.marker, MarkerShape::get_name);
+        entry.style.marker_size = self.marker_size;
+        entry.style.connect = self.connect;
+        [entry.range.min, entry.range.max, entry.range.step] = self.range;
+        entry.animate = self.animate;
+    }
+}
+impl SliderState {
+    /// The state of `slider`.
+    pub fn capture(slider: &Slider) -> SliderState {
+        SliderState {
+            name: slider.name.clone(),
+            value: slider.value,
+            min: slider.min,
+            max: slider.max,
+            step: slider.step,
+            playing: slider.playing,
+        }
+    }
+    /// The saved slider, with the value kept inside its bounds.
+    pub fn restore(&self) -> Slider {
+        let mut slider = Slider::new(&self.name);
+        slider.set_bounds(self.min, self.max, self.step);
+        slider.value = self.value.clamp(slider.min, slider.max);
+        slider.playing = self.playing;
+        slider
+    }
+}
+impl Session {
+    /// The current app state, without history.
+    pub fn capture(
+        settings: SettingsState,
+        camera: &Camera,
+        entries: &EntryList,
+        sliders: &SliderList,
+    ) -> Session {
+        Session {
+            version: SESSION_VERSION,
+            settings,
+            camera: CameraState::capture(camera),
+            entries: entries.entries.iter().map(EntryState::capture).collect(),
+            sliders: sliders.sliders.iter().map(SliderState::capture).collect(),
+            lists: entries
+                .context
+                .lists
+                .iter()
+                .map(|(name, values)| (name.clone(), values.clone()))
+                .collect(),
+            history: None,
+        }
+    }
+    /// Replaces the entries and sliders, the settings are left to the caller
+    pub fn restore(
+        &self,
+        camera: &mut Camera,
+        entries: &mut EntryList,
+        sliders: &mut SliderList,
+    ) -> Result<(), String> {
+        self.camera.restore(camera)?;
+        entries.clear();
+        // Lists first so the entries reading them parse their names as one term
+        for (name, values) in self.lists.iter() {
+            entries.set_list(name, values.clone());
+        }
+        for entry in self.entries.iter() {
+            entry.restore(entries);
+        }
+        sliders.sliders = self.sliders.iter().map(SliderState::restore).collect();
+        sliders.sync(entries);
+        Ok(())
+    }
+    /// The session as TOML text.
+    pub fn to_toml(&self) -> Result<String, String> {
+        toml::to_string_pretty(self).map_err(|x| x.to_string())
+    }
+    /// Reads a session from TOML text, migrating files from older versions.
+    pub fn from_toml(text: &str) -> Result<Session, String> {
+        let mut table = text.parse::<toml::Table>().map_err(|x| x.to_string())?;
+        let version = match table.get("version") {
+            Some(toml::Value::Integer(x)) if (*x >= 1) => *x,
+            _ => return Err(MISSING_VERSION_ERROR.to_string()),
+        };
+        if version > SESSION_VERSION {
+            return Err(NEWER_VERSION_ERROR.to_string());
+        }
+        for migration in MIGRATIONS.iter().skip((version - 1) as usize) {
+            migration(&mut table);
+        }
+        table.insert("version".to_string(), toml::Value::Integer(SESSION_VERSION));
+        table.try_into().map_err(|x: toml::de::Error| x.to_string())
+    }
+    /// Writes the session to `path`. Creates the folder the file goes in when it does not exist yet
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let error = |x: std::io::Error| format!("Could not save {}: {}", path, x);
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(parent).map_err(error)?;
+        }
+        std::fs::write(path, self.to_toml()?).map_err(error)
+    }
+    /// Reads the session file at `path`.
+    pub fn load(path: &str) -> Result<Session, String> {
+        let text =
+            std::fs::read_to_string(path).map_err(|x| format!("Could not read {}: {}", path, x))?;
+        Session::from_toml(&text)
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::history::Change;
+    // `Camera::new` reads the screen size, which needs a window
+    fn test_camera() -> Camera {
+        Camera {
+            position: BigVec2::new_d(0.0, 0.0),
+            number_distance: BigNumber::new_d(50.0),
+        }
+    }
+    fn settings() -> SettingsState {
+        SettingsState {
+            position: [400.0, 200.0],
+            resolution: 0.75,
+            grid: GridMode::ALL[1].get_name().to_string(),
+            save_history: true,
+        }
+    }
+    #[test]
+    fn sessions_round_trip() {
+        let mut entries = EntryList::new();
+        entries.set_list("height", vec![1.5, 1.75, 2.0]);
+        entries.add("a*sin(x)");
+        entries.add("histogram(height)");
+        entries.entries[0].style.dash = DashStyle::Dotted;
+        entries.entries[0].style.color = Color::new(0.1, 0.2, 0.3, 0.4);
+        entries.entries[1].label = "f7".to_string();
+        let mut sliders = SliderList::new();
+        sliders.sync(&mut entries);
+        sliders.sliders[0].value = 0.1 + 0.2;
+        let mut camera = test_camera();
+        camera.position = BigVec2::new(
+            BigNumber::from_parts(1.0 / 3.0, 500),
+            BigNumber::new_d(-2.5),
+        );
+        let session = Session::capture(settings(), &camera, &entries, &sliders);
+        let text = session.to_toml().unwrap();
+        assert_eq!(Session::from_toml(&text), Ok(session.clone()));
+        let (mut restored, mut restored_sliders) = (EntryList::new(), SliderList::new());
+        let mut restored_camera = test_camera();
+        session
+            .restore(&mut restored_camera, &mut restored, &mut restored_sliders)
+            .unwrap();
+        let again = Session::capture(settings(), &restored_camera, &restored, &restored_sliders);
+        assert_eq!(again, session);
+        assert_eq!(restored.context.variables["a"], 0.1 + 0.2);
+        assert_eq!(again.settings.get_grid_mode(), GridMode::ALL[1]);
+        // Labels keep counting after the highest restored one
+        restored.add("x");
+        assert_eq!(restored.entries[2].label, "f8");
+    }
+    #[test]
+    fn versions_are_checked() {
+        let session = Session::capture(
+            settings(),
+            &test_camera(),
+            &EntryList::new(),
+            &SliderList::new(),
+        );
+        let text = session.to_toml().unwrap();
+        let newer = text.replace("version = 3", "version = 99");
+        assert_eq!(
+            Session::from_toml(&newer),
+            Err(NEWER_VERSION_ERROR.to_string())
+        );
+        let missing = text.replace("version = 3\n", "");
+        assert_eq!(
+            Session::from_toml(&missing),
+            Err(MISSING_VERSION_ERROR.to_string())
+        );
+        // Sections added after a file was written fall back to empty
+        let minimal = "version = 1\n[settings]\nposition = [0.0, 0.0]\nresolution = 1.0\n\
+            grid = \"Cartesian\"\n[camera]\nx = \"0e0\"\ny = \"0e0\"\nnumber_distance = \"1e2\"\n";
+        let session = Session::from_toml(minimal).unwrap();
+        assert!(session.entries.is_empty() && session.lists.is_empty());
+        assert!(!session.settings.save_history);
+        assert!(session.history.is_none());
+        let mut camera = test_camera();
+        session.camera.restore(&mut camera).unwrap();
+        assert_eq!(camera.get_pixels_per_unit(), 100.0);
+    }
+    #[test]
+    fn version_1_files_are_migrated() {
+        let entry = |table: &str, label: &str| {
+            format!(
+                "[[{table}]]\nlabel = \"{label}\"\nexpression = \"x\"\ncolor = [0.0, 0.0, 0.0, 1.0]\n\
+                line_width = 2.0\ndash = \"Solid\"\nvisible = true\nmarker = \"Circle\"\n\
+                marker_size = 4.0\nconnect = false\nrange = [0.0, 1.0, 0.1]\nanimate = false\n"
+            )
+        };
+        let camera = "x = \"0e0\"\ny = \"0e0\"\nnumber_distance = \"1e2\"\n";
+        // Two entries left after f2 was deleted, the deletion is in the saved history
+        let text = format!(
+            "version = 1\n[settings]\nposition = [0.0, 0.0]\nresolution = 1.0\n\
+            grid = \"Cartesian\"\nsave_history = true\n[camera]\n{camera}{}{}\
+            [history]\nredo = []\n[[history.undo]]\ntarget = \"Entries\"\n\
+            [history.undo.before]\nsliders = []\n[history.undo.before.camera]\n{camera}{}{}{}\
+            [history.undo.after]\nsliders = []\n[history.undo.after.camera]\n{camera}{}{}",
+            entry("entries", "f1"),
+            entry("entries", "f3"),
+            entry("history.undo.before.entries", "f1"),
+            entry("history.undo.before.entries", "f2"),
+            entry("history.undo.before.entries", "f3"),
+            entry("history.undo.after.entries", "f1"),
+            entry("history.undo.after.entries", "f3"),
+        );
+        let session = Session::from_toml(&text).unwrap();
+        assert_eq!(session.version, SESSION_VERSION);
+        let ids = |entries: &[EntryState]| entries.iter().map(|x| x.id).collect::<Vec<_>>();
+        assert_eq!(ids(&session.entries), [1, 2]);
+        // The saved step is migrated to just the deleted entry
+        let command = &session.history.as_ref().unwrap().undo[0];
+        assert_eq!(command.changes.len(), 1);
+        match &command.changes[0] {
+            Change::Entry {
+                id, index, after, ..
+            } => assert_eq!((*id, *index, after), (3, 1, &None)),
+            x => panic!("{x:?}"),
+        }
+        let (mut entries, mut sliders) = (EntryList::new(), SliderList::new());
+        session
+            .restore(&mut test_camera(), &mut entries, &mut sliders)
+            .unwrap();
+        let labels = entries.entries.iter().map(|x| (x.id, x.label.as_str()));
+        assert_eq!(labels.collect::<Vec<_>>(), [(1, "f1"), (2, "f3")]);
+        // The deleted entry's id is not handed out again
+        entries.add("x");
+        assert_eq!(
+            (entries.entries[2].id, entries.entries[2].label.as_str()),
+            (4, "f4")
+        );
+    }
+    #[test]
+    fn saving_creates_the_folder() {
+        let folder = std::env::temp_dir().join(format!("session-test-{}", std::process::id()));
+        let path = folder.join(APP_DIRECTORY).join(SESSION_PATH);
+        let path = path.to_str().unwrap();
+        let session = Session::capture(
+            settings(),
+            &test_camera(),
+            &EntryList::new(),
+            &SliderList::new(),
+        );
+        session.save(path).unwrap();
+        assert_eq!(Session::load(path), Ok(session));
+        std::fs::remove_dir_all(folder).unwrap();
+        assert!(get_default_path().ends_with(SESSION_PATH));
+    }
+}
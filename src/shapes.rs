@@ -0,0 +1,156 @@
+//! Point markers in a few shapes that keep their pixel size at every zoom.
+use crate::big_number::BigVec2;
+use crate::camera::Camera;
+use crate::prelude::*;
+use crate::scene::{Primitive, Scene, CIRCLE_SIDES};
+
+const LINE_THICKNESS: f32 = 2.0;
+
+/// The shape a marker is drawn as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerShape {
+    /// A filled dot
+    Circle,
+    /// An axis aligned square
+    Square,
+    /// A triangle pointing up
+    Triangle,
+    /// A square standing on a corner
+    Diamond,
+    /// An `x`
+    Cross,
+}
+impl MarkerShape {
+    /// Every shape, in the order the style settings list them.
+    pub const ALL: [MarkerShape; 5] = [
+        MarkerShape::Circle,
+        MarkerShape::Square,
+        MarkerShape::Triangle,
+        MarkerShape::Diamond,
+        MarkerShape::Cross,
+    ];
+    /// The name shown in the style settings.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            MarkerShape::Circle => "Circle",
+            MarkerShape::Square => "Square",
+            MarkerShape::Triangle => "Triangle",
+            MarkerShape::Diamond => "Diamond",
+            MarkerShape::Cross => "Cross",
+        }
+    }
+}
+/// A marker sits at a point of the plane and keeps its size in pixels at every zoom
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marker {
+    /// Horizontal world coordinate
+    pub x: f64,
+    /// Vertical world coordinate
+    pub y: f64,
+    /// How the marker is drawn
+    pub shape: MarkerShape,
+    /// Radius in pixels
+    pub size: f32,
+    /// Fill color
+    pub color: Color,
+}
+/// The markers of one frame, collected from every source and drawn in one go.
+#[derive(Default)]
+pub struct ShapeCache {
+    cache: Vec<Marker>,
+}
+
+// Helper Functions
+fn push_marker(scene: &mut Scene, marker: &Marker, center: Vec2) {
+    let (size, color) = (marker.size, marker.color);
+    let mut polygon = |sides: u8, rotation: f32| {
+        scene.push(Primitive::Polygon {
+            center,
+            sides,
+            radius: size,
+            rotation,
+            fill: Some(color),
+            stroke: None,
+        })
+    };
+    match marker.shape {
+        MarkerShape::Circle => polygon(CIRCLE_SIDES, 0.0),
+        MarkerShape::Square => polygon(4, 45.0),
+        MarkerShape::Triangle => polygon(3, -90.0),
+        MarkerShape::Diamond => polygon(4, 0.0),
+        MarkerShape::Cross => {
+            for corner in [vec2(size, size), vec2(size, -size)] {
+                scene.line(center - corner, center + corner, LINE_THICKNESS, color);
+            }
+        }
+    }
+}
+//
+
+impl ShapeCache {
+    /// An empty cache.
+    pub fn new() -> ShapeCache {
+        ShapeCache::default()
+    }
+    /// The number of markers.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+    /// Whether there are no markers.
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+    /// Identical markers are only drawn once, returns whether the marker was new
+    pub fn push(&mut self, marker: Marker) -> bool {
+        if self.cache.contains(&marker) {
+            return false;
+        }
+        self.cache.push(marker);
+        true
+    }
+    /// Drops every marker, for the next frame.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+    /// Adds the markers that are on screen to the scene.
+    pub fn push_to_scene(&self, scene: &mut Scene, camera: &Camera) {
+        let screen = scene.size;
+        let margin = vec2(1.0, 1.0) * self.cache.iter().map(|x| x.size).fold(0.0, f32::max);
+        let bounds = Rect::new(
+            -margin.x,
+            -margin.y,
+            screen.x + margin.x * 2.0,
+            screen.y + margin.y * 2.0,
+        );
+        for marker in self.cache.iter() {
+            let center = camera.world_to_screen(&BigVec2::new_d(marker.x, marker.y), screen);
+            if bounds.contains(center) {
+                push_marker(scene, marker, center);
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn duplicates_are_cached_once() {
+        let mut cache = ShapeCache::new();
+        let marker = Marker {
+            x: 1.0,
+            y: 2.0,
+            shape: MarkerShape::Circle,
+            size: 4.0,
+            color: BLACK,
+        };
+        assert!(cache.push(marker));
+        assert!(!cache.push(marker));
+        assert!(cache.push(Marker {
+            shape: MarkerShape::Cross,
+            ..marker
+        }));
+        assert_eq!(cache.len(), 2);
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! Sliders for the free parameters of the entries.
+use crate::entries::EntryList;
+use itertools::Itertools;
+#[cfg(feature = "ui")]
+use macroquad::ui::{hash, Ui};
+
+/// Seconds between two steps of a playing slider
+pub const PLAY_INTERVAL: f64 = 0.05;
+const DEFAULT_VALUE: f64 = 1.0;
+const DEFAULT_MIN: f64 = -10.0;
+const DEFAULT_MAX: f64 = 10.0;
+const DEFAULT_STEP: f64 = 0.1;
+
+/// A value for one free variable, dragged by hand or played through its range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Slider {
+    /// The variable the slider sets
+    pub name: String,
+    /// Current value, always in [min, max]
+    pub value: f64,
+    /// Lower bound
+    pub min: f64,
+    /// Upper bound
+    pub max: f64,
+    /// How far one step of playback moves
+    pub step: f64,
+    /// Whether the value moves on its own
+    pub playing: bool,
+    // What is typed into the min, max and step boxes, only applied once it parses
+    bounds_text: [String; 3],
+}
+/// Every slider, one per free variable of the entries.
+#[derive(Default)]
+pub struct SliderList {
+    /// The sliders in the order their variables first appeared
+    pub sliders: Vec<Slider>,
+}
+
+impl Slider {
+    /// A slider at 1 in [-10, 10].
+    pub fn new(name: &str) -> Slider {
+        Slider {
+            name: name.to_string(),
+            value: DEFAULT_VALUE,
+            min: DEFAULT_MIN,
+            max: DEFAULT_MAX,
+            step: DEFAULT_STEP,
+            playing: false,
+            bounds_text: [DEFAULT_MIN, DEFAULT_MAX, DEFAULT_STEP].map(|x| x.to_string()),
+        }
+    }
+    /// One step forward, wrapping around to `min` after passing `max`
+    pub fn advance(&mut self) {
+        let next = self.value + self.step;
+        self.value = match next > self.max + self.step * 1e-9 {
+            true => self.min,
+            false => next.min(self.max),
+        };
+    }
+    /// Sets the bounds when `min < max` and `step > 0`, and keeps the value inside them.
+    pub fn set_bounds(&mut self, min: f64, max: f64, step: f64) {
+        self.bounds_text = [min, max, step].map(|x| x.to_string());
+        self.apply_bounds_text();
+    }
+    /// Rounds to the nearest `min + k * step` inside the bounds.
+    pub fn snap(&self, value: f64) -> f64 {
+        let steps = ((value - self.min) / self.step).round();
+        (self.min + steps * self.step).clamp(self.min, self.max)
+    }
+    // Takes the typed bounds when they make sense and keeps the value inside them
+    fn apply_bounds_text(&mut self) {
+        let parsed = self.bounds_text.clone().map(|x| x.trim().parse::<f64>());
+        if let [Ok(min), Ok(max), Ok(step)] = parsed {
+            if min < max && step > 0.0 {
+                self.min = min;
+                self.max = max;
+                self.step = step;
+            }
+        }
+        self.value = self.value.clamp(self.min, self.max);
+    }
+}
+impl SliderList {
+    /// No sliders.
+    pub fn new() -> SliderList {
+        SliderList::default()
+    }
+    /// The slider for `name`, if there is one.
+    pub fn get(&self, name: &str) -> Option<&Slider> {
+        self.sliders.iter().find(|x| x.name == name)
+    }
+    /// Adds a slider for every free variable of the entries and drops the ones nothing
+    /// reads anymore, then hands the values to the entries
+    pub fn sync(&mut self, entries: &mut EntryList) {
+        let names = entries
+            .entries
+            .iter()
+            .flat_map(|x| x.get_free_variables())
+            .unique()
+            .collect::<Vec<String>>();
+        for slider in self.sliders.iter() {
+            if !names.contains(&slider.name) {
+                entries.remove_variable(&slider.name);
+            }
+        }
+        self.sliders.retain(|x| names.contains(&x.name));
+        for name in names.iter() {
+            if self.get(name).is_none() {
+                self.sliders.push(Slider::new(name));
+            }
+        }
+        for slider in self.sliders.iter() {
+            entries.set_variable(&slider.name, slider.value);
+        }
+    }
+    /// Called whenever the play job of the cooldown scheduler fires
+    pub fn advance(&mut self) {
+        for slider in self.sliders.iter_mut().filter(|x| x.playing) {
+            slider.advance();
+        }
+    }
+}
+#[cfg(feature = "ui")]
+impl SliderList {
+    /// Draws every slider with its bounds and play button into `ui`.
+    pub fn create_ui(&mut self, ui: &mut Ui) {
+        for slider in self.sliders.iter_mut() {
+            ui.separator();
+            let mut value = slider.value as f32;
+            ui.slider(
+                hash!("value", slider.name.as_str()),
+                &slider.name,
+                slider.min as f32..slider.max as f32,
+                &mut value,
+            );
+            if value != slider.value as f32 {
+                slider.value = slider.snap(value as f64);
+            }
+            let labels = ["min", "max", "step"];
+            for (label, text) in labels.iter().zip(slider.bounds_text.iter_mut()) {
+                ui.input_text(hash!(*label, slider.name.as_str()), label, text);
+            }
+            slider.apply_bounds_text();
+            let label = match slider.playing {
+                true => "Pause",
+                false => "Play",
+            };
+            if ui.button(None, label) {
+                slider.playing = !slider.playing;
+            }
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn sliders_follow_free_variables() {
+        let mut entries = EntryList::new();
+        entries.add("a*sin(b x)");
+        let mut sliders = SliderList::new();
+        sliders.sync(&mut entries);
+        let names = sliders
+            .sliders
+            .iter()
+            .map(|x| x.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(entries.entries[0].get_error(&entries.context), None);
+        assert_eq!(entries.entries[0].evaluate(0.0, &entries.context), 0.0);
+        entries.entries[0].set_expression("a x");
+        sliders.sync(&mut entries);
+        assert_eq!(sliders.sliders.len(), 1);
+        assert!(!entries.context.variables.contains_key("b"));
+    }
+    #[test]
+    fn changing_a_value_touches_its_readers() {
+        let mut entries = EntryList::new();
+        entries.add("a x");
+        entries.add("x");
+        let mut sliders = SliderList::new();
+        sliders.sync(&mut entries);
+        let revisions = (entries.entries[0].revision, entries.entries[1].revision);
+        sliders.sliders[0].value = 3.0;
+        sliders.sync(&mut entries);
+        assert_eq!(entries.entries[0].revision, revisions.0 + 1);
+        assert_eq!(entries.entries[1].revision, revisions.1);
+        assert_eq!(entries.entries[0].evaluate(2.0, &entries.context), 6.0);
+    }
+    #[test]
+    fn playing_wraps_around() {
+        let mut slider = Slider::new("a");
+        slider.bounds_text = ["0".to_string(), "1".to_string(), "0.25".to_string()];
+        slider.apply_bounds_text();
+        assert_eq!(
+            (slider.min, slider.max, slider.step, slider.value),
+            (0.0, 1.0, 0.25, 1.0)
+        );
+        slider.advance();
+        assert_eq!(slider.value, 0.0);
+        slider.advance();
+        assert_eq!(slider.value, 0.25);
+        // Bounds that do not make sense are ignored
+        slider.bounds_text[0] = "2".to_string();
+        slider.apply_bounds_text();
+        assert_eq!(slider.min, 0.0);
+    }
+    #[test]
+    fn dragged_values_snap_to_the_step() {
+        let mut slider = Slider::new("a");
+        slider.set_bounds(1.0, 10.0, 2.0);
+        assert_eq!(slider.snap(4.2), 5.0);
+        assert_eq!(slider.snap(1.9), 1.0);
+        assert_eq!(slider.snap(0.0), 1.0);
+        assert_eq!(slider.snap(10.0), 10.0);
+        assert_eq!(slider.snap(9.5), 9.0);
+        slider.set_bounds(-10.0, 10.0, 0.5);
+        assert_eq!(slider.snap(0.3), 0.5);
+        assert_eq!(slider.snap(-3.1), -3.0);
+    }
+}
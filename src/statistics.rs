@@ -0,0 +1,189 @@
+// Statistics over lists of numbers. Anything that is undefined for the given list, such as
+// the mean of an empty list, comes back as NaN so plots simply leave it out
+
+// More bins than this would not be visible as separate bars anyway
+const MAX_BINS: usize = 10000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub min: f64,
+    pub lower_quartile: f64,
+    pub median: f64,
+    pub upper_quartile: f64,
+    pub max: f64,
+}
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bin {
+    pub start: f64,
+    pub end: f64,
+    pub count: usize,
+}
+
+pub fn sorted(values: &[f64]) -> Vec<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+}
+pub fn sum(values: &[f64]) -> f64 {
+    values.iter().sum()
+}
+pub fn mean(values: &[f64]) -> f64 {
+    sum(values) / values.len() as f64
+}
+pub fn min(values: &[f64]) -> f64 {
+    values.iter().cloned().reduce(f64::min).unwrap_or(f64::NAN)
+}
+pub fn max(values: &[f64]) -> f64 {
+    values.iter().cloned().reduce(f64::max).unwrap_or(f64::NAN)
+}
+// Linear interpolation between the closest ranks, `p` runs from 0 to 1
+pub fn quantile(values: &[f64], p: f64) -> f64 {
+    if values.is_empty() || !(0.0..=1.0).contains(&p) {
+        return f64::NAN;
+    }
+    let sorted = sorted(values);
+    let rank = p * (sorted.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+pub fn median(values: &[f64]) -> f64 {
+    quantile(values, 0.5)
+}
+// Most frequent value, the smallest one when several are tied
+pub fn mode(values: &[f64]) -> f64 {
+    let sorted = sorted(values);
+    let mut best = (f64::NAN, 0);
+    for run in sorted.chunk_by(|a, b| a == b) {
+        if run.len() > best.1 {
+            best = (run[0], run.len());
+        }
+    }
+    best.0
+}
+// Sample variance, dividing by n - 1
+pub fn variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return f64::NAN;
+    }
+    let mean = mean(values);
+    values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+pub fn standard_deviation(values: &[f64]) -> f64 {
+    variance(values).sqrt()
+}
+// Pearson correlation of two lists of the same length
+pub fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    if a.len() != b.len() || a.len() < 2 {
+        return f64::NAN;
+    }
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let covariance = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x - mean_a) * (y - mean_b))
+        .sum::<f64>();
+    let spread_a = a.iter().map(|x| (x - mean_a).powi(2)).sum::<f64>();
+    let spread_b = b.iter().map(|y| (y - mean_b).powi(2)).sum::<f64>();
+    covariance / (spread_a * spread_b).sqrt()
+}
+// Five number summary for box plots
+pub fn summarize(values: &[f64]) -> Option<Summary> {
+    if values.is_empty() || values.iter().any(|x| !x.is_finite()) {
+        return None;
+    }
+    Some(Summary {
+        min: min(values),
+        lower_quartile: quantile(values, 0.25),
+        median: median(values),
+        upper_quartile: quantile(values, 0.75),
+        max: max(values),
+    })
+}
+// Bins of `width` aligned to multiples of the width, `None` picks the width from Sturges'
+// rule. A value on the edge between two bins counts towards the upper one
+pub fn histogram(values: &[f64], width: Option<f64>) -> Vec<Bin> {
+    let values = values
+        .iter()
+        .cloned()
+        .filter(|x| x.is_finite())
+        .collect::<Vec<f64>>();
+    if values.is_empty() {
+        return Vec::new();
+    }
+    let (low, high) = (min(&values), max(&values));
+    let width = match width {
+        Some(x) if (x > 0.0 && x.is_finite()) => x,
+        Some(_) => return Vec::new(),
+        None => {
+            let count = (values.len() as f64).log2().ceil() + 1.0;
+            match high > low {
+                true => (high - low) / count,
+                false => 1.0,
+            }
+        }
+    };
+    let first = (low / width).floor();
+    let bin_count = ((high / width).floor() - first) as usize + 1;
+    if bin_count > MAX_BINS {
+        return Vec::new();
+    }
+    let mut bins = (0..bin_count)
+        .map(|i| Bin {
+            start: (first + i as f64) * width,
+            end: (first + i as f64 + 1.0) * width,
+            count: 0,
+        })
+        .collect::<Vec<Bin>>();
+    for x in values.iter() {
+        let index = ((x / width).floor() - first) as usize;
+        bins[index.min(bin_count - 1)].count += 1;
+    }
+    bins
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    const DATA: [f64; 8] = [4.0, 1.0, 3.0, 3.0, 9.0, 7.0, 3.0, 2.0];
+    #[test]
+    fn averages() {
+        assert_eq!(mean(&DATA), 4.0);
+        assert_eq!(median(&DATA), 3.0);
+        assert_eq!(mode(&DATA), 3.0);
+        assert_eq!(mode(&[2.0, 1.0]), 1.0);
+        assert_eq!(sum(&DATA), 32.0);
+        assert_eq!((min(&DATA), max(&DATA)), (1.0, 9.0));
+        assert!(mean(&[]).is_nan() && median(&[]).is_nan());
+    }
+    #[test]
+    fn spread() {
+        assert!((variance(&DATA) - 50.0 / 7.0).abs() < 1e-12);
+        assert!((standard_deviation(&DATA) - (50.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert_eq!(quantile(&DATA, 0.25), 2.75);
+        assert_eq!(quantile(&DATA, 1.0), 9.0);
+        assert!(quantile(&DATA, 1.5).is_nan());
+        let a = [1.0, 2.0, 3.0, 4.0];
+        assert!((correlation(&a, &[2.0, 4.0, 6.0, 8.0]) - 1.0).abs() < 1e-12);
+        assert!((correlation(&a, &[8.0, 6.0, 4.0, 2.0]) + 1.0).abs() < 1e-12);
+        assert!(correlation(&a, &[1.0]).is_nan());
+    }
+    #[test]
+    fn plots() {
+        let summary = summarize(&DATA).unwrap();
+        assert_eq!(
+            (summary.min, summary.median, summary.upper_quartile),
+            (1.0, 3.0, 4.75)
+        );
+        let bins = histogram(&DATA, Some(2.0));
+        let counts = bins.iter().map(|x| x.count).collect::<Vec<usize>>();
+        assert_eq!(counts, vec![1, 4, 1, 1, 1]);
+        assert_eq!((bins[0].start, bins[4].end), (0.0, 10.0));
+        assert_eq!(
+            histogram(&DATA, None)
+                .iter()
+                .map(|x| x.count)
+                .sum::<usize>(),
+            8
+        );
+        assert!(histogram(&DATA, Some(-1.0)).is_empty());
+    }
+}
@@ -0,0 +1,232 @@
+//! Writing a [`Scene`] as an SVG document.
+use crate::prelude::*;
+use crate::scene::{self, Primitive, Scene, TextAnchor};
+use std::fmt::Write;
+
+/// Where the app writes SVG exports.
+pub const EXPORT_PATH: &str = "graph.svg";
+const FONT_FAMILY: &str = "sans-serif";
+
+// Helper Functions
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+// Two decimals are plenty at screen resolution and keep the file small
+fn number(x: f32) -> String {
+    let rounded = format!("{:.2}", x);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" => "0".to_string(),
+        x => x.to_string(),
+    }
+}
+fn points(points: &[Vec2]) -> String {
+    points
+        .iter()
+        .map(|x| format!("{},{}", number(x.x), number(x.y)))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+fn rgb(color: Color) -> String {
+    let byte = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        byte(color.r),
+        byte(color.g),
+        byte(color.b)
+    )
+}
+// `fill="..."` and `stroke="..."` attributes, transparency as separate opacities
+fn paint(fill: Option<Color>, stroke: Option<(f32, Color)>) -> String {
+    let mut attributes = match fill {
+        Some(x) if (x.a < 1.0) => format!(r#"fill="{}" fill-opacity="{}""#, rgb(x), number(x.a)),
+        Some(x) => format!(r#"fill="{}""#, rgb(x)),
+        None => r#"fill="none""#.to_string(),
+    };
+    if let Some((thickness, color)) = stroke {
+        let _ = write!(
+            attributes,
+            r#" stroke="{}" stroke-width="{}""#,
+            rgb(color),
+            number(thickness)
+        );
+        if color.a < 1.0 {
+            let _ = write!(attributes, r#" stroke-opacity="{}""#, number(color.a));
+        }
+    }
+    attributes
+}
+fn element(primitive: &Primitive) -> Option<String> {
+    let element = match primitive {
+        Primitive::Polyline {
+            points: list,
+            thickness,
+            color,
+            dash,
+        } => {
+            if list.len() < 2 {
+                return None;
+            }
+            let mut attributes = paint(None, Some((*thickness, *color)));
+            if let Some((on, off)) = dash.get_pattern(*thickness) {
+                let _ = write!(
+                    attributes,
+                    r#" stroke-dasharray="{} {}""#,
+                    number(on),
+                    number(off)
+                );
+            }
+            format!(
+                r#"<polyline points="{}" {} stroke-linejoin="round"/>"#,
+                points(list),
+                attributes
+            )
+        }
+        Primitive::Rectangle { rect, fill, stroke } => format!(
+            r#"<rect x="{}" y="{}" width="{}" height="{}" {}/>"#,
+            number(rect.x),
+            number(rect.y),
+            number(rect.w),
+            number(rect.h),
+            paint(*fill, *stroke)
+        ),
+        Primitive::Polygon {
+            center,
+            sides,
+            radius,
+            rotation,
+            fill,
+            stroke,
+        } => match *sides == scene::CIRCLE_SIDES {
+            true => format!(
+                r#"<circle cx="{}" cy="{}" r="{}" {}/>"#,
+                number(center.x),
+                number(center.y),
+                number(*radius),
+                paint(*fill, *stroke)
+            ),
+            false => format!(
+                r#"<polygon points="{}" {}/>"#,
+                points(&scene::polygon_points(*center, *sides, *radius, *rotation)),
+                paint(*fill, *stroke)
+            ),
+        },
+        Primitive::Text {
+            text,
+            position,
+            size,
+            color,
+            anchor,
+        } => {
+            // Without the font the width is estimated, which only matters for text pulled
+            // back from the left edge
+            let width = scene::estimate_text_width(text, *size);
+            let corner = scene::anchor_text(*position, width, *size, *anchor);
+            let (x, text_anchor) = match anchor {
+                TextAnchor::Left => (corner.x, "start"),
+                TextAnchor::Right => (corner.x + width, "end"),
+                TextAnchor::Top | TextAnchor::Center => (corner.x + width / 2.0, "middle"),
+            };
+            format!(
+                r#"<text x="{}" y="{}" font-family="{}" font-size="{}" fill="{}" text-anchor="{}" dominant-baseline="middle">{}</text>"#,
+                number(x),
+                number(corner.y + size / 2.0),
+                FONT_FAMILY,
+                number(*size),
+                rgb(*color),
+                text_anchor,
+                escape(text)
+            )
+        }
+    };
+    Some(element)
+}
+//
+
+/// A standalone SVG document drawing the scene with vector shapes
+pub fn render(scene: &Scene) -> String {
+    let (width, height) = (number(scene.size.x), number(scene.size.y));
+    let mut document = format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        width, height
+    );
+    document.push('\n');
+    let _ = writeln!(
+        document,
+        r#"<rect width="100%" height="100%" {}/>"#,
+        paint(Some(scene.background), None)
+    );
+    for line in scene.primitives.iter().filter_map(element) {
+        document.push_str(&line);
+        document.push('\n');
+    }
+    document.push_str("</svg>\n");
+    document
+}
+/// Renders the scene and writes it to `path`.
+pub fn export(scene: &Scene, path: &str) -> Result<(), String> {
+    std::fs::write(path, render(scene)).map_err(|x| format!("Could not save {}: {}", path, x))
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::big_number::{BigNumber, BigVec2};
+    use crate::camera::Camera;
+    use crate::entries::EntryList;
+    use crate::grid;
+    use crate::plotter::DashStyle;
+    #[test]
+    fn primitives_become_elements() {
+        let mut scene = Scene::new(vec2(200.0, 100.0));
+        scene.polyline(
+            vec![vec2(0.0, 0.0), vec2(10.5, 20.25)],
+            2.0,
+            Color::new(1.0, 0.0, 0.0, 1.0),
+            DashStyle::Dashed,
+        );
+        scene.rectangle(
+            Rect::new(1.0, 2.0, 3.0, 4.0),
+            Some(Color::new(0.0, 0.0, 1.0, 0.25)),
+            None,
+        );
+        scene.circle(vec2(5.0, 5.0), 3.0, Some(WHITE), Some((2.0, BLACK)));
+        scene.text("a < b", vec2(50.0, 50.0), 18.0, BLACK, TextAnchor::Top);
+        let document = render(&scene);
+        assert!(document.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"200\""));
+        assert!(document.contains(
+            r##"<polyline points="0,0 10.5,20.25" fill="none" stroke="#ff0000" stroke-width="2" stroke-dasharray="14 8""##
+        ));
+        assert!(document.contains(
+            r##"<rect x="1" y="2" width="3" height="4" fill="#0000ff" fill-opacity="0.25"/>"##
+        ));
+        assert!(document.contains(
+            r##"<circle cx="5" cy="5" r="3" fill="#ffffff" stroke="#000000" stroke-width="2"/>"##
+        ));
+        assert!(document.contains("text-anchor=\"middle\""));
+        assert!(document.contains(">a &lt; b</text>"));
+        assert!(document.ends_with("</svg>\n"));
+    }
+    #[test]
+    fn graphs_export_without_a_window() {
+        let camera = Camera {
+            position: BigVec2::new_d(0.0, 0.0),
+            number_distance: BigNumber::new_d(50.0),
+        };
+        let mut entries = EntryList::new();
+        entries.add("sin(x)");
+        entries.add("y < x");
+        let mut scene = Scene::new(vec2(400.0, 300.0));
+        grid::push_to_scene(&grid::compute(&camera, scene.size), &mut scene);
+        entries.push_to_scene(&mut scene, &camera, 0.0);
+        entries.push_legend(&mut scene);
+        let document = render(&scene);
+        // Curve, shaded region, tick labels and the legend
+        assert!(document.contains(r##"stroke="#c74540" stroke-width="3""##));
+        assert!(document.contains(r##"fill="#2e70b3" fill-opacity"##));
+        assert!(document.contains(">2</text>"));
+        assert!(document.contains(">f2</text>"));
+    }
+}
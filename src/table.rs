@@ -0,0 +1,253 @@
+//! The table of values next to the graph, with typed x values and added points.
+use crate::entries::{EntryKind, EntryList, ExpressionEntry};
+use crate::shapes::{Marker, MarkerShape, ShapeCache};
+use crate::trace::format_value;
+use macroquad::prelude::*;
+use macroquad::ui::{hash, root_ui, widgets};
+
+const MAX_ROWS: usize = 200;
+// Characters per column, the ui font is not monospaced so this only lines them up roughly
+const COLUMN_WIDTH: usize = 12;
+const POINT_RADIUS: f32 = 5.0;
+const POINT_COLOR: Color = DARKGRAY;
+/// Where the table is exported to.
+pub const EXPORT_PATH: &str = "table.csv";
+
+/// x against every function for `rows` values of x starting at `start`, plus a list of
+/// points typed in by hand
+pub struct ValueTable {
+    /// The first x
+    pub start: f64,
+    /// Distance between two consecutive x values
+    pub step: f64,
+    /// How many x values are listed
+    pub rows: usize,
+    // What is typed into the start, step and rows boxes, only applied once it parses
+    settings_text: [String; 3],
+    /// Editable (x, y) cells, the last row is always empty so typing into it adds a point
+    pub cells: Vec<[String; 2]>,
+    status: Option<String>,
+}
+
+// Helper Functions
+fn columns(entries: &EntryList) -> Vec<&ExpressionEntry> {
+    entries
+        .entries
+        .iter()
+        .filter(|x| x.style.visible && x.ast.is_ok() && x.get_kind() == EntryKind::Function)
+        .collect()
+}
+fn pad(cells: &[String]) -> String {
+    cells
+        .iter()
+        .map(|x| format!("{:<width$}", x, width = COLUMN_WIDTH))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+//
+
+impl ValueTable {
+    /// A table of x from -5 to 5.
+    pub fn new() -> ValueTable {
+        ValueTable::default()
+    }
+    /// The x value of every row.
+    pub fn get_x_values(&self) -> Vec<f64> {
+        (0..self.rows)
+            .map(|i| self.start + self.step * i as f64)
+            .collect()
+    }
+    /// Rows of the table, x first and then one value per function
+    pub fn get_values(&self, entries: &EntryList) -> Vec<Vec<f64>> {
+        let columns = columns(entries);
+        self.get_x_values()
+            .into_iter()
+            .map(|x| {
+                let mut row = vec![x];
+                row.extend(
+                    columns
+                        .iter()
+                        .map(|entry| entry.evaluate(x, &entries.context)),
+                );
+                row
+            })
+            .collect()
+    }
+    /// Rows where both cells hold a number
+    pub fn get_points(&self) -> Vec<(f64, f64)> {
+        self.cells
+            .iter()
+            .filter_map(|[x, y]| Some((x.trim().parse().ok()?, y.trim().parse().ok()?)))
+            .collect()
+    }
+    /// Adds a typed point above the empty last row.
+    pub fn add_point(&mut self, x: f64, y: f64) {
+        self.cells
+            .insert(self.cells.len() - 1, [x.to_string(), y.to_string()]);
+    }
+    /// The rows as CSV with a header of the function labels. Undefined values are left
+    /// empty
+    pub fn to_csv(&self, entries: &EntryList) -> String {
+        let mut header = vec!["x".to_string()];
+        header.extend(columns(entries).iter().map(|x| x.label.replace(',', " ")));
+        let mut lines = vec![header.join(",")];
+        for row in self.get_values(entries) {
+            let cells = row
+                .iter()
+                .map(|x| match x.is_finite() {
+                    true => x.to_string(),
+                    false => String::new(),
+                })
+                .collect::<Vec<String>>();
+            lines.push(cells.join(","));
+        }
+        lines.join("\n") + "\n"
+    }
+    fn apply_settings_text(&mut self) {
+        let [start, step, rows] = &self.settings_text;
+        if let (Ok(start), Ok(step), Ok(rows)) = (
+            start.trim().parse::<f64>(),
+            step.trim().parse::<f64>(),
+            rows.trim().parse::<usize>(),
+        ) {
+            if start.is_finite() && step.is_finite() && step != 0.0 {
+                self.start = start;
+                self.step = step;
+                self.rows = rows.clamp(1, MAX_ROWS);
+            }
+        }
+    }
+    // Cleared rows disappear and a new empty row shows up once the last one is used
+    fn tidy_cells(&mut self) {
+        let last = self.cells.len().saturating_sub(1);
+        let mut index = 0;
+        self.cells.retain(|[x, y]| {
+            index += 1;
+            index - 1 == last || !(x.is_empty() && y.is_empty())
+        });
+        let filled = match self.cells.last() {
+            Some([x, y]) => !(x.is_empty() && y.is_empty()),
+            None => true,
+        };
+        if filled {
+            self.cells.push([String::new(), String::new()]);
+        }
+    }
+    /// A marker for every typed point.
+    pub fn push_markers(&self, shapes: &mut ShapeCache) {
+        for (x, y) in self.get_points() {
+            shapes.push(Marker {
+                x,
+                y,
+                shape: MarkerShape::Circle,
+                size: POINT_RADIUS,
+                color: POINT_COLOR,
+            });
+        }
+    }
+    /// The typed points as a list expression such as `[(1, 2), (3, 4)]`
+    pub fn get_point_list(&self) -> String {
+        let points = self
+            .get_points()
+            .iter()
+            .map(|(x, y)| format!("({}, {})", x, y))
+            .collect::<Vec<String>>();
+        format!("[{}]", points.join(", "))
+    }
+    /// Draws the window, exporting the table or plotting the points when asked.
+    pub fn create_ui(&mut self, entries: &mut EntryList, position: Vec2) {
+        let mut export = false;
+        let mut plot = false;
+        widgets::Window::new(hash!(), position, vec2(420.0, 400.0))
+            .label("Table")
+            .movable(true)
+            .ui(&mut root_ui(), |ui| {
+                let labels = ["start", "step", "rows"];
+                for (label, text) in labels.iter().zip(self.settings_text.iter_mut()) {
+                    ui.input_text(hash!("table", *label), label, text);
+                }
+                self.apply_settings_text();
+                let mut header = vec!["x".to_string()];
+                header.extend(columns(entries).iter().map(|x| x.label.clone()));
+                ui.label(None, &pad(&header));
+                for row in self.get_values(entries) {
+                    let cells = row.into_iter().map(format_value).collect::<Vec<String>>();
+                    ui.label(None, &pad(&cells));
+                }
+                if ui.button(None, "Export CSV") {
+                    export = true;
+                }
+                if let Some(ref status) = self.status {
+                    ui.label(None, status);
+                }
+                ui.separator();
+                ui.label(None, "Points");
+                for (index, [x, y]) in self.cells.iter_mut().enumerate() {
+                    ui.input_text(hash!("point x", index), "x", x);
+                    ui.input_text(hash!("point y", index), "y", y);
+                }
+                if ui.button(None, "Plot as list") {
+                    plot = true;
+                }
+            });
+        self.tidy_cells();
+        // The new entry can then be styled like any other point list
+        if plot && !self.get_points().is_empty() {
+            entries.add(&self.get_point_list());
+        }
+        if export {
+            self.status = Some(match std::fs::write(EXPORT_PATH, self.to_csv(entries)) {
+                Ok(_) => format!("Saved {}", EXPORT_PATH),
+                Err(x) => format!("Could not save {}: {}", EXPORT_PATH, x),
+            });
+        }
+    }
+}
+impl Default for ValueTable {
+    fn default() -> Self {
+        ValueTable {
+            start: -5.0,
+            step: 1.0,
+            rows: 11,
+            settings_text: ["-5".to_string(), "1".to_string(), "11".to_string()],
+            cells: vec![[String::new(), String::new()]],
+            status: None,
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[test]
+    fn values_follow_the_settings() {
+        let mut entries = EntryList::new();
+        entries.add("x^2");
+        entries.add("1/x");
+        let mut table = ValueTable::new();
+        table.settings_text = ["0".to_string(), "0.5".to_string(), "3".to_string()];
+        table.apply_settings_text();
+        assert_eq!(table.get_x_values(), vec![0.0, 0.5, 1.0]);
+        assert_eq!(table.to_csv(&entries), "x,f1,f2\n0,0,\n0.5,0.25,2\n1,1,1\n");
+        // A step of 0 would repeat the same row
+        table.settings_text[1] = "0".to_string();
+        table.apply_settings_text();
+        assert_eq!(table.step, 0.5);
+    }
+    #[test]
+    fn typed_cells_become_points() {
+        let mut table = ValueTable::new();
+        table.cells[0] = ["1".to_string(), "2".to_string()];
+        table.tidy_cells();
+        assert_eq!(table.cells.len(), 2);
+        table.cells[1][0] = "3".to_string();
+        table.tidy_cells();
+        assert_eq!(table.get_points(), vec![(1.0, 2.0)]);
+        table.add_point(-1.0, 0.5);
+        assert_eq!(table.get_points(), vec![(1.0, 2.0), (-1.0, 0.5)]);
+        table.cells[0] = [String::new(), String::new()];
+        table.tidy_cells();
+        assert_eq!(table.get_points(), vec![(-1.0, 0.5)]);
+        assert_eq!(table.cells.len(), 3);
+        assert_eq!(table.get_point_list(), "[(-1, 0.5)]");
+    }
+}
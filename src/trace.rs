@@ -0,0 +1,247 @@
+//! Tracing a curve with the mouse or the arrow keys and showing the point under it.
+use crate::big_number::{BigNumber, BigVec2};
+use crate::camera::Camera;
+use crate::entries::{EntryKind, EntryList, ExpressionEntry};
+use macroquad::prelude::*;
+use macroquad::ui::root_ui;
+
+// Pixels the cursor may be away from a curve and still snap onto it
+const SNAP_DISTANCE: f32 = 24.0;
+// Pixels moved along the curve per arrow key press
+const STEP_PIXELS: f64 = 4.0;
+const MARKER_RADIUS: f32 = 6.0;
+const LABEL_FONT_SIZE: f32 = 18.0;
+const LABEL_PADDING: f32 = 6.0;
+
+/// A point on a function entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TracePoint {
+    /// The id of the entry
+    pub entry_id: usize,
+    /// Horizontal coordinate
+    pub x: f64,
+    /// The value of the function at x
+    pub y: f64,
+    /// The derivative at x
+    pub slope: f64,
+}
+/// Follows the mouse or the arrow keys along the visible functions.
+#[derive(Default)]
+pub struct Tracer {
+    /// The traced point, none when the cursor is away from every curve
+    pub point: Option<TracePoint>,
+    last_mouse_position: Option<Vec2>,
+}
+
+// Helper Functions
+pub(crate) fn format_value(value: f64) -> String {
+    match value.is_finite() {
+        true => BigNumber::new_d(value).to_string(),
+        false => "undefined".to_string(),
+    }
+}
+fn traceable(entries: &EntryList) -> impl Iterator<Item = &ExpressionEntry> {
+    entries
+        .entries
+        .iter()
+        .filter(|x| x.style.visible && x.ast.is_ok() && x.get_kind() == EntryKind::Function)
+}
+fn locate(entries: &EntryList, entry_id: usize, x: f64) -> Option<TracePoint> {
+    let entry = traceable(entries).find(|entry| entry.id == entry_id)?;
+    let y = entry.evaluate(x, &entries.context);
+    match y.is_finite() {
+        true => Some(TracePoint {
+            entry_id,
+            x,
+            y,
+            slope: entry.slope(x, &entries.context),
+        }),
+        false => None,
+    }
+}
+//
+
+impl Tracer {
+    /// A tracer that has not found a curve yet.
+    pub fn new() -> Tracer {
+        Tracer::default()
+    }
+    /// Nearest curve point to `pixel`, searched one pixel column at a time around it
+    pub fn snap(
+        entries: &EntryList,
+        camera: &Camera,
+        screen: Vec2,
+        pixel: Vec2,
+    ) -> Option<TracePoint> {
+        let mut best: Option<(f32, TracePoint)> = None;
+        let columns = SNAP_DISTANCE as i32;
+        for entry in traceable(entries) {
+            for column in -columns..=columns {
+                let x = camera
+                    .screen_to_world(pixel + vec2(column as f32, 0.0), screen)
+                    .x
+                    .get_deserialized();
+                let y = entry.evaluate(x, &entries.context);
+                if !y.is_finite() {
+                    continue;
+                }
+                let distance = camera
+                    .world_to_screen(&BigVec2::new_d(x, y), screen)
+                    .distance(pixel);
+                let closer = match best {
+                    Some((x, _)) => distance < x,
+                    None => true,
+                };
+                if distance <= SNAP_DISTANCE && closer {
+                    best = Some((
+                        distance,
+                        TracePoint {
+                            entry_id: entry.id,
+                            x,
+                            y,
+                            slope: 0.0,
+                        },
+                    ));
+                }
+            }
+        }
+        let (_, point) = best?;
+        locate(entries, point.entry_id, point.x)
+    }
+    /// Moves a few pixels along the curve, right for a positive `direction`.
+    pub fn step(&mut self, entries: &EntryList, camera: &Camera, direction: f64) {
+        if let Some(ref point) = self.point {
+            let x = point.x + direction * STEP_PIXELS / camera.get_pixels_per_unit();
+            self.point = locate(entries, point.entry_id, x);
+        }
+    }
+    /// Moves to the next curve that is defined at the same x
+    pub fn cycle(&mut self, entries: &EntryList) {
+        let point = match self.point {
+            Some(ref x) => x.clone(),
+            None => return,
+        };
+        let ids = traceable(entries).map(|x| x.id).collect::<Vec<usize>>();
+        let start = ids.iter().position(|x| *x == point.entry_id).unwrap_or(0);
+        for offset in 1..=ids.len() {
+            let id = ids[(start + offset) % ids.len()];
+            if let Some(next) = locate(entries, id, point.x) {
+                self.point = Some(next);
+                return;
+            }
+        }
+    }
+    /// Follows the mouse, the arrow keys and Tab for this frame.
+    pub fn update(&mut self, entries: &EntryList, camera: &Camera, screen: Vec2) {
+        let mouse = Vec2::from(mouse_position());
+        let moved = self.last_mouse_position != Some(mouse);
+        self.last_mouse_position = Some(mouse);
+        if moved && !root_ui().is_mouse_over(mouse) {
+            self.point = Tracer::snap(entries, camera, screen, mouse);
+        } else if let Some(ref point) = self.point {
+            // The entry may have been edited, hidden or removed since the last frame
+            self.point = locate(entries, point.entry_id, point.x);
+        }
+        if is_key_pressed(KeyCode::Left) {
+            self.step(entries, camera, -1.0);
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.step(entries, camera, 1.0);
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.cycle(entries);
+        }
+    }
+    /// Draws the point with its coordinates and slope.
+    pub fn draw(&self, entries: &EntryList, camera: &Camera, screen: Vec2) {
+        let point = match self.point {
+            Some(ref x) => x,
+            None => return,
+        };
+        let entry = match entries.entries.iter().find(|x| x.id == point.entry_id) {
+            Some(x) => x,
+            None => return,
+        };
+        let position = camera.world_to_screen(&BigVec2::new_d(point.x, point.y), screen);
+        draw_circle(position.x, position.y, MARKER_RADIUS, entry.style.color);
+        draw_circle_lines(position.x, position.y, MARKER_RADIUS, 2.0, WHITE);
+        let lines = [
+            entry.label.clone(),
+            format!("({}, {})", format_value(point.x), format_value(point.y)),
+            format!("slope: {}", format_value(point.slope)),
+        ];
+        let width = lines
+            .iter()
+            .map(|x| measure_text(x, None, LABEL_FONT_SIZE as u16, 1.0).width)
+            .fold(0.0, f32::max);
+        let size = vec2(
+            width + LABEL_PADDING * 2.0,
+            LABEL_FONT_SIZE * lines.len() as f32 + LABEL_PADDING * 2.0,
+        );
+        // Keep the box on screen by flipping it to the other side of the marker
+        let mut corner = position + vec2(MARKER_RADIUS * 2.0, MARKER_RADIUS * 2.0);
+        if corner.x + size.x > screen.x {
+            corner.x = position.x - MARKER_RADIUS * 2.0 - size.x;
+        }
+        if corner.y + size.y > screen.y {
+            corner.y = position.y - MARKER_RADIUS * 2.0 - size.y;
+        }
+        draw_rectangle(
+            corner.x,
+            corner.y,
+            size.x,
+            size.y,
+            Color::new(1.0, 1.0, 1.0, 0.9),
+        );
+        draw_rectangle_lines(corner.x, corner.y, size.x, size.y, 1.0, entry.style.color);
+        for (index, line) in lines.iter().enumerate() {
+            draw_text(
+                line,
+                corner.x + LABEL_PADDING,
+                corner.y + LABEL_PADDING + LABEL_FONT_SIZE * (index as f32 + 0.8),
+                LABEL_FONT_SIZE,
+                DARKGRAY,
+            );
+        }
+    }
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    fn setup() -> (EntryList, Camera, Vec2) {
+        let mut entries = EntryList::new();
+        entries.add("x^2");
+        entries.add("2x");
+        let camera = Camera {
+            position: BigVec2::new_d(0.0, 0.0),
+            number_distance: BigNumber::new_d(100.0),
+        };
+        (entries, camera, vec2(1000.0, 800.0))
+    }
+    #[test]
+    fn snaps_to_the_nearest_curve() {
+        let (entries, camera, screen) = setup();
+        // (1.5, 2.2) is closer to y = x^2 than to y = 2x
+        let pixel = camera.world_to_screen(&BigVec2::new_d(1.5, 2.2), screen);
+        let point = Tracer::snap(&entries, &camera, screen, pixel).unwrap();
+        assert_eq!(point.entry_id, entries.entries[0].id);
+        assert!((point.y - point.x * point.x).abs() < 1e-9);
+        assert!((point.slope - 2.0 * point.x).abs() < 1e-9);
+        let far = camera.world_to_screen(&BigVec2::new_d(-3.0, 4.0), screen);
+        assert_eq!(Tracer::snap(&entries, &camera, screen, far), None);
+    }
+    #[test]
+    fn steps_and_cycles() {
+        let (entries, camera, _screen) = setup();
+        let mut tracer = Tracer::new();
+        tracer.point = locate(&entries, entries.entries[0].id, 2.0);
+        tracer.step(&entries, &camera, 1.0);
+        assert_eq!(tracer.point.as_ref().unwrap().x, 2.04);
+        tracer.cycle(&entries);
+        let point = tracer.point.clone().unwrap();
+        assert_eq!(point.entry_id, entries.entries[1].id);
+        assert_eq!((point.x, point.y, point.slope), (2.04, 4.08, 2.0));
+        tracer.cycle(&entries);
+        assert_eq!(tracer.point.unwrap().entry_id, entries.entries[0].id);
+    }
+}